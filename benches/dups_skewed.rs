@@ -0,0 +1,68 @@
+// Times "fx dups" on a tree with skewed group sizes: one giant group of
+// same-sized files among many tiny ones, which is the worst case for
+// balancing the work of refining groups across threads.
+//
+// Run with "cargo bench --bench dups_skewed". To compare implementations,
+// run it on each revision.
+
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    time::{Duration, Instant},
+};
+
+const GIANT_GROUP_MEMBERS: usize = 4_000;
+const TINY_GROUPS: usize = 4_000;
+const FILE_SIZE: usize = 64 * 1024;
+const RUNS: usize = 5;
+
+fn main() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path();
+    populate(root_path);
+
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let status = Command::new(env!("CARGO_BIN_EXE_fx"))
+                .arg("dups")
+                .arg(root_path)
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    println!(
+        "dups_skewed: runs={} min={:?} median={:?} max={:?}",
+        RUNS,
+        times[0],
+        times[RUNS / 2],
+        times[RUNS - 1]
+    );
+}
+
+fn populate(root_path: &Path) {
+    let giant = root_path.join("giant");
+    fs::create_dir(&giant).unwrap();
+    for i in 0..GIANT_GROUP_MEMBERS {
+        // Same size, same head, mid and tail samples, differing only
+        // halfway through, past the mid sample, so that every member goes
+        // through every pass.
+        let mut data = vec![0u8; FILE_SIZE];
+        let half = FILE_SIZE / 2;
+        data[half..half + 8].copy_from_slice(&(i as u64 / 2).to_le_bytes());
+        fs::write(giant.join(i.to_string()), data).unwrap();
+    }
+    let tiny = root_path.join("tiny");
+    fs::create_dir(&tiny).unwrap();
+    for i in 0..TINY_GROUPS {
+        // Pairs of distinct sizes, so each is a group of its own.
+        let data = vec![1u8; FILE_SIZE + 1 + i];
+        fs::write(tiny.join(format!("{i}_a")), &data).unwrap();
+        fs::write(tiny.join(format!("{i}_b")), &data).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+
+use crate::data::{FileType, Meta};
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    fn of(path: &Path) -> anyhow::Result<Self> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let selph = match () {
+            _ if name.ends_with(".tar") => Self::Tar,
+            _ if name.ends_with(".tar.gz") || name.ends_with(".tgz") => {
+                Self::TarGz
+            }
+            _ if name.ends_with(".zip") => Self::Zip,
+            _ => bail!(
+                "Unsupported archive format of path={path:?}. \
+                Expected one of: .tar, .tar.gz, .tgz, .zip"
+            ),
+        };
+        Ok(selph)
+    }
+}
+
+/// Entries of the archive, as read from its index (or, for tar, from the
+/// headers along the stream), without extracting their contents. Paths are
+/// under the archive's own path, as if it was a directory. Entries have no
+/// device or inode, and are taken to be fully allocated, since their
+/// on-disk footprint is that of the archive as a whole.
+///
+/// A failure to read the archive structure ends the entries with the error,
+/// since nothing after it can be trusted.
+pub fn entries(
+    archive_path: &Path,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
+    let format = Format::of(archive_path)?;
+    let file = fs::File::open(archive_path)
+        .context(format!("Failed to open archive path={archive_path:?}"))?;
+    let file = BufReader::new(file);
+    tracing::debug!(?archive_path, ?format, "Reading archive entries.");
+    let entries = match format {
+        Format::Tar => tar_entries(archive_path, file),
+        Format::TarGz => {
+            tar_entries(archive_path, flate2::read::GzDecoder::new(file))
+        }
+        Format::Zip => zip_entries(archive_path, file),
+    };
+    Ok(entries.into_iter())
+}
+
+fn tar_entries(
+    archive_path: &Path,
+    input: impl Read,
+) -> Vec<anyhow::Result<Meta>> {
+    let mut metas = Vec::new();
+    let mut archive = tar::Archive::new(input);
+    let entries = match archive.entries() {
+        Ok(entries) => entries,
+        Err(error) => {
+            metas.push(Err(tar_error(archive_path, error)));
+            return metas;
+        }
+    };
+    for entry_result in entries {
+        let meta = entry_result
+            .map_err(|error| tar_error(archive_path, error))
+            .and_then(|entry| tar_meta(archive_path, &entry));
+        let failed = meta.is_err();
+        metas.push(meta);
+        if failed {
+            break;
+        }
+    }
+    metas
+}
+
+fn tar_meta(
+    archive_path: &Path,
+    entry: &tar::Entry<impl Read>,
+) -> anyhow::Result<Meta> {
+    let header = entry.header();
+    let name = entry
+        .path()
+        .map_err(|error| tar_error(archive_path, error))?;
+    let typ = match header.entry_type() {
+        tar::EntryType::Regular | tar::EntryType::Continuous => {
+            FileType::Regular
+        }
+        tar::EntryType::Directory => FileType::Directory,
+        tar::EntryType::Symlink => FileType::Symlink {
+            dst: entry
+                .link_name()
+                .map_err(|error| tar_error(archive_path, error))?
+                .map(|dst| dst.into_owned())
+                .unwrap_or_default(),
+        },
+        tar::EntryType::Fifo => FileType::Fifo,
+        tar::EntryType::Char => FileType::DevChar,
+        tar::EntryType::Block => FileType::DevBlock,
+        _ => FileType::Unknown,
+    };
+    let meta = synthetic(
+        entry_path(archive_path, &name),
+        typ,
+        entry.size(),
+        header.mode().unwrap_or(0),
+        header.uid().unwrap_or(0),
+        header.gid().unwrap_or(0),
+        header.mtime().unwrap_or(0),
+    );
+    Ok(meta)
+}
+
+fn tar_error(archive_path: &Path, error: io::Error) -> anyhow::Error {
+    anyhow::Error::from(error)
+        .context(format!("Failed to read tar archive path={archive_path:?}"))
+}
+
+fn zip_entries(
+    archive_path: &Path,
+    input: impl Read + io::Seek,
+) -> Vec<anyhow::Result<Meta>> {
+    let context =
+        || format!("Failed to read zip archive path={archive_path:?}");
+    let mut archive = match zip::ZipArchive::new(input) {
+        Ok(archive) => archive,
+        Err(error) => {
+            return vec![Err(anyhow::Error::from(error).context(context()))];
+        }
+    };
+    (0..archive.len())
+        .map(|index| {
+            // Raw, so that contents are not decompressed just to be listed.
+            let entry = archive.by_index_raw(index).context(context())?;
+            let Some(name) = entry.enclosed_name() else {
+                bail!(
+                    "Unsafe entry name={:?} in zip archive path={:?}",
+                    entry.name(),
+                    archive_path
+                );
+            };
+            let typ = if entry.is_dir() {
+                FileType::Directory
+            } else {
+                FileType::Regular
+            };
+            let mtime = entry
+                .last_modified()
+                .and_then(|time| {
+                    chrono::NaiveDate::from_ymd_opt(
+                        time.year().into(),
+                        time.month().into(),
+                        time.day().into(),
+                    )?
+                    .and_hms_opt(
+                        time.hour().into(),
+                        time.minute().into(),
+                        time.second().into(),
+                    )
+                })
+                .map_or(0, |time| time.and_utc().timestamp());
+            let meta = synthetic(
+                entry_path(archive_path, &name),
+                typ,
+                entry.size(),
+                entry.unix_mode().unwrap_or(0),
+                0,
+                0,
+                u64::try_from(mtime).unwrap_or(0),
+            );
+            Ok(meta)
+        })
+        .collect()
+}
+
+/// Entry name, stripped of anything that would lead outside the archive
+/// (root, "..", etc.), joined under the archive's path.
+fn entry_path(archive_path: &Path, name: &Path) -> PathBuf {
+    let relative: PathBuf = name
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect();
+    archive_path.join(relative)
+}
+
+fn synthetic(
+    path: PathBuf,
+    typ: FileType,
+    size: u64,
+    mode: u32,
+    uid: u64,
+    gid: u64,
+    mtime: u64,
+) -> Meta {
+    let mtime = i64::try_from(mtime).unwrap_or(i64::MAX);
+    Meta {
+        path,
+        typ,
+        size,
+        mode,
+        perms: mode & 0o777,
+        uid: u32::try_from(uid).unwrap_or(u32::MAX),
+        gid: u32::try_from(gid).unwrap_or(u32::MAX),
+        dev: 0,
+        ino: 0,
+        nlink: 1,
+        rdev: 0,
+        atime: mtime,
+        mtime,
+        ctime: mtime,
+        blksize: 512,
+        blocks: size.div_ceil(512),
+    }
+}
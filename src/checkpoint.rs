@@ -0,0 +1,145 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::{data::Meta, hash};
+
+/// A file is assumed unchanged if none of these changed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    algo: String,
+    dev: u64,
+    ino: u64,
+    mtime: i64,
+    size: u64,
+}
+
+impl Key {
+    fn new(algo: &str, meta: &Meta) -> Self {
+        Self {
+            algo: algo.to_string(),
+            dev: meta.dev,
+            ino: meta.ino,
+            mtime: meta.mtime,
+            size: meta.size,
+        }
+    }
+}
+
+/// Persisted record of already-computed file hashes, appended to as new
+/// hashes are computed, so that an interrupted run can be resumed without
+/// re-hashing what was already hashed.
+///
+/// Format is one record per line:
+///
+/// ```text
+/// <algo> <dev> <ino> <mtime> <size> <hash as hex>
+/// ```
+pub struct Checkpoint {
+    hashes: HashMap<Key, Vec<u8>>,
+    file: Mutex<fs::File>,
+}
+
+impl Checkpoint {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut hashes = HashMap::new();
+        match fs::File::open(path) {
+            Ok(file) => {
+                for (i, line_result) in
+                    BufReader::new(file).lines().enumerate()
+                {
+                    let line = line_result.context(format!(
+                        "Failed to read checkpoint line from path={path:?}"
+                    ))?;
+                    match parse(&line) {
+                        Ok((key, hash)) => {
+                            hashes.insert(key, hash);
+                        }
+                        // Likely a partial write from an interruption.
+                        Err(error) => {
+                            tracing::warn!(
+                                ?path,
+                                line = i + 1,
+                                ?error,
+                                "Skipping invalid checkpoint record."
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).context(format!(
+                    "Failed to open checkpoint at path={path:?}"
+                ));
+            }
+        }
+        tracing::debug!(?path, hashes = hashes.len(), "Checkpoint loaded.");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .context(format!(
+                "Failed to open checkpoint for writing at path={path:?}"
+            ))?;
+        let selph = Self {
+            hashes,
+            file: Mutex::new(file),
+        };
+        Ok(selph)
+    }
+
+    /// Lookup the hash of the file, computing and recording it if it is
+    /// not already known.
+    pub fn hash<F>(
+        &self,
+        algo: &str,
+        meta: &Meta,
+        compute: F,
+    ) -> anyhow::Result<Vec<u8>>
+    where
+        F: FnOnce() -> anyhow::Result<Vec<u8>>,
+    {
+        let key = Key::new(algo, meta);
+        if let Some(hash) = self.hashes.get(&key) {
+            tracing::trace!(path = ?meta.path, algo, "Checkpoint hit.");
+            return Ok(hash.clone());
+        }
+        let hash = compute()?;
+        let line = format!(
+            "{} {} {} {} {} {}\n",
+            key.algo,
+            key.dev,
+            key.ino,
+            key.mtime,
+            key.size,
+            hash::to_hex(&hash)
+        );
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // Whole line at once, to keep the records intact.
+        file.write_all(line.as_bytes())?;
+        Ok(hash)
+    }
+}
+
+fn parse(line: &str) -> anyhow::Result<(Key, Vec<u8>)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let [algo, dev, ino, mtime, size, hash] = fields[..] else {
+        return Err(anyhow!("Expected 6 fields, found {}", fields.len()));
+    };
+    let key = Key {
+        algo: algo.to_string(),
+        dev: dev.parse()?,
+        ino: ino.parse()?,
+        mtime: mtime.parse()?,
+        size: size.parse()?,
+    };
+    let hash = hash::from_hex(hash)?;
+    Ok((key, hash))
+}
@@ -0,0 +1,161 @@
+use std::path::Path;
+
+use crate::{
+    data::{self, Meta},
+    on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+    users,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+#[command(group(
+    clap::ArgGroup::new("criteria")
+        .required(true)
+        .multiple(true)
+        .args(["world_writable", "setuid", "setgid", "owner", "group"])
+))]
+pub struct Cmd {
+    /// Writable by others. Symlinks are excluded, since their permissions
+    /// are always all-allowing and meaningless.
+    #[clap(long)]
+    world_writable: bool,
+
+    /// With the set-user-ID bit.
+    #[clap(long)]
+    setuid: bool,
+
+    /// With the set-group-ID bit.
+    #[clap(long)]
+    setgid: bool,
+
+    /// Owned by this user, given by name or ID.
+    #[clap(long, value_name = "USER")]
+    owner: Option<String>,
+
+    /// Owned by this group, given by name or ID.
+    #[clap(long, value_name = "GROUP")]
+    group: Option<String>,
+
+    /// Report owners by user and group names, rather than IDs, where
+    /// known.
+    #[clap(long)]
+    names: bool,
+
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        let criteria = Criteria {
+            world_writable: self.world_writable,
+            setuid: self.setuid,
+            setgid: self.setgid,
+            uid: self.owner.as_deref().map(users::uid).transpose()?,
+            gid: self.group.as_deref().map(users::gid).transpose()?,
+        };
+        audit_perms(
+            &root_path,
+            &criteria,
+            self.names,
+            self.null_line_sep,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// What an entry must have to be reported. All given criteria must match.
+#[derive(Debug, Default)]
+pub struct Criteria {
+    pub world_writable: bool,
+    pub setuid: bool,
+    pub setgid: bool,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl Criteria {
+    fn matches(&self, meta: &Meta) -> bool {
+        (!self.world_writable
+            || (meta.perms & 0o002 != 0 && !meta.is_symlink()))
+            && (!self.setuid || meta.mode & 0o4000 != 0)
+            && (!self.setgid || meta.mode & 0o2000 != 0)
+            && self.uid.is_none_or(|uid| meta.uid == uid)
+            && self.gid.is_none_or(|gid| meta.gid == gid)
+    }
+}
+
+/// Entries matching the criteria, ordered by path, with their permission
+/// bits (including setuid, setgid and sticky) in octal, and ownership.
+#[tracing::instrument]
+pub fn audit_perms(
+    root_path: &Path,
+    criteria: &Criteria,
+    names: bool,
+    null_line_sep: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut matches: Vec<Meta> = Vec::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        if criteria.matches(&meta) {
+            matches.push(meta);
+        }
+    }
+    matches.sort_by(Meta::cmp_by_path);
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    let mut user_names = users::Names::users();
+    let mut group_names = users::Names::groups();
+    for meta in &matches {
+        let mode = Value::Text(format!("{:04o}", meta.mode & 0o7777).into());
+        let (owner, group) = if names {
+            (
+                (
+                    "user",
+                    Value::Text(user_names.name(meta.uid).to_owned().into()),
+                ),
+                (
+                    "group",
+                    Value::Text(group_names.name(meta.gid).to_owned().into()),
+                ),
+            )
+        } else {
+            (
+                ("uid", Value::Int(i64::from(meta.uid))),
+                ("gid", Value::Int(i64::from(meta.gid))),
+            )
+        };
+        reporter.row(&[
+            ("mode", mode),
+            owner,
+            group,
+            ("path", Value::Path(&meta.path)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{List, ListItem, ListState},
+};
+
+use crate::cmd::top;
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Print the listing of each directory, depth first, as it would be
+    /// shown, instead of browsing them interactively.
+    #[clap(long)]
+    print: bool,
+
+    #[clap(default_value = ".")]
+    root_path: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let given = &self.root_path;
+        let canonicalized = self
+            .root_path
+            .canonicalize()
+            .context(format!("Failed to canonicalize path={:?}", given))?;
+        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
+        let root_path = canonicalized;
+        if self.print {
+            print(&root_path)?;
+        } else {
+            browse(&root_path)?;
+        }
+        Ok(())
+    }
+}
+
+#[tracing::instrument]
+pub fn browse(root_path: &Path) -> anyhow::Result<()> {
+    let tree = Tree::collect(root_path)?;
+    let mut browser = Browser::new(root_path, &tree);
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+/// Listings of all directories, largest first, separated by blank lines.
+#[tracing::instrument]
+pub fn print(root_path: &Path) -> anyhow::Result<()> {
+    let tree = Tree::collect(root_path)?;
+    let mut out = io::stdout().lock();
+    let mut dirs = vec![root_path.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        if dir != root_path {
+            writeln!(out)?;
+        }
+        let total = tree.size(&dir);
+        writeln!(out, "{}", header(&dir, total))?;
+        let entries = tree.children(&dir);
+        for entry in entries {
+            writeln!(out, "{}", line(entry, total))?;
+        }
+        // Reversed, for the first listed to be popped first.
+        dirs.extend(
+            entries
+                .iter()
+                .rev()
+                .filter(|entry| entry.is_dir)
+                .map(|entry| entry.path.clone()),
+        );
+    }
+    Ok(())
+}
+
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    is_dir: bool,
+}
+
+/// Directory sizes, as aggregated by top, indexed by parent.
+struct Tree {
+    sizes: HashMap<PathBuf, u64>,
+    children: HashMap<PathBuf, Vec<Entry>>,
+}
+
+impl Tree {
+    fn collect(root_path: &Path) -> anyhow::Result<Self> {
+        let files = top::file_sizes(root_path)?;
+        let sizes = top::count_dir_sizes(&files, root_path);
+        let mut children: HashMap<PathBuf, Vec<Entry>> = HashMap::new();
+        let dirs = sizes.iter().map(|(path, size)| (path, *size, true));
+        let files = files.iter().map(|(path, size)| (path, *size, false));
+        for (path, size, is_dir) in dirs.chain(files) {
+            // Root's parent is outside of our view.
+            if path == root_path {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                children.entry(parent.to_owned()).or_default().push(Entry {
+                    path: path.to_owned(),
+                    size,
+                    is_dir,
+                });
+            }
+        }
+        for entries in children.values_mut() {
+            // Largest on top.
+            entries.sort_by(|a, b| {
+                b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path))
+            });
+        }
+        Ok(Self { sizes, children })
+    }
+
+    fn size(&self, dir: &Path) -> u64 {
+        self.sizes.get(dir).copied().unwrap_or(0)
+    }
+
+    fn children(&self, dir: &Path) -> &[Entry] {
+        self.children.get(dir).map(Vec::as_slice).unwrap_or(&[])
+    }
+}
+
+struct Browser<'a> {
+    root: &'a Path,
+    tree: &'a Tree,
+    cwd: PathBuf,
+    state: ListState,
+
+    /// Selections in the parents we descended from, to restore on return.
+    trail: Vec<Option<usize>>,
+}
+
+impl<'a> Browser<'a> {
+    fn new(root: &'a Path, tree: &'a Tree) -> Self {
+        Self {
+            root,
+            tree,
+            cwd: root.to_owned(),
+            state: ListState::default().with_selected(Some(0)),
+            trail: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        loop {
+            terminal.draw(|frame| self.render(frame))?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => break,
+                        KeyCode::Up | KeyCode::Char('k') => {
+                            self.state.select_previous();
+                        }
+                        KeyCode::Down | KeyCode::Char('j') => {
+                            self.state.select_next();
+                        }
+                        KeyCode::Home | KeyCode::Char('g') => {
+                            self.state.select_first();
+                        }
+                        KeyCode::End | KeyCode::Char('G') => {
+                            self.state.select_last();
+                        }
+                        KeyCode::Enter
+                        | KeyCode::Right
+                        | KeyCode::Char('l') => self.descend(),
+                        KeyCode::Left
+                        | KeyCode::Backspace
+                        | KeyCode::Char('h') => self.ascend(),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn descend(&mut self) {
+        let entries = self.tree.children(&self.cwd);
+        if let Some(Entry {
+            path, is_dir: true, ..
+        }) = self.state.selected().and_then(|i| entries.get(i))
+        {
+            self.trail.push(self.state.selected());
+            self.cwd = path.clone();
+            self.state.select(Some(0));
+        }
+    }
+
+    fn ascend(&mut self) {
+        // Don't go above requested root:
+        if self.cwd == self.root {
+            return;
+        }
+        if let Some(parent) = self.cwd.parent() {
+            self.cwd = parent.to_owned();
+            self.state.select(self.trail.pop().unwrap_or(Some(0)));
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let total = self.tree.size(&self.cwd);
+        frame.render_widget(
+            Line::from(self::header(&self.cwd, total))
+                .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+        let items: Vec<ListItem> = self
+            .tree
+            .children(&self.cwd)
+            .iter()
+            .map(|entry| ListItem::new(line(entry, total)))
+            .collect();
+        let list = List::new(items)
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, body, &mut self.state);
+        frame.render_widget(
+            Line::from("↑/↓: select  →/enter: open  ←: back  q: quit"),
+            footer,
+        );
+    }
+}
+
+fn header(dir: &Path, size: u64) -> String {
+    format!("{} ({})", dir.display(), bytesize::ByteSize(size))
+}
+
+/// Size of the entry, in bytes and as a share of its parent, then name.
+fn line(Entry { path, size, is_dir }: &Entry, parent_size: u64) -> String {
+    const BAR_WIDTH: u64 = 20;
+    let filled = size
+        .saturating_mul(BAR_WIDTH)
+        .checked_div(parent_size)
+        .unwrap_or(0);
+    let percent = if parent_size == 0 {
+        0.0
+    } else {
+        *size as f64 * 100.0 / parent_size as f64
+    };
+    let bar: String = (0..BAR_WIDTH)
+        .map(|i| if i < filled { '#' } else { ' ' })
+        .collect();
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix = if *is_dir { "/" } else { "" };
+    format!(
+        "{:>10} {percent:>5.1}% [{bar}] {name}{suffix}",
+        bytesize::ByteSize(*size).to_string()
+    )
+}
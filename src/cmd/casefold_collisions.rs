@@ -0,0 +1,135 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    data, on_error,
+    path::UnicodeForm,
+    report::{self, OutputFormat, Value},
+    root::Root,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Normalize names to this Unicode form before comparing them, so that
+    /// names differing only by form (e.g. "é" composed or decomposed)
+    /// collide too, as they would on filesystems which normalize them.
+    #[clap(long, value_enum, default_value_t)]
+    normalize_unicode: UnicodeForm,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        casefold_collisions(
+            &root_path,
+            self.normalize_unicode,
+            self.null_line_sep,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// Groups of paths which differ, but are equal when case-folded, and so
+/// would collide on a case-insensitive filesystem. Collisions are compared
+/// over whole paths under the root, so "A/b" and "a/B" collide even though
+/// they are in different directories.
+///
+/// Only the shallowest collisions are reported: once "A" and "a" collide,
+/// so do all their same-named descendants, which would only be noise.
+#[tracing::instrument]
+pub fn casefold_collisions(
+    root_path: &Path,
+    form: UnicodeForm,
+    null_line_sep: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut index: HashMap<PathBuf, BTreeSet<PathBuf>> = HashMap::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        // Root itself is whatever it is, we only care about what is in it.
+        // Listed paths, not being under a root, are taken whole.
+        let relative = if data::is_stdin(root_path) {
+            Some(meta.path.as_path())
+        } else {
+            meta.path
+                .strip_prefix(root_path)
+                .ok()
+                .filter(|relative| !relative.as_os_str().is_empty())
+        };
+        if let Some(relative) = relative {
+            index
+                .entry(fold(relative, form))
+                .or_default()
+                .insert(meta.path);
+        }
+    }
+    index.retain(|_, paths| paths.len() > 1);
+    let colliding: HashSet<PathBuf> = index.keys().cloned().collect();
+    let mut groups: Vec<BTreeSet<PathBuf>> = index
+        .into_iter()
+        .filter(|(folded, _)| {
+            !folded.ancestors().skip(1).any(|a| colliding.contains(a))
+        })
+        .map(|(_, paths)| paths)
+        .collect();
+    // Sorted, so the output is the same from run to run.
+    groups.sort();
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for paths in groups {
+        let rows: Vec<Vec<report::Field>> = paths
+            .iter()
+            .map(|path| vec![("path", Value::Path(path))])
+            .collect();
+        reporter.group(&rows)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
+
+/// Full Unicode case folding of each component, in the given normal form,
+/// both before and after folding, since folding can itself denormalize.
+/// Components which are not valid UTF-8 are kept as they are, since their
+/// case is unknowable.
+fn fold(path: &Path, form: UnicodeForm) -> PathBuf {
+    path.components()
+        .map(|component| {
+            let component = component.as_os_str();
+            match component.to_str() {
+                Some(name) => {
+                    let folded =
+                        caseless::default_case_fold_str(&form.apply(name));
+                    OsString::from(form.apply(&folded))
+                }
+                None => component.to_owned(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,160 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    cmd::{dups, top},
+    data::{self, Meta},
+    progress::Progress,
+    report::{self, OutputFormat, Value},
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// For partial file reads. Byte size of samples collected from
+    /// heads, mids and tails of files, as a cheap filter before hashing.
+    #[clap(short, long = "sample", default_value_t = 8192)]
+    sample_size: usize,
+
+    /// Skip the pass by tail samples, between those by mid samples and by
+    /// hashes, e.g. for many small files, for which a seek per file costs
+    /// more than it filters.
+    #[clap(long)]
+    no_tail_sample: bool,
+
+    /// For full-file reads during hashing. Byte size of chunks to read at a time.
+    #[clap(short, long = "chunk", default_value_t = 8192)]
+    chunk_size: usize,
+
+    /// Enable BLAKE3 pass.
+    #[clap(long = "blake3")]
+    enable_blake3_pass: bool,
+
+    /// Enable SHA2-512 pass.
+    #[clap(long = "sha")]
+    enable_sha2_512_pass: bool,
+
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    root_a: PathBuf,
+
+    root_b: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let canonicalize = |given: &Path| -> anyhow::Result<PathBuf> {
+            let canonicalized = given.canonicalize().context(format!(
+                "Failed to canonicalize path={:?}",
+                given
+            ))?;
+            tracing::debug!(
+                ?given,
+                ?canonicalized,
+                "Canonicalized root path."
+            );
+            Ok(canonicalized)
+        };
+        common(
+            &canonicalize(&self.root_a)?,
+            &canonicalize(&self.root_b)?,
+            self.sample_size,
+            !self.no_tail_sample,
+            self.chunk_size,
+            self.enable_blake3_pass,
+            self.enable_sha2_512_pass,
+            self.null_line_sep,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// Groups of files with identical contents, each with members from both
+/// roots. Within a group, members from root A come first.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all)]
+pub fn common(
+    root_a: &Path,
+    root_b: &Path,
+    sample_size: usize,
+    tail_sample: bool,
+    chunk_size: usize,
+    enable_blake3_pass: bool,
+    enable_sha2_512_pass: bool,
+    null_line_sep: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    // Otherwise a file could be on both sides and so match itself.
+    if root_a.starts_with(root_b) || root_b.starts_with(root_a) {
+        bail!(
+            "Roots must not overlap: root_a={:?}, root_b={:?}",
+            root_a,
+            root_b
+        );
+    }
+    let in_a = |m: &Meta| m.path.starts_with(root_a);
+    let is_cross_root =
+        |group: &Vec<Meta>| group.iter().any(in_a) && !group.iter().all(in_a);
+
+    let mut files = top::find_files(root_a, data::FindOpts::default())?;
+    files.extend(top::find_files(root_b, data::FindOpts::default())?);
+    files.retain(|Meta { size, .. }| *size > 0);
+    let mut groups = vec![files];
+    let heads = Arc::new(dups::Heads::default());
+    for (span, f) in dups::groupers(
+        sample_size,
+        tail_sample,
+        false,
+        chunk_size,
+        enable_blake3_pass,
+        enable_sha2_512_pass,
+        None,
+        None,
+        false,
+        Some(heads.clone()),
+        Arc::new(Progress::default()),
+    ) {
+        groups = dups::refine(span, &groups, f)?;
+        // No point in further refining groups confined to one root.
+        groups.retain(is_cross_root);
+        heads.retain(&groups);
+    }
+
+    for group in groups.iter_mut() {
+        group.sort_by(|a, b| {
+            in_a(b).cmp(&in_a(a)).then_with(|| a.cmp_by_path(b))
+        });
+    }
+    groups.sort_by(|a, b| {
+        a.iter().map(|m| &m.path).cmp(b.iter().map(|m| &m.path))
+    });
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for group in &groups {
+        let rows: Vec<Vec<report::Field>> = group
+            .iter()
+            .map(|file| vec![("path", Value::Path(&file.path))])
+            .collect();
+        reporter.group(&rows)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
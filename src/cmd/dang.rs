@@ -3,27 +3,64 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::data;
+use crate::{
+    data, on_error, output,
+    report::{self, OutputFormat, Value},
+};
 
-#[derive(clap::Args, Debug)]
+#[derive(clap::Args, Debug, serde::Serialize)]
 pub struct Cmd {
     /// Print targets with links.
     /// e.g.: "/a/b/c -> ../foo/bar" instead of just "/a/b/c".
     #[clap(short = 't', long = "target")]
     print_with_target: bool,
 
+    /// Style of quoting targets, by --target.
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires = "print_with_target"
+    )]
+    quote_style: output::QuoteStyle,
+
     /// Separate output lines/records with a null (\0)
     /// instead of linefeed (\n) character.
     #[clap(short = 'Z', long = "null")]
     null_line_sep: bool,
 
+    /// Encoding of outputted paths.
+    /// Raw writes exact path bytes and so requires null separation (-Z).
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("raw", "null_line_sep")
+    )]
+    output_encoding: output::Encoding,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    /// A symlink root is examined itself, rather than followed.
+    /// Or "-" to read a list of paths from stdin instead,
+    /// separated by nulls, if there are any, or else by linefeeds.
+    /// Of the listed paths, only the symlinks are examined.
     #[clap(default_value = ".")]
     root_path: PathBuf,
 }
 
 impl Cmd {
     pub fn run(&self) -> anyhow::Result<()> {
-        dang(&self.root_path, self.print_with_target, self.null_line_sep)?;
+        dang(
+            &self.root_path,
+            self.print_with_target,
+            self.quote_style,
+            self.null_line_sep,
+            self.output_encoding,
+            self.format,
+        )?;
         Ok(())
     }
 }
@@ -32,37 +69,60 @@ impl Cmd {
 pub fn dang(
     root_path: &Path,
     print_with_target: bool,
+    quote_style: output::QuoteStyle,
     null_line_sep: bool,
+    output_encoding: output::Encoding,
+    format: OutputFormat,
 ) -> anyhow::Result<()> {
     let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
-    for (src, dst) in dangling_symlinks(root_path)? {
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            // Targets are printed quoted, to delineate them from the arrow.
+            quote: print_with_target.then_some(quote_style),
+            output_encoding,
+            field_sep: " -> ".to_string(),
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for link_result in dangling_symlinks(root_path)? {
+        let (src, dst) = link_result?;
+        let mut row = vec![("path", Value::Path(&src))];
         if print_with_target {
-            print!("{src:?} -> {dst:?}{sep}");
-        } else {
-            print!("{}{sep}", src.display());
+            row.push(("target", Value::Path(&dst)));
         }
+        reporter.row(&row)?;
     }
+    reporter.finish()?;
     Ok(())
 }
 
 fn dangling_symlinks(
     root_path: &Path,
-) -> anyhow::Result<impl Iterator<Item = (PathBuf, PathBuf)>> {
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<(PathBuf, PathBuf)>>>
+{
     let dangling_symlinks =
-        data::find_symlinks(root_path)?.filter(|(src, _)| {
+        data::find_symlinks(root_path)?.filter_map(|link_result| {
+            let (src, dst) = match link_result {
+                Ok(link) => link,
+                Err(error) => return Some(Err(error)),
+            };
             match src.canonicalize() {
-                Ok(_) => false,
-                Err(error) => match error.kind() {
-                    io::ErrorKind::NotFound => true,
-                    _ => {
-                        tracing::error!(
-                            %error,
-                            path = ?src,
-                            "Failed to canonicalize symlink path."
-                        );
-                        false
-                    }
-                },
+                Ok(_) => None,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                    Some(Ok((src, dst)))
+                }
+                Err(error) => {
+                    let error = anyhow::Error::from(error)
+                        .context(format!("path={src:?}"));
+                    on_error::handle::<()>(
+                        Err(error),
+                        "Failed to canonicalize symlink path.",
+                    )
+                    .err()
+                    .map(Err)
+                }
             }
         });
     Ok(dangling_symlinks)
@@ -0,0 +1,243 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    cmd::snap,
+    data::{FileType, Meta},
+    report::{self, OutputFormat, Value},
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// Do not report changes in this field, e.g. in mtime, since changes
+    /// of timestamps alone (like from touch) are often noise. Repeatable.
+    #[clap(long = "ignore-field", value_enum, value_name = "FIELD")]
+    ignored_fields: Vec<Field>,
+
+    /// Earlier snapshot, as written by snap.
+    snapshot_a: PathBuf,
+
+    /// Later snapshot, as written by snap.
+    snapshot_b: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        diff(
+            &self.snapshot_a,
+            &self.snapshot_b,
+            &self.ignored_fields,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// Fields of which modifications are reported: those which users care
+/// about, i.e. not the ones which only locate the entry (dev, ino), nor
+/// those which follow from others (blocks, nlink).
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Field {
+    Type,
+    Target,
+    Size,
+    #[value(alias = "perms")]
+    Mode,
+    Uid,
+    Gid,
+    Atime,
+    Mtime,
+    Ctime,
+}
+
+impl Field {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Type => "type",
+            Self::Target => "target",
+            Self::Size => "size",
+            Self::Mode => "mode",
+            Self::Uid => "uid",
+            Self::Gid => "gid",
+            Self::Atime => "atime",
+            Self::Mtime => "mtime",
+            Self::Ctime => "ctime",
+        }
+    }
+
+    fn value(&self, m: &Meta) -> String {
+        match self {
+            Self::Type => m.typ.name().to_string(),
+            Self::Target => match &m.typ {
+                FileType::Symlink { dst } => dst.to_string_lossy().into(),
+                _ => String::new(),
+            },
+            Self::Size => m.size.to_string(),
+            Self::Mode => format!("{:04o}", m.mode & 0o7777),
+            Self::Uid => m.uid.to_string(),
+            Self::Gid => m.gid.to_string(),
+            Self::Atime => m.atime.to_string(),
+            Self::Mtime => m.mtime.to_string(),
+            Self::Ctime => m.ctime.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Added,
+    Deleted,
+    Moved,
+    Modified,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Deleted => "deleted",
+            Self::Moved => "moved",
+            Self::Modified => "modified",
+        }
+    }
+}
+
+/// One row of the report: what changed about which path. For a move, the
+/// path is the new one and the field is "path", from the old one. For a
+/// modification, there is one change per field which differs.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Change {
+    path: PathBuf,
+    kind: Kind,
+    field: &'static str,
+    before: String,
+    after: String,
+}
+
+/// Changes in metadata between two snapshots, by path, in path order:
+/// entries added, deleted, moved (a path deleted and another added, of the
+/// same dev and inode, as well as of the same type, size and mtime, since
+/// inodes of deleted entries get reused) and modified (each differing
+/// field, unless ignored, with its values before and after).
+#[tracing::instrument]
+pub fn diff(
+    snapshot_a: &Path,
+    snapshot_b: &Path,
+    ignored: &[Field],
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let a = load(snapshot_a)?;
+    let b = load(snapshot_b)?;
+    let fields: Vec<Field> = Field::value_variants()
+        .iter()
+        .filter(|field| !ignored.contains(field))
+        .copied()
+        .collect();
+    let mut changes: Vec<Change> = Vec::new();
+
+    // Deleted, unless found moved below, by dev and inode. Synthetic
+    // entries (e.g. of archives) have neither, so cannot be told moved.
+    let mut deleted: HashMap<(u64, u64), Vec<&Meta>> = HashMap::new();
+    for meta in a.values().filter(|meta| !b.contains_key(&meta.path)) {
+        deleted.entry((meta.dev, meta.ino)).or_default().push(meta);
+    }
+    for meta in b.values() {
+        let Some(before) = a.get(&meta.path) else {
+            let moved_from = (meta.dev != 0 || meta.ino != 0)
+                .then(|| {
+                    let candidates =
+                        deleted.get_mut(&(meta.dev, meta.ino))?;
+                    let i = candidates.iter().position(|before| {
+                        before.typ.name() == meta.typ.name()
+                            && before.size == meta.size
+                            && before.mtime == meta.mtime
+                    })?;
+                    Some(candidates.swap_remove(i))
+                })
+                .flatten();
+            match moved_from {
+                Some(before) => {
+                    changes.push(Change {
+                        path: meta.path.clone(),
+                        kind: Kind::Moved,
+                        field: "path",
+                        before: before.path.to_string_lossy().into(),
+                        after: meta.path.to_string_lossy().into(),
+                    });
+                    modifications(before, meta, &fields, &mut changes);
+                }
+                None => changes.push(whole(Kind::Added, meta)),
+            }
+            continue;
+        };
+        modifications(before, meta, &fields, &mut changes);
+    }
+    for meta in deleted.into_values().flatten() {
+        changes.push(whole(Kind::Deleted, meta));
+    }
+    changes.sort();
+
+    let mut reporter = report::new(format, report::Options::default());
+    for change in &changes {
+        reporter.row(&[
+            ("kind", Value::Text(change.kind.name().into())),
+            ("field", Value::Text(change.field.into())),
+            ("before", Value::Text(Cow::from(&change.before))),
+            ("after", Value::Text(Cow::from(&change.after))),
+            ("path", Value::Path(&change.path)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
+
+fn load(path: &Path) -> anyhow::Result<BTreeMap<PathBuf, Meta>> {
+    let (header, entries) = snap::read(path)?;
+    tracing::debug!(?path, ?header, "Loading snapshot.");
+    entries
+        .map(|meta_result| meta_result.map(|meta| (meta.path.clone(), meta)))
+        .collect()
+}
+
+fn whole(kind: Kind, meta: &Meta) -> Change {
+    Change {
+        path: meta.path.clone(),
+        kind,
+        field: "",
+        before: String::new(),
+        after: String::new(),
+    }
+}
+
+/// Changes of the given fields, of those which differ.
+fn modifications(
+    a: &Meta,
+    b: &Meta,
+    fields: &[Field],
+    changes: &mut Vec<Change>,
+) {
+    for field in fields {
+        let (before, after) = (field.value(a), field.value(b));
+        if before != after {
+            changes.push(Change {
+                path: b.path.clone(),
+                kind: Kind::Modified,
+                field: field.name(),
+                before,
+                after,
+            });
+        }
+    }
+}
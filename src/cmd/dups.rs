@@ -1,33 +1,234 @@
 use std::{
-    collections::HashMap,
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
     ffi::OsString,
     fs,
+    hash::Hasher,
     io::{self, Read, Seek, SeekFrom},
+    os::{fd::AsRawFd, unix::ffi::OsStrExt},
     path::{Path, PathBuf},
-    sync::Arc,
+    process,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use anyhow::Context;
-use rayon::iter::{
-    IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator,
-};
+use anyhow::{Context, bail};
+use bytesize::ByteSize;
+use dashmap::DashMap;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
-    data::{self, Meta},
-    hash,
+    checkpoint::Checkpoint,
+    cmd::stale,
+    data::{self, FileType, Meta},
+    events::{Event, Events, Stats},
+    hash, on_error, output,
+    progress::Progress,
+    report::{self, OutputFormat, Value},
+    root::Root,
+    slow,
+    spill::{self, Spill},
+    xattr_cache,
 };
 
-#[derive(clap::Args, Debug)]
+/// What the content of a symlink is taken to be.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkContent {
+    /// None. Symlinks are skipped.
+    #[default]
+    Skip,
+
+    /// That of the regular file it resolves to, with which it is grouped.
+    /// Each target is included at most once, so a symlink to a file already
+    /// in the tree is not reported as its dup.
+    ResolvedFile,
+
+    /// Its target path, as stored in it, unresolved. Symlinks are grouped
+    /// among themselves, by pointing to the same path.
+    TargetPath,
+}
+
+/// How certain it is that members of reported groups are the same.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Certainty {
+    /// By a fast, non-cryptographic hash (xxh3), which different contents
+    /// could, if only in theory, share.
+    #[default]
+    Probabilistic,
+
+    /// By a cryptographic hash too (BLAKE3), as with --blake3.
+    Cryptographic,
+
+    /// By comparing contents byte by byte, after hashing. Files left out
+    /// along the way are reported too, with why, as "anomalies", by
+    /// --format json.
+    Exact,
+}
+
+/// Why a candidate file was left out of the groups.
+#[derive(Clone, Copy, Debug)]
+enum Anomaly {
+    /// Its size is no longer what it was when found.
+    SizeChanged,
+
+    /// It failed to be read, as handled per --on-error.
+    ReadError,
+
+    /// Its contents differ from those of all the others it was hashed the
+    /// same as.
+    VerifySplit,
+}
+
+impl Anomaly {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::SizeChanged => "size-changed",
+            Self::ReadError => "read-error",
+            Self::VerifySplit => "verify-split",
+        }
+    }
+}
+
+/// Files left out of the groups, with why, as noted by the passes.
+#[derive(Default)]
+struct Anomalies(Mutex<Vec<(PathBuf, Anomaly)>>);
+
+impl Anomalies {
+    fn add(&self, path: &Path, anomaly: Anomaly) {
+        tracing::debug!(?path, ?anomaly, "Leaving out.");
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((path.to_owned(), anomaly));
+    }
+
+    /// By path, since they are noted in whatever order the passes run.
+    fn into_sorted(self) -> Vec<(PathBuf, Anomaly)> {
+        let mut anomalies =
+            self.0.into_inner().unwrap_or_else(|e| e.into_inner());
+        anomalies.sort_by(|(a, _), (b, _)| a.cmp(b));
+        anomalies
+    }
+}
+
+#[derive(clap::Args, Debug, serde::Serialize)]
 pub struct Cmd {
     /// For partial file reads. Byte size of samples collected from
-    /// heads and mids of files, as a cheap filter before hashing.
+    /// heads, mids and tails of files, as a cheap filter before hashing.
     #[clap(short, long = "sample", default_value_t = 8192)]
     sample_size: usize,
 
+    /// Skip the pass by tail samples, between those by mid samples and by
+    /// hashes, e.g. for many small files, for which a seek per file costs
+    /// more than it filters. Files too big to hash are still sampled.
+    #[clap(long, conflicts_with_all = ["content_prefix", "grouper_cmd"])]
+    no_tail_sample: bool,
+
+    /// Cap on memory for samples held at once, e.g. "1GiB". If all samples
+    /// would take more, each is reduced to a hash of itself, which filters
+    /// just as well, at some CPU cost.
+    #[clap(long)]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    max_sample_memory: Option<ByteSize>,
+
+    /// Soft limit on memory used for candidate files, e.g. "2GiB". Beyond
+    /// it, they are spilled to a temporary file, then refined in batches of
+    /// roughly this much each, by size. Estimated roughly, so leave some
+    /// headroom. The groups found are still all held, for the report.
+    #[clap(long)]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    max_memory: Option<ByteSize>,
+
     /// For full-file reads during hashing. Byte size of chunks to read at a time.
     #[clap(short, long = "chunk", default_value_t = 8192)]
     chunk_size: usize,
 
+    /// Approximate mode: group files by a hash of only their first this
+    /// many bytes (e.g. "1MiB"), regardless of their sizes, skipping all
+    /// other passes. Files shorter than it are compared whole. Finds files
+    /// which are probably the same, like truncated copies of a big file,
+    /// much faster than full hashing, but files which differ only after
+    /// the prefix are reported as duplicates too.
+    #[clap(
+        long,
+        value_name = "SIZE",
+        conflicts_with_all = [
+            "max_sample_memory",
+            "enable_blake3_pass",
+            "enable_sha2_512_pass",
+            "checkpoint",
+            "xattr_cache",
+        ]
+    )]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    content_prefix: Option<ByteSize>,
+
+    /// Custom mode: group files by the output of this program, run on each
+    /// file's path as its sole argument, instead of by contents. E.g. a
+    /// script printing a hash of an audio file's stream without its tags.
+    /// Files for which it exits with failure are excluded, as per
+    /// --on-error. Runs as many at once as there are CPUs.
+    #[clap(
+        long,
+        value_name = "PROGRAM",
+        conflicts_with_all = [
+            "content_prefix",
+            "max_sample_memory",
+            "enable_blake3_pass",
+            "enable_sha2_512_pass",
+            "checkpoint",
+            "xattr_cache",
+        ]
+    )]
+    grouper_cmd: Option<PathBuf>,
+
+    /// After grouping by size, order the groups largest first, so that the
+    /// passes which read contents get to the biggest potential reclaims
+    /// first, as seen in the progress line. Roughly so, since members are
+    /// processed in parallel. The report is ordered the same either way.
+    #[clap(long, conflicts_with_all = ["content_prefix", "grouper_cmd"])]
+    big_first: bool,
+
+    /// Certainty with which members of reported groups are the same,
+    /// determining the passes which group them.
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["content_prefix", "grouper_cmd"]
+    )]
+    certainty: Certainty,
+
+    /// Shorthand for "--certainty exact": after all hashing, compare the
+    /// members of each group byte by byte, reading a chunk at a time and
+    /// stopping at the first difference.
+    #[clap(
+        short,
+        long,
+        conflicts_with_all = ["certainty", "content_prefix", "grouper_cmd"]
+    )]
+    exact: bool,
+
     /// Enable BLAKE3 pass.
     #[clap(long = "blake3")]
     enable_blake3_pass: bool,
@@ -36,16 +237,75 @@ pub struct Cmd {
     #[clap(long = "sha")]
     enable_sha2_512_pass: bool,
 
+    /// Files bigger than this, e.g. "10GiB", are not hashed, but only
+    /// compared by samples of their heads, mids and tails, to bound the
+    /// time spent reading. Groups of them are probable duplicates only, so
+    /// rows are flagged with how they matched: "sampled" or "hashed".
+    #[clap(
+        long,
+        value_name = "SIZE",
+        conflicts_with_all = ["content_prefix", "grouper_cmd", "group_by_dir"]
+    )]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    max_hash_size: Option<ByteSize>,
+
+    /// Record computed hashes in this file and reuse those already recorded
+    /// there, so that an interrupted run can be resumed. Files are assumed
+    /// unchanged if their dev, inode, mtime and size are unchanged.
+    #[clap(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Store computed hashes in extended attributes of the hashed files
+    /// (as "user.fx.<algo>") and reuse those already stored there, if the
+    /// file's mtime and size are unchanged. Falls back to plain hashing
+    /// where extended attributes are unsupported.
+    #[clap(long)]
+    xattr_cache: bool,
+
+    /// What to take as the content of symlinks, by which to group them.
+    #[clap(long, value_enum, default_value_t)]
+    symlink_content: SymlinkContent,
+
+    /// Same as --symlink-content=resolved-file.
+    #[clap(long, conflicts_with = "symlink_content")]
+    resolve_symlinks: bool,
+
     /// Skip all directories with this name.
     /// (This option can be used multiple times)
+    /// Appended to those listed in the FX_SKIP_DIRS environment variable,
+    /// separated by colons.
     #[clap(long)]
+    #[serde(serialize_with = "crate::config::os_strings")]
     skip_dir: Vec<OsString>,
 
     /// Skip all paths starting with this prefix.
+    /// Relative prefixes are relative to the root path.
     /// (This option can be used multiple times)
+    /// Appended to those listed in the FX_SKIP_PREFIXES environment
+    /// variable, separated by colons.
     #[clap(long)]
     skip_prefix: Vec<PathBuf>,
 
+    /// Only include paths starting with this prefix, e.g. to walk "/", but
+    /// only "/home" and "/var" under it. Relative prefixes are relative to
+    /// the root path. The skip options still apply under them.
+    /// (This option can be used multiple times)
+    #[clap(long)]
+    only_prefix: Vec<PathBuf>,
+
+    /// Skip all paths matching patterns in this file of gitignore syntax.
+    /// Patterns are relative to the root path.
+    /// (This option can be used multiple times)
+    #[clap(long)]
+    ignore_file: Vec<PathBuf>,
+
+    /// Only include entries on filesystems mounted under the root, but not
+    /// the root's own filesystem. Useful for analyzing what is mounted under
+    /// a path (e.g. a container's overlay and bind mounts), while ignoring
+    /// the contents of the base filesystem.
+    #[clap(long)]
+    mounts_only: bool,
+
     /// Separate output lines/records with a null (\0)
     /// instead of linefeed (\n) character.
     #[clap(short = 'Z', long = "null")]
@@ -55,180 +315,1340 @@ pub struct Cmd {
     #[clap(short, long = "quote")]
     quote_paths: bool,
 
-    #[clap(default_value = ".")]
-    root_path: PathBuf,
+    /// Style of quoting, by --quote.
+    #[clap(long, value_enum, default_value_t, requires = "quote_paths")]
+    quote_style: output::QuoteStyle,
+
+    /// Encoding of outputted paths.
+    /// Raw writes exact path bytes and so requires null separation (-Z).
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("raw", "null_line_sep")
+    )]
+    output_encoding: output::Encoding,
+
+    /// Report real paths, i.e. with all symlinks resolved, at the cost of
+    /// an extra syscall per path. Paths which fail to resolve are skipped.
+    #[clap(long)]
+    real_paths: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    /// Shorthand for "--format json": an array of groups, each an array of
+    /// its members' records. Paths are JSON strings, so need no quoting nor
+    /// null separation, and those not valid UTF-8 come with their bytes.
+    #[clap(long, conflicts_with_all = ["format", "null_line_sep"])]
+    json: bool,
+
+    /// Report only this many groups.
+    /// Groups are ordered by how many bytes deleting all but one member of
+    /// each would reclaim, largest first.
+    #[clap(short, long = "lim")]
+    limit: Option<usize>,
+
+    /// Reverse the order of groups, i.e. smallest first.
+    /// Applied before --lim, so together they select the smallest groups.
+    #[clap(short, long)]
+    reverse: bool,
+
+    /// Report only groups which would reclaim at least this much,
+    /// e.g. "100MB" or "1GiB".
+    #[clap(long)]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    min_reclaimable: Option<ByteSize>,
+
+    /// Measure reclaimable bytes by blocks allocated on disk, rather than
+    /// apparent file sizes. Matters for sparse and compressed files.
+    #[clap(long)]
+    disk_usage: bool,
+
+    /// Count members which are hardlinks of each other, i.e. share a
+    /// (device, inode), once when measuring reclaimable bytes, since
+    /// deleting all but one of them frees nothing.
+    #[clap(long)]
+    count_blocks_once: bool,
+
+    /// Do not report hardlinks, i.e. paths of the same (device, inode), as
+    /// duplicates of each other: list only one path of each file, and omit
+    /// groups of only one file's paths. Either way, each file is read once.
+    #[clap(long)]
+    skip_hardlinks: bool,
+
+    /// Report only duplicates on the same device as each other, i.e. those
+    /// which could be replaced by hardlinks, which cannot cross devices.
+    #[clap(long)]
+    same_device: bool,
+
+    /// Print a status line to stderr every this many seconds, with counts
+    /// of entries scanned and bytes hashed so far.
+    #[clap(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    progress_interval: Option<u64>,
+
+    /// Instead of the groups, report pairs of directories between which
+    /// duplicates are found, with counts of such groups and their size
+    /// (of one copy each). Spots whole directories worth reconciling.
+    #[clap(long)]
+    group_by_dir: bool,
+
+    /// Add a column with the size of each file, which is the same for all
+    /// members of a group.
+    #[clap(long, conflicts_with = "group_by_dir")]
+    show_size: bool,
+
+    /// Instead of the groups, report a flat list of all their members,
+    /// largest first, each with its size and the number of copies of its
+    /// contents (itself included). Shows which big files are duplicated.
+    #[clap(long, conflicts_with_all = ["group_by_dir", "show_size"])]
+    flat: bool,
+
+    /// Add a column with the id of each group: the hex of the key by which
+    /// the last pass grouped its members, i.e. their content hash, by
+    /// default. Stable from run to run, so groups can be referred to by it.
+    #[clap(long, conflicts_with_all = ["group_by_dir", "flat"])]
+    print_group_id: bool,
+
+    /// Add a "dedup" column classifying each group by how its duplicates
+    /// could be reclaimed: "reflink", if all members are on the same
+    /// filesystem of a type which can share extents between files (btrfs,
+    /// xfs, bcachefs, ocfs2), so they could be deduplicated in place,
+    /// keeping their identities; or "delete", otherwise. Heuristic, by the
+    /// mount table, since e.g. xfs may have been made without reflinks.
+    #[clap(long, conflicts_with_all = ["group_by_dir", "flat"])]
+    across_mounts_report: bool,
+
+    /// Instead of the groups, report the files which could be deduplicated
+    /// in place, each with the source it would share extents with: the
+    /// first member of its group, of those classified "reflink", as by
+    /// --across-mounts-report. Nothing is changed without --yes.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "group_by_dir",
+            "flat",
+            "show_size",
+            "print_group_id",
+            "across_mounts_report",
+        ]
+    )]
+    reflink: bool,
+
+    /// Deduplicate the files reported by --reflink, by FIDEDUPERANGE, which
+    /// has the kernel check that the contents are the same, so a file
+    /// changed since it was hashed is never lost.
+    #[clap(long, requires = "reflink")]
+    yes: bool,
+
+    /// Report using human-readable (i.e. aggregated) units.
+    #[clap(short = 'H', long)]
+    human: bool,
+
+    /// Write a stream of events, as NDJSON, to this file (e.g. /dev/fd/3),
+    /// as the run proceeds, for a supervising process to follow it by:
+    /// file_found, pass_finished, group_finalized and done, with stats.
+    /// Separate from the report, which is unaffected.
+    #[clap(long, value_name = "PATH")]
+    emit_events: Option<PathBuf>,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
 }
 
 impl Cmd {
     pub fn run(&self) -> anyhow::Result<()> {
-        let given = &self.root_path;
-        let canonicalized = self
-            .root_path
-            .canonicalize()
-            .context(format!("Failed to canonicalize path={:?}", given))?;
-        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
-        let root_path = canonicalized;
-        dups(
-            &root_path,
-            self.sample_size,
-            self.chunk_size,
-            self.enable_blake3_pass,
-            self.enable_sha2_512_pass,
-            &self.skip_dir[..],
-            &self.skip_prefix[..],
-            self.quote_paths,
-            self.null_line_sep,
-        )?;
+        let given = &self.root.root_path;
+        let root_path = if data::is_stdin(given) {
+            given.clone()
+        } else {
+            let canonicalized = crate::path::canonicalize_or_normalize(
+                given,
+                Path::canonicalize,
+            );
+            tracing::debug!(
+                ?given,
+                ?canonicalized,
+                "Canonicalized root path."
+            );
+            canonicalized
+        };
+        let skip_dirs: Vec<OsString> = data::env_list(data::ENV_SKIP_DIRS)
+            .into_iter()
+            .chain(self.skip_dir.iter().cloned())
+            .collect();
+        let skip_prefixes: Vec<PathBuf> =
+            data::env_list(data::ENV_SKIP_PREFIXES)
+                .into_iter()
+                .map(PathBuf::from)
+                .chain(self.skip_prefix.iter().cloned())
+                .collect();
+        let find_opts = data::FindOpts {
+            skip_dirs,
+            skip_prefixes,
+            only_prefixes: self.only_prefix.clone(),
+            ignore_files: self.ignore_file.clone(),
+            mounts_only: self.mounts_only,
+            ..Default::default()
+        };
+        let report_opts = report::Options {
+            quote: self.quote_paths.then_some(self.quote_style),
+            output_encoding: self.output_encoding,
+            record_sep: if self.null_line_sep { "\0" } else { "\n" }
+                .to_string(),
+            real_paths: self.real_paths,
+            human: self.human,
+            ..Default::default()
+        };
+        let opts = Options {
+            sample_size: self.sample_size,
+            tail_sample: !self.no_tail_sample,
+            max_sample_memory: self.max_sample_memory.map(|s| s.as_u64()),
+            max_memory: self.max_memory.map(|size| size.as_u64()),
+            chunk_size: self.chunk_size,
+            content_prefix: self.content_prefix.map(|size| size.as_u64()),
+            grouper_cmd: self.grouper_cmd.clone(),
+            big_first: self.big_first,
+            enable_blake3_pass: self.enable_blake3_pass
+                || self.certainty == Certainty::Cryptographic,
+            enable_sha2_512_pass: self.enable_sha2_512_pass,
+            compare_bytes: self.exact || self.certainty == Certainty::Exact,
+            max_hash_size: self.max_hash_size.map(|size| size.as_u64()),
+            checkpoint: self.checkpoint.clone(),
+            xattr_cache: self.xattr_cache,
+            symlink_content: if self.resolve_symlinks {
+                SymlinkContent::ResolvedFile
+            } else {
+                self.symlink_content
+            },
+            format: if self.json {
+                OutputFormat::Json
+            } else {
+                self.format
+            },
+            report_limit: self.limit,
+            reverse: self.reverse,
+            min_reclaimable: self.min_reclaimable.map(|size| size.as_u64()),
+            disk_usage: self.disk_usage,
+            count_blocks_once: self.count_blocks_once,
+            skip_hardlinks: self.skip_hardlinks,
+            same_device: self.same_device,
+            progress_interval: self
+                .progress_interval
+                .map(Duration::from_secs),
+            group_by_dir: self.group_by_dir,
+            show_size: self.show_size,
+            flat: self.flat,
+            print_group_id: self.print_group_id,
+            across_mounts_report: self.across_mounts_report,
+            reflink: self.reflink.then_some(self.yes),
+            emit_events: self.emit_events.clone(),
+        };
+        dups(&root_path, find_opts, report_opts, &opts)?;
         Ok(())
     }
 }
 
+/// What to find duplicates by and what to report of them, beyond the walk
+/// and the presentation, as resolved from the command line.
+#[derive(Debug)]
+pub struct Options {
+    pub sample_size: usize,
+    pub tail_sample: bool,
+    pub max_sample_memory: Option<u64>,
+    pub max_memory: Option<u64>,
+    pub chunk_size: usize,
+    pub content_prefix: Option<u64>,
+    pub grouper_cmd: Option<PathBuf>,
+    pub big_first: bool,
+    pub enable_blake3_pass: bool,
+    pub enable_sha2_512_pass: bool,
+    pub compare_bytes: bool,
+    pub max_hash_size: Option<u64>,
+    pub checkpoint: Option<PathBuf>,
+    pub xattr_cache: bool,
+    pub symlink_content: SymlinkContent,
+    pub format: OutputFormat,
+    pub report_limit: Option<usize>,
+    pub reverse: bool,
+    pub min_reclaimable: Option<u64>,
+    pub disk_usage: bool,
+    pub count_blocks_once: bool,
+    pub skip_hardlinks: bool,
+    pub same_device: bool,
+    pub progress_interval: Option<Duration>,
+    pub group_by_dir: bool,
+    pub show_size: bool,
+    pub flat: bool,
+    pub print_group_id: bool,
+    pub across_mounts_report: bool,
+    /// Whether to also deduplicate, if reporting what could be reflinked.
+    pub reflink: Option<bool>,
+    pub emit_events: Option<PathBuf>,
+}
+
 #[tracing::instrument(skip_all)]
 pub fn dups(
     root_path: &Path,
-    sample_size: usize,
-    chunk_size: usize,
-    enable_blake3_pass: bool,
-    enable_sha2_512_pass: bool,
-    skip_dirs: &[OsString],
-    skip_prefixes: &[PathBuf],
-    quote_paths: bool,
-    null_line_sep: bool,
+    find_opts: data::FindOpts,
+    report_opts: report::Options,
+    opts: &Options,
 ) -> anyhow::Result<()> {
-    let mut groups: Vec<Vec<Meta>> = {
+    let &Options {
+        max_memory,
+        max_hash_size,
+        ref checkpoint,
+        symlink_content,
+        format,
+        report_limit,
+        reverse,
+        min_reclaimable,
+        disk_usage,
+        count_blocks_once,
+        same_device,
+        progress_interval,
+        group_by_dir,
+        show_size,
+        flat,
+        print_group_id,
+        across_mounts_report,
+        reflink,
+        compare_bytes,
+        ref emit_events,
+        ..
+    } = opts;
+    let events = emit_events.as_deref().map(Events::create).transpose()?;
+    let progress = Arc::new(Progress::default());
+    let _progress_printer =
+        progress_interval.map(|interval| progress.print_every(interval));
+    // Grouped by target path, apart from files, so not refined any further.
+    let mut link_groups: Vec<Vec<Meta>> = Vec::new();
+    let candidates: Candidates = {
         let span = tracing::debug_span!("find_files");
         let _span_guard = span.enter();
-        let files: Vec<Meta> = data::find_while_skipping(
-            root_path,
-            skip_dirs.to_vec(),
-            skip_prefixes.to_vec(),
-        )?
-        .filter_map(|result| match result {
-            Err(error) => {
-                tracing::error!(?error, "Failure while finding files.");
-                None
+        let mut candidates = Candidates::new(max_memory);
+        // Of the files found, for symlinks to them not to add them again.
+        let mut inodes: HashSet<(u64, u64)> = HashSet::new();
+        let mut symlinks: Vec<Meta> = Vec::new();
+        for meta_result in on_error::filter(
+            data::find_with(root_path, find_opts)?,
+            "Failure while finding files.",
+        ) {
+            let meta = meta_result?;
+            progress.add_scanned(1);
+            if meta.is_regular_file() {
+                if let Some(events) = &events {
+                    events.emit(&Event::FileFound {
+                        path: meta.path.as_path().into(),
+                        size: meta.size,
+                    })?;
+                }
+                if symlink_content == SymlinkContent::ResolvedFile {
+                    inodes.insert((meta.dev, meta.ino));
+                }
+                candidates.add(meta)?;
+            } else if symlink_content != SymlinkContent::Skip
+                && meta.is_symlink()
+            {
+                symlinks.push(meta);
             }
-            Ok(m) => Some(m),
-        })
-        .filter(Meta::is_regular_file)
-        .filter(|Meta { size, .. }| *size > 0)
-        .collect();
-        tracing::debug!(files = files.len(), "Found.");
-        vec![files]
+        }
+        if symlink_content == SymlinkContent::TargetPath {
+            let mut by_target: BTreeMap<PathBuf, Vec<Meta>> = BTreeMap::new();
+            for link in symlinks {
+                if let FileType::Symlink { dst } = &link.typ {
+                    by_target.entry(dst.clone()).or_default().push(link);
+                }
+            }
+            link_groups = by_target
+                .into_values()
+                .filter(|group| group.len() > 1)
+                .collect();
+        } else if symlink_content == SymlinkContent::ResolvedFile {
+            for link in symlinks {
+                match Meta::from_path_resolved(&link.path) {
+                    Ok(meta) if meta.is_regular_file() => {
+                        if inodes.insert((meta.dev, meta.ino)) {
+                            candidates.add(meta)?;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(error) => {
+                        // Most likely dangling, which is not our concern.
+                        tracing::debug!(
+                            ?error,
+                            path = ?link.path,
+                            "Failed to resolve symlink."
+                        );
+                    }
+                }
+            }
+        }
+        tracing::debug!(files = candidates.len(), "Found.");
+        candidates
     };
+    if same_device {
+        link_groups = by_device(link_groups);
+    }
+
+    let checkpoint = checkpoint
+        .as_deref()
+        .map(|path| Checkpoint::open(path).map(Arc::new))
+        .transpose()?;
+    let mut groups: Vec<Vec<Meta>> = Vec::new();
+    let mut group_ids: GroupIds = HashMap::new();
+    let anomalies = Anomalies::default();
+    for batch in candidates.into_batches()? {
+        let (batch_groups, batch_ids) = refine_files(
+            batch?,
+            opts,
+            checkpoint.clone(),
+            &progress,
+            events.as_ref(),
+            &anomalies,
+        )?;
+        groups.extend(batch_groups);
+        group_ids.extend(batch_ids);
+    }
+    for group in link_groups {
+        if print_group_id {
+            for link in &group {
+                if let FileType::Symlink { dst } = &link.typ {
+                    let id = hash::to_hex(dst.as_os_str().as_bytes());
+                    group_ids.insert(link.path.clone(), id);
+                }
+            }
+        }
+        groups.push(group);
+    }
+
+    if let Some(min) = min_reclaimable {
+        groups.retain(|group| {
+            reclaimable(group, disk_usage, count_blocks_once) >= min
+        });
+    }
+    sort(&mut groups, disk_usage, count_blocks_once);
+    if reverse {
+        groups.reverse();
+    }
+    if let Some(n) = report_limit {
+        groups.truncate(n);
+    }
+    let groups_count = groups.len();
+    let files_count: usize = groups.iter().map(Vec::len).sum();
+    if let Some(events) = &events {
+        for group in &groups {
+            events.emit(&Event::GroupFinalized {
+                size: group.first().map_or(0, |m| m.size),
+                members: group
+                    .iter()
+                    .map(|m| m.path.as_path().into())
+                    .collect(),
+            })?;
+        }
+    }
+
+    let span = tracing::debug_span!("report");
+    let _span_guard = span.enter();
+    tracing::debug!(groups = groups.len(), "Reporting.");
+    let mut reporter = report::new(format, report_opts);
+    // How the file matched its group, if any were too big to hash.
+    let matched = |file: &Meta| {
+        max_hash_size
+            .map(|max| if file.size > max { "sampled" } else { "hashed" })
+    };
+    if group_by_dir {
+        for ((dir_a, dir_b), (count, size)) in dir_pairs(&groups) {
+            reporter.row(&[
+                ("groups", Value::Int(i64::try_from(count)?)),
+                ("size", Value::Size(size)),
+                ("dir_a", Value::Path(&dir_a)),
+                ("dir_b", Value::Path(&dir_b)),
+            ])?;
+        }
+    } else if flat {
+        let mut files: Vec<(&Meta, usize)> = groups
+            .iter()
+            .flat_map(|group| group.iter().map(|file| (file, group.len())))
+            .collect();
+        files.sort_by(|(a, _), (b, _)| {
+            b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path))
+        });
+        for (file, copies) in files {
+            let mut row = vec![
+                ("size", Value::Size(file.size)),
+                ("copies", Value::Int(i64::try_from(copies)?)),
+            ];
+            if let Some(matched) = matched(file) {
+                row.push(("match", Value::Text(matched.into())));
+            }
+            row.push(("path", Value::Path(&file.path)));
+            reporter.row(&row)?;
+        }
+    } else if let Some(dedupe) = reflink {
+        let mounts = mount_table();
+        let mut deduplicable = 0;
+        for group in &groups {
+            // Symlink groups are of paths, not contents.
+            if !group.iter().all(Meta::is_regular_file)
+                || !reflinkable(group, &mounts)
+            {
+                continue;
+            }
+            let Some((src, rest)) = group.split_first() else {
+                continue;
+            };
+            // Other paths of the same files already share everything.
+            let mut inodes: HashSet<(u64, u64)> =
+                HashSet::from([(src.dev, src.ino)]);
+            for dest in rest {
+                if !inodes.insert((dest.dev, dest.ino)) {
+                    continue;
+                }
+                if dedupe {
+                    dedupe_file(&src.path, &dest.path, dest.size)?;
+                    tracing::info!(src = ?src.path, dest = ?dest.path, "Deduplicated.");
+                }
+                deduplicable += 1;
+                reporter.row(&[
+                    ("source", Value::Path(&src.path)),
+                    ("path", Value::Path(&dest.path)),
+                ])?;
+            }
+        }
+        if !dedupe && deduplicable > 0 {
+            eprintln!(
+                "Warning: Nothing deduplicated. Pass --yes to deduplicate \
+                the {deduplicable} files listed."
+            );
+        }
+    } else {
+        let mounts = if across_mounts_report {
+            mount_table()
+        } else {
+            Vec::new()
+        };
+        for group in groups {
+            let dedup = across_mounts_report.then(|| {
+                if reflinkable(&group, &mounts) {
+                    "reflink"
+                } else {
+                    "delete"
+                }
+            });
+            // TODO Lister grouper outputs.
+            let rows: Vec<Vec<report::Field>> = group
+                .iter()
+                .map(|file| {
+                    let mut row = Vec::new();
+                    if print_group_id {
+                        let id = group_ids
+                            .get(&file.path)
+                            .map_or("", String::as_str);
+                        row.push(("group_id", Value::Text(id.into())));
+                    }
+                    if show_size {
+                        row.push(("size", Value::Size(file.size)));
+                    }
+                    if let Some(matched) = matched(file) {
+                        row.push(("match", Value::Text(matched.into())));
+                    }
+                    if let Some(dedup) = dedup {
+                        row.push(("dedup", Value::Text(dedup.into())));
+                    }
+                    row.push(("path", Value::Path(&file.path)));
+                    row
+                })
+                .collect();
+            reporter.group(&rows)?;
+        }
+    }
+    if compare_bytes {
+        let anomalies = anomalies.into_sorted();
+        let rows: Vec<Vec<report::Field>> = anomalies
+            .iter()
+            .map(|(path, anomaly)| {
+                vec![
+                    ("path", Value::Path(path)),
+                    ("reason", Value::Text(anomaly.as_str().into())),
+                ]
+            })
+            .collect();
+        reporter.aside("anomalies", &rows)?;
+    }
+    reporter.finish()?;
+    if let Some(events) = &events {
+        events.emit(&Event::Done {
+            stats: Stats {
+                scanned: progress.scanned(),
+                hashed: progress.hashed(),
+                groups: groups_count,
+                files: files_count,
+            },
+        })?;
+    }
 
-    // TODO First pass should be group by (dev, inode) - which is 100%
-    //      certainty, but is a special case in that even though it is
-    //      most certain it is also cheapest.
+    Ok(())
+}
 
-    for (span, f) in groupers(
+/// Group ids of members, by path.
+type GroupIds = HashMap<PathBuf, String>;
+
+/// Groups of duplicates among the given files, by all passes, with the ids
+/// of their members by the last, if they are to be printed. Files left out
+/// for failing to be read, or for the byte-by-byte comparison, are noted.
+fn refine_files(
+    files: Vec<Meta>,
+    opts: &Options,
+    checkpoint: Option<Arc<Checkpoint>>,
+    progress: &Arc<Progress>,
+    events: Option<&Events>,
+    anomalies: &Anomalies,
+) -> anyhow::Result<(Vec<Vec<Meta>>, GroupIds)> {
+    let &Options {
         sample_size,
+        tail_sample,
+        max_sample_memory,
         chunk_size,
+        content_prefix,
+        ref grouper_cmd,
+        big_first,
         enable_blake3_pass,
         enable_sha2_512_pass,
-    ) {
-        groups = refine(span, &groups, f)?;
+        compare_bytes,
+        max_hash_size,
+        xattr_cache,
+        skip_hardlinks,
+        same_device,
+        print_group_id,
+        ..
+    } = opts;
+    let mut groups = vec![files];
+    if same_device {
+        groups = by_device(groups);
     }
+    // Paths of the same file are certainly duplicates of each other, so
+    // only one of them needs to go through the passes, which it does for
+    // all of them.
+    let links = split_links(&mut groups);
+
+    // Samples of all members of a pass are held at once.
+    let sample_memory: u64 = groups
+        .iter()
+        .flatten()
+        .map(|m| m.size.min(sample_size as u64))
+        .sum();
+    let hash_samples =
+        max_sample_memory.is_some_and(|max| sample_memory > max);
+    tracing::debug!(sample_memory, hash_samples, "Estimated samples.");
 
-    // TODO Optional last pass should be byte-by-bye comparisson.
+    // Hash states of heads are larger than hashes of samples, so are not
+    // kept when memory is short.
+    let heads = (!hash_samples).then(|| Arc::new(Heads::default()));
+    let passes = match (content_prefix, grouper_cmd) {
+        (Some(prefix_size), _) => {
+            vec![content_prefix_grouper(usize::try_from(prefix_size)?)]
+        }
+        (None, Some(program)) => vec![command_grouper(program.clone())],
+        (None, None) => groupers(
+            sample_size,
+            tail_sample,
+            hash_samples,
+            chunk_size,
+            enable_blake3_pass,
+            enable_sha2_512_pass,
+            max_hash_size,
+            checkpoint,
+            xattr_cache,
+            heads.clone(),
+            progress.clone(),
+        ),
+    };
+    // Of all members, only by the last pass, which is the most exact.
+    let mut group_ids: GroupIds = HashMap::new();
+    let last_pass = passes.len().saturating_sub(1);
+    for (pass, (span, f)) in passes.into_iter().enumerate() {
+        let keyed = refine_keyed(span, &groups, f, Some(anomalies))?;
+        if print_group_id && pass == last_pass {
+            group_ids = keyed
+                .iter()
+                .flat_map(|(id, group)| {
+                    let id = hash::to_hex(id);
+                    group.iter().map(move |m| (m.path.clone(), id.clone()))
+                })
+                .collect();
+        }
+        groups = keyed.into_iter().map(|(_, group)| group).collect();
+        if let Some(events) = events {
+            events.emit(&Event::PassFinished {
+                pass,
+                groups: groups.len(),
+            })?;
+        }
+        if let Some(heads) = &heads {
+            heads.retain(&groups);
+        }
+        // The first pass is by size, so all members of a group have the
+        // same size.
+        if big_first && pass == 0 {
+            groups.sort_by_key(|group| {
+                Reverse(group.first().map_or(0, |m| m.size))
+            });
+        }
+    }
+    if compare_bytes {
+        groups =
+            compare_contents(&groups, chunk_size, max_hash_size, anomalies)?;
+    }
+    rejoin_links(
+        &mut groups,
+        links,
+        skip_hardlinks,
+        print_group_id.then_some(&mut group_ids),
+    );
+    Ok((groups, group_ids))
+}
+
+/// Files to find duplicates among: held in memory, unless they take more
+/// than the budget, in which case all of them are spilled to disk.
+struct Candidates {
+    budget: Option<u64>,
+    footprint: u64,
+    files: Vec<Meta>,
+    spill: Option<Spill>,
+}
+
+impl Candidates {
+    fn new(budget: Option<u64>) -> Self {
+        Self {
+            budget,
+            footprint: 0,
+            files: Vec::new(),
+            spill: None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.files.len() + self.spill.as_ref().map_or(0, Spill::len)
+    }
+
+    fn add(&mut self, file: Meta) -> anyhow::Result<()> {
+        // Empty files are all the same, but nothing is gained from that.
+        if file.size == 0 {
+            return Ok(());
+        }
+        if let Some(spill) = &mut self.spill {
+            return spill.push(&file);
+        }
+        self.footprint += spill::footprint(&file);
+        self.files.push(file);
+        if self.budget.is_some_and(|b| self.footprint > b) {
+            self.switch()?;
+        }
+        Ok(())
+    }
+
+    fn switch(&mut self) -> anyhow::Result<()> {
+        eprintln!(
+            "Warning: memory budget exceeded after {} files. \
+            Spilling them to disk, to be refined in batches.",
+            self.files.len()
+        );
+        let mut spill = Spill::create()?;
+        for file in std::mem::take(&mut self.files) {
+            spill.push(&file)?;
+        }
+        self.spill = Some(spill);
+        Ok(())
+    }
 
-    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
-    tracing::debug!(groups = groups.len(), ?sep, "Reporting.");
+    /// Batches to be refined apart, each with all the files of its sizes,
+    /// so that none of their groups is split between batches.
+    fn into_batches(
+        self,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<Vec<Meta>>>>>
+    {
+        match (self.spill, self.budget) {
+            (Some(spill), Some(budget)) => {
+                Ok(Box::new(spill.batches(budget, |m| m.size)?))
+            }
+            _ => Ok(Box::new(std::iter::once(Ok(self.files)))),
+        }
+    }
+}
+
+/// Count and size of duplicate groups shared by each pair of directories,
+/// largest first. A directory pairs with itself if it has more than one
+/// member of a group.
+fn dir_pairs(groups: &[Vec<Meta>]) -> Vec<((PathBuf, PathBuf), (u64, u64))> {
+    let mut pairs: HashMap<(PathBuf, PathBuf), (u64, u64)> = HashMap::new();
     for group in groups {
-        // TODO Lister grouper outputs.
-        for file in group {
-            if quote_paths {
-                print!("{:?}{}", &file.path, sep);
-            } else {
-                print!("{}{}", &file.path.display(), sep);
+        let mut dirs: Vec<&Path> =
+            group.iter().filter_map(|m| m.path.parent()).collect();
+        dirs.sort();
+        let mut group_pairs: HashSet<(&Path, &Path)> = HashSet::new();
+        for (i, a) in dirs.iter().enumerate() {
+            for b in &dirs[i + 1..] {
+                group_pairs.insert((a, b));
             }
         }
-        println!();
+        let size = group.first().map_or(0, |m| m.size);
+        for (a, b) in group_pairs {
+            let (count, bytes) =
+                pairs.entry((a.to_owned(), b.to_owned())).or_default();
+            *count += 1;
+            *bytes += size;
+        }
+    }
+    let mut pairs: Vec<((PathBuf, PathBuf), (u64, u64))> =
+        pairs.into_iter().collect();
+    pairs.sort_by(
+        |(dirs_a, (count_a, bytes_a)), (dirs_b, (count_b, bytes_b))| {
+            bytes_b
+                .cmp(bytes_a)
+                .then_with(|| count_b.cmp(count_a))
+                .then_with(|| dirs_a.cmp(dirs_b))
+        },
+    );
+    pairs
+}
+
+/// Groups split by comparing the contents of their members byte by byte,
+/// each against the first member of each split so far, which, after
+/// hashing, is almost always the only one. Members whose sizes changed
+/// since they were found are left out, without being compared.
+#[tracing::instrument(skip_all)]
+fn compare_contents(
+    groups: &[Vec<Meta>],
+    chunk_size: usize,
+    max_size: Option<u64>,
+    anomalies: &Anomalies,
+) -> anyhow::Result<Vec<Vec<Meta>>> {
+    tracing::debug!(groups = groups.len(), "Comparing bytes.");
+    let splits: Vec<Vec<Vec<Meta>>> = groups
+        .par_iter()
+        .map(|group| {
+            // Too big to even hash, let alone compare.
+            if group
+                .first()
+                .is_some_and(|m| max_size.is_some_and(|max| m.size > max))
+            {
+                return Ok(vec![group.clone()]);
+            }
+            let mut splits: Vec<Vec<Meta>> = Vec::new();
+            'members: for member in group {
+                let size = fs::metadata(&member.path)
+                    .map(|m| m.len())
+                    .with_context(|| format!("file={:?}", member.path));
+                match on_error::handle(size, "Failed to recheck size.")? {
+                    Some(size) if size == member.size => {}
+                    Some(size) => {
+                        tracing::warn!(
+                            path = ?member.path,
+                            found = member.size,
+                            now = size,
+                            "Size changed since found."
+                        );
+                        anomalies.add(&member.path, Anomaly::SizeChanged);
+                        continue 'members;
+                    }
+                    None => {
+                        anomalies.add(&member.path, Anomaly::ReadError);
+                        continue 'members;
+                    }
+                }
+                for split in splits.iter_mut() {
+                    let first = &split[0].path;
+                    let same = same_contents(first, &member.path, chunk_size)
+                        .with_context(|| {
+                            format!("a={:?} b={:?}", first, member.path)
+                        });
+                    match on_error::handle(same, "Failed to compare.")? {
+                        Some(true) => {
+                            split.push(member.clone());
+                            continue 'members;
+                        }
+                        Some(false) => {}
+                        None => {
+                            anomalies.add(&member.path, Anomaly::ReadError);
+                            continue 'members;
+                        }
+                    }
+                }
+                splits.push(vec![member.clone()]);
+            }
+            // Alone in their splits, so no longer in any group.
+            if splits.len() > 1 {
+                for split in splits.iter().filter(|split| split.len() == 1) {
+                    anomalies.add(&split[0].path, Anomaly::VerifySplit);
+                }
+            }
+            Ok(splits)
+        })
+        .collect::<anyhow::Result<_>>()?;
+    let groups = splits
+        .into_iter()
+        .flatten()
+        .filter(|group| group.len() > 1)
+        .collect();
+    Ok(groups)
+}
+
+fn same_contents(a: &Path, b: &Path, chunk_size: usize) -> io::Result<bool> {
+    let mut file_a = fs::File::open(a)?;
+    let mut file_b = fs::File::open(b)?;
+    let mut buf_a = vec![0u8; chunk_size];
+    let mut buf_b = vec![0u8; chunk_size];
+    loop {
+        let n_a = read_full(a, &mut file_a, &mut buf_a)?;
+        let n_b = read_full(b, &mut file_b, &mut buf_b)?;
+        if buf_a[..n_a] != buf_b[..n_b] {
+            return Ok(false);
+        }
+        if n_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Read until the buffer is full or the file ends, so that chunks of two
+/// files line up, however the reads happen to be cut short.
+fn read_full(
+    path: &Path,
+    file: &mut fs::File,
+    buf: &mut [u8],
+) -> io::Result<usize> {
+    let mut read_total = 0;
+    while read_total < buf.len() {
+        let read_result =
+            slow::time("read", path, || file.read(&mut buf[read_total..]));
+        match read_result {
+            Ok(0) => break,
+            Ok(read_current) => read_total += read_current,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read_total)
+}
+
+/// Filesystem types which can share extents between files, i.e. reflink.
+const REFLINK_FS_TYPES: &[&str] = &["btrfs", "xfs", "bcachefs", "ocfs2"];
+
+struct Mount {
+    point: PathBuf,
+    source: String,
+    fs_type: String,
+}
+
+/// Best-effort: empty if mounts cannot be determined.
+fn mount_table() -> Vec<Mount> {
+    let Ok(mounts) =
+        fs::read_to_string("/proc/self/mounts").inspect_err(|error| {
+            tracing::debug!(?error, "Failed to read mounts.")
+        })
+    else {
+        return Vec::new();
+    };
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let source = fields.next()?.to_string();
+            let point = stale::unescape_mount_field(fields.next()?);
+            let fs_type = fields.next()?.to_string();
+            Some(Mount {
+                point,
+                source,
+                fs_type,
+            })
+        })
+        .collect()
+}
+
+/// Whether all members are on the same reflink-capable filesystem. Same by
+/// its source, rather than by device number, which differs between btrfs
+/// subvolumes, while reflinks between them work.
+fn reflinkable(group: &[Meta], mounts: &[Mount]) -> bool {
+    let filesystem_of = |path: &Path| {
+        // Longest mount point prefix is the one the path is on.
+        mounts
+            .iter()
+            .filter(|mount| path.starts_with(&mount.point))
+            .max_by_key(|mount| mount.point.components().count())
+            .map(|mount| (&mount.source, &mount.fs_type))
+    };
+    let mut filesystems = group.iter().map(|m| filesystem_of(&m.path));
+    match filesystems.next() {
+        Some(Some(first @ (_, fs_type))) => {
+            REFLINK_FS_TYPES.contains(&fs_type.as_str())
+                && filesystems.all(|filesystem| filesystem == Some(first))
+        }
+        _ => false,
     }
+}
 
+/// struct file_dedupe_range of linux/fs.h, without the destinations which
+/// follow it.
+#[repr(C)]
+struct FileDedupeRange {
+    src_offset: u64,
+    src_length: u64,
+    dest_count: u16,
+    reserved1: u16,
+    reserved2: u32,
+}
+
+/// struct file_dedupe_range_info of linux/fs.h.
+#[repr(C)]
+struct FileDedupeRangeInfo {
+    dest_fd: i64,
+    dest_offset: u64,
+    bytes_deduped: u64,
+    status: i32,
+    reserved: u32,
+}
+
+/// A range with the one destination it is deduplicated into.
+#[repr(C)]
+struct Dedupe {
+    range: FileDedupeRange,
+    info: FileDedupeRangeInfo,
+}
+
+const FILE_DEDUPE_RANGE_SAME: i32 = 0;
+const FILE_DEDUPE_RANGE_DIFFERS: i32 = 1;
+
+/// Share the extents of src with dest, by FIDEDUPERANGE, in steps, since
+/// filesystems cap how much is deduplicated per call (e.g. btrfs, at
+/// 16 MiB). The kernel compares the contents first, so nothing changes if
+/// they differ, which is an error, as they were found to be the same.
+fn dedupe_file(src: &Path, dest: &Path, size: u64) -> anyhow::Result<()> {
+    let src_file = fs::File::open(src)
+        .context(format!("Failed to open file for reading: {src:?}"))?;
+    // Older kernels require the destination to be open for writing.
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .open(dest)
+        .context(format!("Failed to open file for writing: {dest:?}"))?;
+    let mut offset: u64 = 0;
+    while offset < size {
+        let mut dedupe = Dedupe {
+            range: FileDedupeRange {
+                src_offset: offset,
+                src_length: size - offset,
+                dest_count: 1,
+                reserved1: 0,
+                reserved2: 0,
+            },
+            info: FileDedupeRangeInfo {
+                dest_fd: dest_file.as_raw_fd().into(),
+                dest_offset: offset,
+                bytes_deduped: 0,
+                status: 0,
+                reserved: 0,
+            },
+        };
+        let request = libc::_IOWR::<FileDedupeRange>(0x94, 54);
+        // SAFETY: dedupe is laid out as the kernel expects: the range,
+        // followed by as many infos as its dest_count says. It outlives
+        // the call, which writes only to the info.
+        let result = unsafe {
+            libc::ioctl(src_file.as_raw_fd(), request, &raw mut dedupe)
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error()).context(format!(
+                "Failed to deduplicate dest={dest:?} against src={src:?}"
+            ));
+        }
+        let info = dedupe.info;
+        match info.status {
+            FILE_DEDUPE_RANGE_SAME if info.bytes_deduped > 0 => {
+                offset += info.bytes_deduped;
+            }
+            FILE_DEDUPE_RANGE_SAME => {
+                bail!("No progress deduplicating dest={dest:?} at {offset}");
+            }
+            FILE_DEDUPE_RANGE_DIFFERS => {
+                bail!(
+                    "Contents changed since they were compared: \
+                    dest={dest:?}, src={src:?}"
+                );
+            }
+            errno => {
+                return Err(io::Error::from_raw_os_error(-errno)).context(
+                    format!(
+                        "Failed to deduplicate dest={dest:?} against \
+                        src={src:?}"
+                    ),
+                );
+            }
+        }
+    }
     Ok(())
 }
 
-fn refine<F>(
+/// Other paths of the files which have more than one among the members,
+/// by (dev, inode), each after the one which is kept in its group. The one
+/// kept is the first by path, so that it is the same from run to run.
+fn split_links(groups: &mut [Vec<Meta>]) -> HashMap<(u64, u64), Vec<Meta>> {
+    let mut links: HashMap<(u64, u64), Vec<Meta>> = HashMap::new();
+    for group in groups.iter_mut() {
+        group.sort_by(Meta::cmp_by_path);
+        let mut inodes: HashSet<(u64, u64)> = HashSet::new();
+        let (kept, others): (Vec<Meta>, Vec<Meta>) = std::mem::take(group)
+            .into_iter()
+            .partition(|m| inodes.insert((m.dev, m.ino)));
+        *group = kept;
+        if others.is_empty() {
+            continue;
+        }
+        for m in others {
+            links.entry((m.dev, m.ino)).or_default().push(m);
+        }
+        // Inodes are never shared across groups, which are at most split
+        // by device.
+        for m in group.iter() {
+            if let Some(others) = links.get_mut(&(m.dev, m.ino)) {
+                others.insert(0, m.clone());
+            }
+        }
+    }
+    tracing::debug!(files = links.len(), "Found hardlinked.");
+    links
+}
+
+/// Reverse of split_links: other paths join the groups of the ones kept,
+/// and those of files which are in no group form their own, identified by
+/// the (dev, inode) they share. Unless hardlinks are skipped, in which case
+/// they are all dropped.
+fn rejoin_links(
+    groups: &mut Vec<Vec<Meta>>,
+    mut links: HashMap<(u64, u64), Vec<Meta>>,
+    skip: bool,
+    mut group_ids: Option<&mut HashMap<PathBuf, String>>,
+) {
+    if skip {
+        return;
+    }
+    for group in groups.iter_mut() {
+        let mut joining: Vec<Meta> = Vec::new();
+        for m in group.iter() {
+            let Some(paths) = links.remove(&(m.dev, m.ino)) else {
+                continue;
+            };
+            match group_ids.as_deref_mut() {
+                Some(ids) if ids.contains_key(&m.path) => {
+                    let id = ids[&m.path].clone();
+                    for other in &paths[1..] {
+                        ids.insert(other.path.clone(), id.clone());
+                    }
+                }
+                _ => {}
+            }
+            joining.extend(paths.into_iter().skip(1));
+        }
+        group.append(&mut joining);
+    }
+    for ((dev, ino), paths) in links {
+        if let Some(ids) = group_ids.as_deref_mut() {
+            let key = [dev.to_le_bytes(), ino.to_le_bytes()].concat();
+            let id = hash::to_hex(&key);
+            for m in &paths {
+                ids.insert(m.path.clone(), id.clone());
+            }
+        }
+        groups.push(paths);
+    }
+}
+
+/// Groups split by the device of their members, so that those of each
+/// device are grouped apart from the others.
+fn by_device(groups: Vec<Vec<Meta>>) -> Vec<Vec<Meta>> {
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            let mut devices: BTreeMap<u64, Vec<Meta>> = BTreeMap::new();
+            for member in group {
+                devices.entry(member.dev).or_default().push(member);
+            }
+            devices.into_values().filter(|group| group.len() > 1)
+        })
+        .collect()
+}
+
+/// Largest reclaimable first, ties broken by paths, so that the order is
+/// the same from run to run.
+fn sort(groups: &mut [Vec<Meta>], disk_usage: bool, count_blocks_once: bool) {
+    for group in groups.iter_mut() {
+        group.sort_by(Meta::cmp_by_path);
+    }
+    groups.sort_by(|a, b| {
+        reclaimable(b, disk_usage, count_blocks_once)
+            .cmp(&reclaimable(a, disk_usage, count_blocks_once))
+            .then_with(|| b.len().cmp(&a.len()))
+            .then_with(|| {
+                a.iter().map(|m| &m.path).cmp(b.iter().map(|m| &m.path))
+            })
+    });
+}
+
+/// Bytes freed by deleting all but one member of the group. If blocks are
+/// counted once, members sharing an inode are counted as one, being one
+/// and the same storage.
+fn reclaimable(
+    group: &[Meta],
+    disk_usage: bool,
+    count_blocks_once: bool,
+) -> u64 {
+    let mut inodes: HashSet<(u64, u64)> = HashSet::new();
+    let members: Vec<&Meta> = group
+        .iter()
+        .filter(|m| !count_blocks_once || inodes.insert((m.dev, m.ino)))
+        .collect();
+    // Allocations of equal contents can differ, as can sizes of those
+    // matched only by a content prefix, so keep the largest.
+    let sizes = members
+        .iter()
+        .map(|m| if disk_usage { m.on_disk() } else { m.size });
+    let total: u64 = sizes.clone().fold(0, u64::saturating_add);
+    total.saturating_sub(sizes.max().unwrap_or(0))
+}
+
+pub(crate) fn refine<F>(
     span: tracing::Span,
-    groups: &Vec<Vec<Meta>>,
+    groups: &[Vec<Meta>],
     grouper: F,
 ) -> anyhow::Result<Vec<Vec<Meta>>>
+where
+    F: Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>,
+{
+    let refined = refine_keyed(span, groups, grouper, None)?;
+    Ok(refined.into_iter().map(|(_, group)| group).collect())
+}
+
+/// Refined groups, each with the key by which its members were grouped.
+/// Members which failed to be grouped are noted, if anomalies are wanted.
+fn refine_keyed<F>(
+    span: tracing::Span,
+    groups: &[Vec<Meta>],
+    grouper: F,
+    anomalies: Option<&Anomalies>,
+) -> anyhow::Result<Vec<(Vec<u8>, Vec<Meta>)>>
 where
     F: Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>,
 {
     let _span_guard = span.enter();
     tracing::debug!(groups = groups.len(), "Refining.");
-    let grouper = Arc::new(grouper);
-    let refined_groups: Vec<Vec<Meta>> = groups
-        .par_iter()
+    // One flat parallel iteration over the members of all groups, rather
+    // than one nested in another over the groups, since a single giant
+    // group among many tiny ones would otherwise balance poorly.
+    let members: Vec<(usize, &Meta)> = groups
+        .iter()
         .enumerate()
-        .map({
-            |(group_seq, group)| {
-                let parent_span = span.clone();
-                let _parent_span_guard = parent_span.enter();
-                let group_span = tracing::trace_span!(
-                    "group",
-                    seq = group_seq,
-                    mem = group.len()
-                );
-                let _group_span_guard = group_span.enter();
-                let mut refined_groups: HashMap<Vec<u8>, Vec<Meta>> =
-                    HashMap::new();
-                for (id, member) in group
-                    .par_iter()
-                    .filter_map(|member| {
-                        let group_span = group_span.clone();
-                        let _group_span_guard = group_span.enter();
-                        let member_span = tracing::trace_span!(
-                            "member",
-                            path = ?member.path,
-                            size = member.size,
-                        );
-                        let _member_span_guard = member_span.enter();
-                        match grouper(member) {
-                            Err(error) => {
-                                tracing::error!(
-                                    ?error,
-                                    file = ?member.path,
-                                    "Failed to process."
-                                );
-                                None
-                            }
-                            Ok(id) => Some((id, member.clone())),
-                        }
-                    })
-                    .collect::<Vec<(Vec<u8>, Meta)>>()
-                {
-                    refined_groups
-                        .entry(id)
-                        .or_default()
-                        .push(member.clone());
+        .flat_map(|(group_seq, group)| {
+            group.iter().map(move |member| (group_seq, member))
+        })
+        .collect();
+    let ids: Vec<(usize, Vec<u8>, &Meta)> = members
+        .par_iter()
+        .filter_map(|(group_seq, member)| {
+            let _parent_span_guard = span.enter();
+            let member_span = tracing::trace_span!(
+                "member",
+                group = group_seq,
+                path = ?member.path,
+                size = member.size,
+            );
+            let _member_span_guard = member_span.enter();
+            let id = grouper(member)
+                .with_context(|| format!("file={:?}", member.path));
+            match on_error::handle(id, "Failed to process.") {
+                Ok(Some(id)) => Some(Ok((*group_seq, id, *member))),
+                Ok(None) => {
+                    if let Some(anomalies) = anomalies {
+                        anomalies.add(&member.path, Anomaly::ReadError);
+                    }
+                    None
                 }
-                refined_groups
-                    .into_values()
-                    .filter(|group| group.len() > 1)
-                    .collect::<Vec<Vec<Meta>>>()
+                Err(error) => Some(Err(error)),
             }
         })
-        .flatten()
+        .collect::<anyhow::Result<_>>()?;
+    // Ordered, so that refined groups come out in the same order from run
+    // to run, each in the order of its parent group, and members keep
+    // their order too, as collected in order from the parallel iteration.
+    let mut refined: BTreeMap<(usize, Vec<u8>), Vec<Meta>> = BTreeMap::new();
+    for (group_seq, id, member) in ids {
+        refined
+            .entry((group_seq, id))
+            .or_default()
+            .push(member.clone());
+    }
+    let refined_groups: Vec<(Vec<u8>, Vec<Meta>)> = refined
+        .into_iter()
+        .map(|((_, id), group)| (id, group))
+        .filter(|(_, group)| group.len() > 1)
         .collect();
     Ok(refined_groups)
 }
 
-fn groupers(
+/// Hash states of heads of files, as read by the head sample pass, for the
+/// xxh pass to resume from, rather than read the heads again. Keyed by path
+/// and pruned to the members of remaining groups after each pass.
+#[derive(Default)]
+pub(crate) struct Heads(DashMap<PathBuf, (twox_hash::XxHash3_64, u64)>);
+
+impl Heads {
+    fn save(&self, path: &Path, head: &[u8]) {
+        let mut hash = twox_hash::XxHash3_64::new();
+        hash.write(head);
+        self.0.insert(path.to_owned(), (hash, head.len() as u64));
+    }
+
+    fn xxh(&self, path: &Path, chunk_size: usize) -> anyhow::Result<u64> {
+        match self.0.remove(path) {
+            Some((_, (hash, offset))) => {
+                hash::xxh_resume(path, chunk_size, hash, offset)
+            }
+            None => hash::xxh(path, chunk_size),
+        }
+    }
+
+    pub(crate) fn retain(&self, groups: &[Vec<Meta>]) {
+        let members: HashSet<&Path> =
+            groups.iter().flatten().map(|m| m.path.as_path()).collect();
+        self.0.retain(|path, _| members.contains(path.as_path()));
+    }
+}
+
+pub(crate) type Grouper =
+    Box<dyn Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>>;
+
+/// With hash_samples, samples are reduced to their hashes, to save memory.
+/// Samples are only a cheap filter, ahead of hashing whole files, so a hash
+/// collision merely lets more files through to the next pass.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn groupers(
     sample_size: usize,
+    tail_sample: bool,
+    hash_samples: bool,
     chunk_size: usize,
     enable_blake3_pass: bool,
     enable_sha2_512_pass: bool,
-) -> Vec<(
-    tracing::Span,
-    Box<dyn Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>>,
-)> {
-    let mut groupers: Vec<(
-        tracing::Span,
-        Box<dyn Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>>,
-    )> = vec![
+    max_hash_size: Option<u64>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    xattr_cache: bool,
+    heads: Option<Arc<Heads>>,
+    progress: Arc<Progress>,
+) -> Vec<(tracing::Span, Grouper)> {
+    let mut groupers: Vec<(tracing::Span, Grouper)> = vec![
         // 1: by size
         (
             tracing::debug_span!("group_by_size"),
@@ -237,39 +1657,193 @@ fn groupers(
         // 2: by head bytes
         (
             tracing::debug_span!("group_by_sample_head"),
-            Box::new(move |m| read_head(m, sample_size)),
+            Box::new({
+                let heads = heads.clone();
+                move |m| {
+                    let head = read_head(m, sample_size)?;
+                    let hashed =
+                        max_hash_size.is_none_or(|max| m.size <= max);
+                    if let (Some(heads), true) = (&heads, hashed) {
+                        heads.save(&m.path, &head);
+                    }
+                    Ok(sample_key(head, hash_samples))
+                }
+            }),
         ),
         // 3: by mid bytes
         (
             tracing::debug_span!("group_by_sample_mid"),
-            Box::new(move |m| read_mid(m, sample_size)),
-        ),
-        // 4: by hash: xxh
-        (
-            tracing::debug_span!("group_by_hash_xxh"),
             Box::new(move |m| {
-                hash::xxh(&m.path, chunk_size)
-                    .map(|h| h.to_le_bytes().to_vec())
+                read_mid(m, sample_size).map(|s| sample_key(s, hash_samples))
             }),
         ),
     ];
+    if tail_sample {
+        // 4: by tail bytes
+        groupers.push((
+            tracing::debug_span!("group_by_sample_tail"),
+            Box::new(move |m| {
+                read_tail(m, sample_size).map(|s| sample_key(s, hash_samples))
+            }),
+        ));
+    }
+    // 5: by hash: xxh, or, for files too big to hash, by tail bytes, unless
+    //    already grouped by them.
+    groupers.push((
+        tracing::debug_span!("group_by_hash_xxh"),
+        capped(
+            max_hash_size,
+            move |m| {
+                if tail_sample {
+                    Ok(Vec::new())
+                } else {
+                    read_tail(m, sample_size)
+                        .map(|s| sample_key(s, hash_samples))
+                }
+            },
+            cached(checkpoint.clone(), xattr_cache, "xxh", {
+                let progress = progress.clone();
+                move |m| {
+                    let hash = match &heads {
+                        Some(heads) => heads.xxh(&m.path, chunk_size),
+                        None => hash::xxh(&m.path, chunk_size),
+                    }?;
+                    // Only once hashed, unlike those failed or cached.
+                    progress.add_hashed(m.size);
+                    Ok(hash.to_le_bytes().to_vec())
+                }
+            }),
+        ),
+    ));
     if enable_blake3_pass {
-        // 5: by hash: blake3
+        // 6: by hash: blake3
         groupers.push((
             tracing::debug_span!("group_by_hash_blake3"),
-            Box::new(move |m| hash::blake3(&m.path, chunk_size)),
+            capped(
+                max_hash_size,
+                // Already grouped by their tails.
+                |_| Ok(Vec::new()),
+                cached(checkpoint.clone(), xattr_cache, "blake3", {
+                    let progress = progress.clone();
+                    move |m| {
+                        let hash = hash::blake3(&m.path, chunk_size)?;
+                        progress.add_hashed(m.size);
+                        Ok(hash)
+                    }
+                }),
+            ),
         ));
     }
     if enable_sha2_512_pass {
-        // 6: by hash: sha2-512
+        // 7: by hash: sha2-512
         groupers.push((
             tracing::debug_span!("group_by_hash_sha2-512"),
-            Box::new(move |m| hash::sha2_512(&m.path, chunk_size)),
+            capped(
+                max_hash_size,
+                |_| Ok(Vec::new()),
+                cached(
+                    checkpoint.clone(),
+                    xattr_cache,
+                    "sha2-512",
+                    move |m| {
+                        let hash = hash::sha2_512(&m.path, chunk_size)?;
+                        progress.add_hashed(m.size);
+                        Ok(hash)
+                    },
+                ),
+            ),
         ));
     }
     groupers
 }
 
+/// Sole pass of the approximate mode: by a hash of the head of each file,
+/// up to the given size, so that files of different sizes can match.
+fn content_prefix_grouper(prefix_size: usize) -> (tracing::Span, Grouper) {
+    (
+        tracing::debug_span!("group_by_content_prefix"),
+        Box::new(move |m| {
+            let head = read_head(m, prefix_size)?;
+            Ok(blake3::hash(&head).as_bytes().to_vec())
+        }),
+    )
+}
+
+/// Sole pass of the custom mode: by the output of the program, run on each
+/// path. Bounded in concurrency by the parallelism of the passes.
+fn command_grouper(program: PathBuf) -> (tracing::Span, Grouper) {
+    (
+        tracing::debug_span!("group_by_command"),
+        Box::new(move |m| {
+            let output = process::Command::new(&program)
+                .arg(&m.path)
+                .stdin(process::Stdio::null())
+                .output()
+                .context(format!("Failed to run program={program:?}"))?;
+            if !output.status.success() {
+                bail!(
+                    "Grouper program={:?} failed with {}: {:?}",
+                    program,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(output.stdout)
+        }),
+    )
+}
+
+/// Hash function for files up to the max size, if any, and the substitute
+/// for those bigger.
+fn capped<F>(max_size: Option<u64>, substitute: F, hash: Grouper) -> Grouper
+where
+    F: 'static + Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>,
+{
+    match max_size {
+        None => hash,
+        Some(max) => {
+            Box::new(
+                move |m| {
+                    if m.size > max { substitute(m) } else { hash(m) }
+                },
+            )
+        }
+    }
+}
+
+/// Hash function behind the enabled caches: checkpoint, then xattr.
+fn cached<F>(
+    checkpoint: Option<Arc<Checkpoint>>,
+    xattr_cache: bool,
+    algo: &'static str,
+    hash: F,
+) -> Grouper
+where
+    F: 'static + Send + Sync + Fn(&Meta) -> anyhow::Result<Vec<u8>>,
+{
+    let hash: Grouper = if xattr_cache {
+        Box::new(move |m| xattr_cache::hash(algo, m, || hash(m)))
+    } else {
+        Box::new(hash)
+    };
+    match checkpoint {
+        None => hash,
+        Some(checkpoint) => {
+            Box::new(move |m| checkpoint.hash(algo, m, || hash(m)))
+        }
+    }
+}
+
+fn sample_key(sample: Vec<u8>, hash: bool) -> Vec<u8> {
+    if hash {
+        twox_hash::XxHash3_64::oneshot(&sample)
+            .to_le_bytes()
+            .to_vec()
+    } else {
+        sample
+    }
+}
+
 fn read_head(
     Meta {
         path, size: total, ..
@@ -283,6 +1857,18 @@ fn read_head(
     Ok(data)
 }
 
+fn read_tail(
+    Meta {
+        path, size: total, ..
+    }: &Meta,
+    sample_size: usize,
+) -> anyhow::Result<Vec<u8>> {
+    let amount = std::cmp::min(*total, u64::try_from(sample_size)?);
+    let offset = SeekFrom::Start(total - amount);
+    let data = read(path, usize::try_from(amount)?, offset)?;
+    Ok(data)
+}
+
 fn read_mid(
     Meta {
         path, size: total, ..
@@ -303,7 +1889,9 @@ fn read(path: &Path, amount: usize, offset: SeekFrom) -> io::Result<Vec<u8>> {
     let mut buf = vec![0u8; amount];
     let mut read_total = 0;
     while read_total < amount {
-        match file.read(&mut buf[read_total..]) {
+        let read_result =
+            slow::time("read", path, || file.read(&mut buf[read_total..]));
+        match read_result {
             // File could've been modified after we determined the amount.
             Ok(0) => {
                 tracing::warn!(
@@ -331,5 +1919,8 @@ fn read(path: &Path, amount: usize, offset: SeekFrom) -> io::Result<Vec<u8>> {
             }
         }
     }
+    // Short, if the file shrank, rather than padded with zeros that are
+    // not in it.
+    buf.truncate(read_total);
     Ok(buf)
 }
@@ -0,0 +1,162 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    data, on_error, output,
+    report::{self, OutputFormat, Value},
+    root::Root,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Find zero-byte regular files.
+    #[clap(long)]
+    files: bool,
+
+    /// Find directories without entries.
+    #[clap(long)]
+    dirs: bool,
+
+    /// Find both empty files and directories. The default, unless only one
+    /// of the above is given.
+    #[clap(long, conflicts_with_all = ["files", "dirs"])]
+    both: bool,
+
+    /// Skip all directories with this name.
+    /// (This option can be used multiple times)
+    /// Appended to those listed in the FX_SKIP_DIRS environment variable,
+    /// separated by colons.
+    #[clap(long)]
+    #[serde(serialize_with = "crate::config::os_strings")]
+    skip_dir: Vec<OsString>,
+
+    /// Skip all paths starting with this prefix.
+    /// Relative prefixes are relative to the root path.
+    /// (This option can be used multiple times)
+    /// Appended to those listed in the FX_SKIP_PREFIXES environment
+    /// variable, separated by colons.
+    #[clap(long)]
+    skip_prefix: Vec<PathBuf>,
+
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Quote the outputted paths.
+    #[clap(short, long = "quote")]
+    quote_paths: bool,
+
+    /// Style of quoting, by --quote.
+    #[clap(long, value_enum, default_value_t, requires = "quote_paths")]
+    quote_style: output::QuoteStyle,
+
+    /// Encoding of outputted paths.
+    /// Raw writes exact path bytes and so requires null separation (-Z).
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("raw", "null_line_sep")
+    )]
+    output_encoding: output::Encoding,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        let skip_dirs: Vec<OsString> = data::env_list(data::ENV_SKIP_DIRS)
+            .into_iter()
+            .chain(self.skip_dir.iter().cloned())
+            .collect();
+        let skip_prefixes: Vec<PathBuf> =
+            data::env_list(data::ENV_SKIP_PREFIXES)
+                .into_iter()
+                .map(PathBuf::from)
+                .chain(self.skip_prefix.iter().cloned())
+                .collect();
+        // Neither, as well as both, means both.
+        let both = self.both || self.files == self.dirs;
+        empties(
+            &root_path,
+            both || self.files,
+            both || self.dirs,
+            &skip_dirs[..],
+            &skip_prefixes[..],
+            self.quote_paths.then_some(self.quote_style),
+            self.null_line_sep,
+            self.output_encoding,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// Zero-byte regular files and directories without entries, in path order.
+/// A directory of which all entries are skipped counts as empty, since
+/// nothing in it is of interest.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument]
+pub fn empties(
+    root_path: &Path,
+    files: bool,
+    dirs: bool,
+    skip_dirs: &[OsString],
+    skip_prefixes: &[PathBuf],
+    quote: Option<output::QuoteStyle>,
+    null_line_sep: bool,
+    output_encoding: output::Encoding,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let find_opts = data::FindOpts {
+        skip_dirs: skip_dirs.to_vec(),
+        skip_prefixes: skip_prefixes.to_vec(),
+        ..Default::default()
+    };
+    let mut empties: BTreeSet<PathBuf> = BTreeSet::new();
+    let mut seen_dirs: Vec<PathBuf> = Vec::new();
+    let mut parents: HashSet<PathBuf> = HashSet::new();
+    for meta_result in on_error::filter(
+        data::find_with(root_path, find_opts)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        if let Some(parent) = meta.path.parent() {
+            parents.insert(parent.to_path_buf());
+        }
+        if files && meta.is_regular_file() && meta.size == 0 {
+            empties.insert(meta.path);
+        } else if dirs && meta.is_directory() {
+            seen_dirs.push(meta.path);
+        }
+    }
+    empties
+        .extend(seen_dirs.into_iter().filter(|dir| !parents.contains(dir)));
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            quote,
+            output_encoding,
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for path in &empties {
+        reporter.row(&[("path", Value::Path(path))])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::{
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{
+        BinaryBuilder, Int64Builder, StringBuilder, UInt32Builder,
+        UInt64Builder,
+    },
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter, basic::Compression,
+    file::properties::WriterProperties,
+};
+
+use crate::{
+    data::{self, FileType, Meta},
+    on_error,
+    root::Root,
+};
+
+/// Records per row group. Bounds memory use regardless of tree size.
+const BATCH_SIZE: usize = 64 * 1024;
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Parquet file to write.
+    #[clap(short, long)]
+    output: PathBuf,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        export(&root_path, &self.output)?;
+        Ok(())
+    }
+}
+
+/// Write metadata of all entries under the root as a Parquet file, one row
+/// per entry, with a column per Meta field.
+///
+/// Paths (and symlink targets) are stored as UTF-8 strings, lossily
+/// converted, for convenience in queries, as well as their exact bytes.
+#[tracing::instrument]
+pub fn export(root_path: &Path, output: &Path) -> anyhow::Result<()> {
+    let file = fs::File::create(output)
+        .context(format!("Failed to create file={:?}", output))?;
+    let schema = schema();
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))?;
+    let mut batch = Batch::default();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        batch.push(&meta_result?);
+        if batch.len >= BATCH_SIZE {
+            writer.write(&batch.finish(&schema)?)?;
+        }
+    }
+    if batch.len > 0 {
+        writer.write(&batch.finish(&schema)?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+fn schema() -> SchemaRef {
+    use DataType::{Binary, Int64, UInt32, UInt64, Utf8};
+
+    let fields = [
+        ("path", Utf8, false),
+        ("path_bytes", Binary, false),
+        ("type", Utf8, false),
+        ("symlink_dst", Utf8, true),
+        ("symlink_dst_bytes", Binary, true),
+        ("size", UInt64, false),
+        ("mode", UInt32, false),
+        ("perms", UInt32, false),
+        ("uid", UInt32, false),
+        ("gid", UInt32, false),
+        ("dev", UInt64, false),
+        ("ino", UInt64, false),
+        ("nlink", UInt64, false),
+        ("rdev", UInt64, false),
+        ("atime", Int64, false),
+        ("mtime", Int64, false),
+        ("ctime", Int64, false),
+        ("blksize", UInt64, false),
+        ("blocks", UInt64, false),
+    ]
+    .into_iter()
+    .map(|(name, typ, nullable)| Field::new(name, typ, nullable));
+    Arc::new(Schema::new(fields.collect::<Vec<Field>>()))
+}
+
+/// Column builders, in schema order.
+#[derive(Default)]
+struct Batch {
+    len: usize,
+    path: StringBuilder,
+    path_bytes: BinaryBuilder,
+    typ: StringBuilder,
+    symlink_dst: StringBuilder,
+    symlink_dst_bytes: BinaryBuilder,
+    size: UInt64Builder,
+    mode: UInt32Builder,
+    perms: UInt32Builder,
+    uid: UInt32Builder,
+    gid: UInt32Builder,
+    dev: UInt64Builder,
+    ino: UInt64Builder,
+    nlink: UInt64Builder,
+    rdev: UInt64Builder,
+    atime: Int64Builder,
+    mtime: Int64Builder,
+    ctime: Int64Builder,
+    blksize: UInt64Builder,
+    blocks: UInt64Builder,
+}
+
+impl Batch {
+    fn push(&mut self, meta: &Meta) {
+        self.len += 1;
+        self.path.append_value(meta.path.to_string_lossy());
+        self.path_bytes
+            .append_value(meta.path.as_os_str().as_bytes());
+        self.typ.append_value(meta.typ.name());
+        match &meta.typ {
+            FileType::Symlink { dst } => {
+                self.symlink_dst.append_value(dst.to_string_lossy());
+                self.symlink_dst_bytes
+                    .append_value(dst.as_os_str().as_bytes());
+            }
+            _ => {
+                self.symlink_dst.append_null();
+                self.symlink_dst_bytes.append_null();
+            }
+        }
+        self.size.append_value(meta.size);
+        self.mode.append_value(meta.mode);
+        self.perms.append_value(meta.perms);
+        self.uid.append_value(meta.uid);
+        self.gid.append_value(meta.gid);
+        self.dev.append_value(meta.dev);
+        self.ino.append_value(meta.ino);
+        self.nlink.append_value(meta.nlink);
+        self.rdev.append_value(meta.rdev);
+        self.atime.append_value(meta.atime);
+        self.mtime.append_value(meta.mtime);
+        self.ctime.append_value(meta.ctime);
+        self.blksize.append_value(meta.blksize);
+        self.blocks.append_value(meta.blocks);
+    }
+
+    /// Take the accumulated rows, leaving the builders empty for reuse.
+    fn finish(&mut self, schema: &SchemaRef) -> anyhow::Result<RecordBatch> {
+        self.len = 0;
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.path.finish()),
+            Arc::new(self.path_bytes.finish()),
+            Arc::new(self.typ.finish()),
+            Arc::new(self.symlink_dst.finish()),
+            Arc::new(self.symlink_dst_bytes.finish()),
+            Arc::new(self.size.finish()),
+            Arc::new(self.mode.finish()),
+            Arc::new(self.perms.finish()),
+            Arc::new(self.uid.finish()),
+            Arc::new(self.gid.finish()),
+            Arc::new(self.dev.finish()),
+            Arc::new(self.ino.finish()),
+            Arc::new(self.nlink.finish()),
+            Arc::new(self.rdev.finish()),
+            Arc::new(self.atime.finish()),
+            Arc::new(self.mtime.finish()),
+            Arc::new(self.ctime.finish()),
+            Arc::new(self.blksize.finish()),
+            Arc::new(self.blocks.finish()),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+        Ok(batch)
+    }
+}
@@ -0,0 +1,122 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    data::{self, Meta},
+    on_error, output,
+    report::{self, OutputFormat, Value},
+    root::Root,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Only report groups of at least this many links found under the
+    /// root, which may be fewer than the files have in all.
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u64).range(2..)
+    )]
+    min_links: u64,
+
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Quote the outputted paths.
+    #[clap(short, long = "quote")]
+    quote_paths: bool,
+
+    /// Style of quoting, by --quote.
+    #[clap(long, value_enum, default_value_t, requires = "quote_paths")]
+    quote_style: output::QuoteStyle,
+
+    /// Encoding of outputted paths.
+    /// Raw writes exact path bytes and so requires null separation (-Z).
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("raw", "null_line_sep")
+    )]
+    output_encoding: output::Encoding,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        hardlinks(
+            &root_path,
+            self.min_links,
+            self.quote_paths.then_some(self.quote_style),
+            self.null_line_sep,
+            self.output_encoding,
+            self.format,
+        )?;
+        Ok(())
+    }
+}
+
+/// Groups of paths of the same regular files, i.e. of the same dev and
+/// inode, each group in path order, and groups in order of their first
+/// paths. The standalone version of grouping duplicates by (dev, inode).
+#[tracing::instrument]
+pub fn hardlinks(
+    root_path: &Path,
+    min_links: u64,
+    quote: Option<output::QuoteStyle>,
+    null_line_sep: bool,
+    output_encoding: output::Encoding,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut inodes: HashMap<(u64, u64), Vec<Meta>> = HashMap::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        // Only those linked elsewhere, so as to not hold all files at once.
+        if meta.is_regular_file() && meta.nlink > 1 {
+            inodes.entry((meta.dev, meta.ino)).or_default().push(meta);
+        }
+    }
+    let mut groups: Vec<Vec<Meta>> = inodes
+        .into_values()
+        .filter(|group| {
+            u64::try_from(group.len()).unwrap_or(u64::MAX) >= min_links
+        })
+        .collect();
+    for group in groups.iter_mut() {
+        group.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    groups.sort_by(|a, b| a[0].path.cmp(&b[0].path));
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            quote,
+            output_encoding,
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for group in &groups {
+        let rows: Vec<Vec<report::Field>> = group
+            .iter()
+            .map(|file| vec![("path", Value::Path(&file.path))])
+            .collect();
+        reporter.group(&rows)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
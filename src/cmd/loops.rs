@@ -1,13 +1,18 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
     path::{Path, PathBuf},
 };
 
-use anyhow::Context;
+use anyhow::{Context, bail};
 
-use crate::data::{self, FileType, Meta};
+use crate::{
+    data::{self, FileType, Meta},
+    on_error, output,
+    report::{self, OutputFormat, Value},
+};
 
-#[derive(clap::Args, Debug)]
+#[derive(clap::Args, Debug, serde::Serialize)]
 pub struct Cmd {
     /// Separate output lines/records with a null (\0)
     /// instead of linefeed (\n) character.
@@ -18,6 +23,50 @@ pub struct Cmd {
     #[clap(short, long = "quote")]
     quote_paths: bool,
 
+    /// Style of quoting, by --quote.
+    #[clap(long, value_enum, default_value_t, requires = "quote_paths")]
+    quote_style: output::QuoteStyle,
+
+    /// Encoding of outputted paths.
+    /// Raw writes exact path bytes and so requires null separation (-Z).
+    #[clap(
+        long,
+        value_enum,
+        default_value_t,
+        requires_if("raw", "null_line_sep")
+    )]
+    output_encoding: output::Encoding,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    /// Resolve relative symlink targets against this directory, rather
+    /// than against the directory containing the link. The default is the
+    /// correct resolution, as done by the OS; this override is only for
+    /// special cases, such as links created relative to where a tool ran.
+    #[clap(long, value_name = "DIR")]
+    resolve_relative_against: Option<PathBuf>,
+
+    /// Same as --resolve-relative-against with the current working
+    /// directory.
+    #[clap(long, conflicts_with = "resolve_relative_against")]
+    resolve_relative_against_cwd: bool,
+
+    /// Instead of the cycles, report the links which would break them: of
+    /// each cycle, the lexicographically last of its links under the root.
+    /// Nothing is removed without --yes.
+    #[clap(long)]
+    fix: bool,
+
+    /// Remove the links reported by --fix.
+    #[clap(long, requires = "fix")]
+    yes: bool,
+
+    /// A symlink root is examined itself, rather than followed.
+    /// Or "-" to read a list of paths from stdin instead,
+    /// separated by nulls, if there are any, or else by linefeeds.
+    /// Of the listed paths, only the symlinks are examined.
     #[clap(default_value = ".")]
     root_path: PathBuf,
 }
@@ -25,91 +74,245 @@ pub struct Cmd {
 impl Cmd {
     pub fn run(&self) -> anyhow::Result<()> {
         let given = &self.root_path;
-        let canonicalized = self
-            .root_path
-            .canonicalize()
-            .context(format!("Failed to canonicalize path={:?}", given))?;
-        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
-        let root_path = canonicalized;
-        loops(&root_path, self.quote_paths, self.null_line_sep)?;
+        let root_path = if data::is_stdin(given) {
+            given.clone()
+        } else {
+            let canonicalized = crate::path::canonicalize_or_normalize(
+                given,
+                crate::path::canonicalize_keeping_symlink,
+            );
+            tracing::debug!(
+                ?given,
+                ?canonicalized,
+                "Canonicalized root path."
+            );
+            canonicalized
+        };
+        let relative_base = match (
+            &self.resolve_relative_against,
+            self.resolve_relative_against_cwd,
+        ) {
+            (Some(given), _) => Some(given.clone()),
+            (None, true) => Some(PathBuf::from(".")),
+            (None, false) => None,
+        }
+        .map(|given| {
+            let canonicalized = given.canonicalize().context(format!(
+                "Failed to canonicalize path={:?}",
+                given
+            ))?;
+            tracing::debug!(
+                ?given,
+                ?canonicalized,
+                "Canonicalized relative symlink base."
+            );
+            anyhow::Ok(canonicalized)
+        })
+        .transpose()?;
+        loops(
+            &root_path,
+            relative_base.as_deref(),
+            self.quote_paths.then_some(self.quote_style),
+            self.null_line_sep,
+            self.output_encoding,
+            self.format,
+            self.fix.then_some(self.yes),
+        )?;
         Ok(())
     }
 }
 
+/// With fix, instead of reporting the cycles, report, and, if fix is true,
+/// remove, the links which would break them.
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument]
 pub fn loops(
     root_path: &Path,
-    quote_paths: bool,
+    relative_base: Option<&Path>,
+    quote: Option<output::QuoteStyle>,
     null_line_sep: bool,
+    output_encoding: output::Encoding,
+    format: OutputFormat,
+    fix: Option<bool>,
 ) -> anyhow::Result<()> {
+    if fix.is_some() && data::is_stdin(root_path) {
+        bail!("Links are only fixed under a root, not from a list of paths.");
+    }
     let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
-    let mut index: HashMap<u64, HashSet<PathBuf>> = HashMap::new();
-    for link_meta in data::find(root_path)?
-        .filter_map(Result::ok)
-        .filter(Meta::is_symlink)
-    {
-        if let Some(inode) = find_cycling_inode(&link_meta)? {
+    let mut index: HashMap<(u64, u64), BTreeSet<PathBuf>> = HashMap::new();
+    let mut fixes: BTreeSet<PathBuf> = BTreeSet::new();
+    for link_meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let link_meta = link_meta_result?;
+        if !link_meta.is_symlink() {
+            continue;
+        }
+        if let Some(file_id) = find_cycling_file(&link_meta, relative_base)? {
+            if fix.is_some() {
+                // Never anything outside the root.
+                let closing = find_cycle_links(&link_meta, relative_base)?
+                    .into_iter()
+                    .filter(|link| link.starts_with(root_path))
+                    .max();
+                fixes.extend(closing);
+            }
             index
-                .entry(inode)
+                .entry(file_id)
                 .or_default()
                 .insert(link_meta.path.clone());
         }
     }
-    for (_looping_inode, entry_paths) in index {
-        for entry_path in entry_paths {
-            if quote_paths {
-                print!("{entry_path:?}{sep}");
-            } else {
-                print!("{}{sep}", entry_path.display());
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            quote,
+            output_encoding,
+            record_sep: sep,
+            // Same as dups: a group ends with a linefeed even when records
+            // are null-separated, so an empty record is never emitted.
+            group_sep: "\n".to_string(),
+            ..Default::default()
+        },
+    );
+    if let Some(remove) = fix {
+        for link in &fixes {
+            if remove {
+                // Could have changed since it was examined.
+                let meta = fs::symlink_metadata(link).context(format!(
+                    "Failed to read metadata from path={link:?}"
+                ))?;
+                if !meta.is_symlink() {
+                    bail!("No longer a symlink: {link:?}");
+                }
+                fs::remove_file(link)
+                    .context(format!("Failed to remove link={link:?}"))?;
+                tracing::info!(?link, "Removed.");
             }
+            reporter.row(&[("path", Value::Path(link))])?;
+        }
+        reporter.finish()?;
+        if !remove && !fixes.is_empty() {
+            eprintln!(
+                "Warning: Nothing removed. Pass --yes to remove the {} \
+                links listed.",
+                fixes.len()
+            );
         }
-        print!("{sep}");
+        return Ok(());
     }
+    // Sorted, so the output is the same from run to run.
+    let mut groups: Vec<BTreeSet<PathBuf>> = index.into_values().collect();
+    groups.sort();
+    for entry_paths in groups {
+        let rows: Vec<Vec<report::Field>> = entry_paths
+            .iter()
+            .map(|path| vec![("path", Value::Path(path))])
+            .collect();
+        reporter.group(&rows)?;
+    }
+    reporter.finish()?;
     Ok(())
 }
 
-fn find_cycling_inode(entry_path: &Meta) -> anyhow::Result<Option<u64>> {
-    let mut visited: HashSet<u64> = HashSet::new();
+/// Links forming the first cycle found from the entry, if any, in order.
+/// Unlike find_cycling_file, only counts a file reached again along the
+/// same path, rather than along any, since otherwise a file reachable
+/// two ways, but without a cycle, would have links to break too.
+fn find_cycle_links(
+    entry: &Meta,
+    relative_base: Option<&Path>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    // Each entry with the files on the path to it, and which were links.
+    type Trail = Vec<((u64, u64), Option<PathBuf>)>;
+    let mut visited: HashSet<(u64, u64)> = HashSet::new();
+    let mut frontier: Vec<(Meta, Trail)> = vec![(entry.clone(), Vec::new())];
+    while let Some((current, mut trail)) = frontier.pop() {
+        let file_id = (current.dev, current.ino);
+        if let Some(start) = trail.iter().position(|(id, _)| *id == file_id) {
+            let links =
+                trail.drain(start..).filter_map(|(_, link)| link).collect();
+            return Ok(links);
+        }
+        if !visited.insert(file_id) {
+            continue;
+        }
+        trail.push((
+            file_id,
+            current.is_symlink().then(|| current.path.clone()),
+        ));
+        for next in next_entries(&current, relative_base)? {
+            frontier.push((next, trail.clone()));
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// File, identified by (dev, ino), at which a cycle is entered, if any.
+/// Inode numbers are only unique per device, and a cycle can span devices,
+/// so the inode alone could conflate distinct files.
+///
+/// Relative symlink targets are resolved against the link's parent
+/// directory, unless a base is given.
+fn find_cycling_file(
+    entry_path: &Meta,
+    relative_base: Option<&Path>,
+) -> anyhow::Result<Option<(u64, u64)>> {
+    let mut visited: HashSet<(u64, u64)> = HashSet::new();
     let mut frontier: Vec<Meta> = vec![entry_path.clone()];
     while let Some(current) = frontier.pop() {
-        if visited.contains(&current.ino) {
-            return Ok(Some(current.ino));
+        let file_id = (current.dev, current.ino);
+        if visited.contains(&file_id) {
+            return Ok(Some(file_id));
         }
-        match &current {
-            Meta {
-                path: src,
-                typ: FileType::Symlink { dst },
-                ..
-            } => {
-                let src_dir = src.parent().unwrap_or_else(|| {
-                    // The only path without a parent is "/" and
-                    // it cannot be a symlink.
-                    unreachable!("Symlink path has no parent: {src:?}")
-                });
-                let dst = crate::path::normalize(src_dir, dst);
-                // Symlink might be dangling, which for the purpose of
-                // finding loops we can just ignore and move on.
-                if let Ok(meta) = Meta::from_path(&dst) {
-                    frontier.push(meta);
-                }
+        frontier.extend(next_entries(&current, relative_base)?);
+        visited.insert(file_id);
+    }
+    Ok(None)
+}
+
+/// Entries reached from the current one: the target of a symlink, or the
+/// entries of a directory.
+fn next_entries(
+    current: &Meta,
+    relative_base: Option<&Path>,
+) -> anyhow::Result<Vec<Meta>> {
+    let mut next = Vec::new();
+    match current {
+        Meta {
+            path: src,
+            typ: FileType::Symlink { dst },
+            ..
+        } => {
+            let src_dir = src.parent().unwrap_or_else(|| {
+                // The only path without a parent is "/" and
+                // it cannot be a symlink.
+                unreachable!("Symlink path has no parent: {src:?}")
+            });
+            let base = relative_base.unwrap_or(src_dir);
+            let dst = crate::path::normalize(base, dst);
+            // Symlink might be dangling, which for the purpose of
+            // finding loops we can just ignore and move on.
+            if let Ok(meta) = Meta::from_path(&dst) {
+                next.push(meta);
             }
-            Meta {
-                path: src,
-                typ: FileType::Directory,
-                ..
-            } => {
-                for entry_result in src
-                    .read_dir()
-                    .context(format!("Failed to read dir at path={src:?}"))?
-                {
-                    let entry = entry_result?;
-                    let meta = Meta::from_dir_entry(&entry)?;
-                    frontier.push(meta);
-                }
+        }
+        Meta {
+            path: src,
+            typ: FileType::Directory,
+            ..
+        } => {
+            for entry_result in src
+                .read_dir()
+                .context(format!("Failed to read dir at path={src:?}"))?
+            {
+                let entry = entry_result?;
+                let meta = Meta::from_dir_entry(&entry)?;
+                next.push(meta);
             }
-            _ => {}
         }
-        visited.insert(current.ino);
+        _ => {}
     }
-    Ok(None)
+    Ok(next)
 }
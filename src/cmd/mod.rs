@@ -1,4 +1,23 @@
+pub mod audit_perms;
+pub mod browse;
+pub mod casefold_collisions;
+pub mod common;
 pub mod dang;
+#[cfg(feature = "snap")]
+pub mod diff;
 pub mod dups;
+pub mod empties;
+#[cfg(feature = "parquet")]
+pub mod export;
+pub mod hardlinks;
 pub mod loops;
+pub mod mtime_anomalies;
+pub mod owned;
+pub mod recent;
+#[cfg(feature = "snap")]
+pub mod snap;
+pub mod specials;
+pub mod stale;
+pub mod timeline;
 pub mod top;
+pub mod verify;
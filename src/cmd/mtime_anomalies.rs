@@ -0,0 +1,112 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    data, on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+    time,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Report directories whose modification time differs from their
+    /// newest entry's by more than this. Units: s, min, h, d, w, m (30
+    /// days), y (365 days).
+    #[clap(long, default_value = "1h", value_parser = time::parse_duration)]
+    #[serde(serialize_with = "crate::config::secs")]
+    tolerance: Duration,
+
+    #[clap(short, long = "lim")]
+    limit: Option<usize>,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        mtime_anomalies(&root_path, self.tolerance, self.limit, self.format)?;
+        Ok(())
+    }
+}
+
+/// Directories whose modification time differs from the newest of their
+/// entries' by more than the tolerance, largest difference first. The
+/// difference is signed: positive where an entry is newer than its
+/// directory.
+///
+/// These are only hints of tampering with timestamps, since there are
+/// innocent causes in both directions: a directory's time changes only
+/// when entries are added, removed or renamed, so editing a file in place
+/// makes it newer than its directory, and removing one makes the directory
+/// newer than all that remain. Times are as the filesystem recorded them.
+#[tracing::instrument]
+pub fn mtime_anomalies(
+    root_path: &Path,
+    tolerance: Duration,
+    report_limit: Option<usize>,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut dirs: HashMap<PathBuf, i64> = HashMap::new();
+    let mut newest: HashMap<PathBuf, i64> = HashMap::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        if let Some(parent) = meta.path.parent() {
+            let mtime = newest.entry(parent.to_owned()).or_insert(meta.mtime);
+            *mtime = (*mtime).max(meta.mtime);
+        }
+        if meta.is_directory() {
+            dirs.insert(meta.path, meta.mtime);
+        }
+    }
+    let tolerance = i64::try_from(tolerance.as_secs()).unwrap_or(i64::MAX);
+    let mut anomalies: Vec<(PathBuf, i64, i64)> = dirs
+        .into_iter()
+        .filter_map(|(dir, dir_mtime)| {
+            let newest = *newest.get(&dir)?;
+            (newest.saturating_sub(dir_mtime).abs() > tolerance)
+                .then_some((dir, dir_mtime, newest))
+        })
+        .collect();
+    anomalies.sort_by(
+        |(path_a, dir_a, newest_a), (path_b, dir_b, newest_b)| {
+            let delta_a = newest_a.saturating_sub(*dir_a).abs();
+            let delta_b = newest_b.saturating_sub(*dir_b).abs();
+            delta_b.cmp(&delta_a).then_with(|| path_a.cmp(path_b))
+        },
+    );
+    if let Some(n) = report_limit {
+        anomalies.truncate(n);
+    }
+
+    let mut reporter = report::new(format, report::Options::default());
+    for (dir, dir_mtime, newest) in &anomalies {
+        reporter.row(&[
+            ("delta_secs", Value::Int(newest.saturating_sub(*dir_mtime))),
+            (
+                "dir_mtime",
+                Value::Text(time::fmt_local(*dir_mtime)?.into()),
+            ),
+            (
+                "newest_entry_mtime",
+                Value::Text(time::fmt_local(*newest)?.into()),
+            ),
+            ("path", Value::Path(dir)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
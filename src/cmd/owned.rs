@@ -0,0 +1,141 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    data, on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+    users,
+};
+
+/// Owners by which to total usage.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum By {
+    #[default]
+    User,
+    Group,
+    /// Each combination of user and group.
+    Both,
+}
+
+/// User ID and group ID, of which only those totaled by are set.
+type Owner = (Option<u32>, Option<u32>);
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Owners by which to total usage.
+    #[clap(long, value_enum, default_value_t)]
+    by: By,
+
+    /// Add columns with user and group names, next to their IDs, where
+    /// known.
+    #[clap(long)]
+    names: bool,
+
+    #[clap(short, long = "lim", default_value_t = 25)]
+    limit: usize,
+
+    /// Report using human-readable (i.e. aggregated) units.
+    #[clap(short = 'H', long)]
+    human: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        owned(
+            &root_path,
+            self.by,
+            self.names,
+            self.limit,
+            self.format,
+            self.human,
+        )?;
+        Ok(())
+    }
+}
+
+/// Count and total size of entries per owner, of the top-N owners by size,
+/// in ascending order, like top, so that the biggest is the last, nearest
+/// to the prompt. Directories count too, since they take space as well.
+#[tracing::instrument]
+pub fn owned(
+    root_path: &Path,
+    by: By,
+    names: bool,
+    limit: usize,
+    format: OutputFormat,
+    human: bool,
+) -> anyhow::Result<()> {
+    let mut totals: HashMap<Owner, (u64, u64)> = HashMap::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        let owner = match by {
+            By::User => (Some(meta.uid), None),
+            By::Group => (None, Some(meta.gid)),
+            By::Both => (Some(meta.uid), Some(meta.gid)),
+        };
+        let (count, size) = totals.entry(owner).or_default();
+        *count += 1;
+        *size += meta.size;
+    }
+    let mut totals: Vec<(Owner, (u64, u64))> = totals.into_iter().collect();
+    // Ties broken by owner, so the cut is the same from run to run.
+    totals.sort_by(|(owner_a, (_, size_a)), (owner_b, (_, size_b))| {
+        size_b.cmp(size_a).then_with(|| owner_a.cmp(owner_b))
+    });
+    totals.truncate(limit);
+    totals.reverse();
+
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            human,
+            ..Default::default()
+        },
+    );
+    let mut user_names = users::Names::users();
+    let mut group_names = users::Names::groups();
+    for ((uid, gid), (count, size)) in totals {
+        let mut row = Vec::new();
+        if let Some(uid) = uid {
+            row.push(("uid", Value::Int(i64::from(uid))));
+            if names {
+                let name = user_names.name(uid).to_owned();
+                row.push(("user", Value::Text(name.into())));
+            }
+        }
+        if let Some(gid) = gid {
+            row.push(("gid", Value::Int(i64::from(gid))));
+            if names {
+                let name = group_names.name(gid).to_owned();
+                row.push(("group", Value::Text(name.into())));
+            }
+        }
+        row.push(("entries", Value::Int(i64::try_from(count)?)));
+        row.push(("size", Value::Size(size)));
+        reporter.row(&row)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
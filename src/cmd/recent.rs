@@ -0,0 +1,136 @@
+use std::{path::Path, time::Duration};
+
+use crate::{
+    cmd::{timeline::TimeField, top},
+    data::{self, Meta},
+    report::{self, OutputFormat, Value},
+    root::Root,
+    time,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+#[command(group(
+    clap::ArgGroup::new("when").args(["after", "since", "since_boot"])
+))]
+pub struct Cmd {
+    /// Timestamp by which files are recent: last modified, accessed, or
+    /// changed (contents or metadata).
+    #[clap(long, value_enum, default_value_t = TimeField::Mtime)]
+    by: TimeField,
+
+    /// Report files of which the time, by --by, is after this time, given
+    /// as "@<secs>" since the Unix epoch, as ISO 8601 with an offset (e.g.
+    /// "2024-05-01T13:00:00Z"), or as local date and time
+    /// ("2024-05-01T13:00:00") or date alone.
+    #[clap(
+        long,
+        alias = "modified-after",
+        value_name = "TIME",
+        value_parser = time::parse_timestamp
+    )]
+    after: Option<i64>,
+
+    /// Report files of which the time, by --by, is within this long ago.
+    /// Units: s, min, h, d, w, m (30 days), y (365 days). E.g.: 2h, 7d.
+    #[clap(long, value_name = "DURATION", value_parser = time::parse_duration)]
+    #[serde(serialize_with = "crate::config::opt_secs")]
+    since: Option<Duration>,
+
+    /// Report files of which the time, by --by, is after the system booted.
+    #[clap(long)]
+    since_boot: bool,
+
+    /// Report using human-readable (i.e. aggregated) units.
+    #[clap(short = 'H', long)]
+    human: bool,
+
+    #[clap(short, long = "lim")]
+    limit: Option<usize>,
+
+    /// Report real paths, i.e. with all symlinks resolved, at the cost of
+    /// an extra syscall per path. Paths which fail to resolve are skipped.
+    #[clap(long)]
+    real_paths: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        let after = match (self.after, self.since, self.since_boot) {
+            (Some(after), _, _) => Some(after),
+            (None, Some(since), _) => Some(time::ago(since)),
+            (None, None, true) => Some(time::boot()?),
+            (None, None, false) => None,
+        };
+        recent(
+            &root_path,
+            self.by,
+            after,
+            self.limit,
+            self.real_paths,
+            self.format,
+            self.human,
+        )?;
+        Ok(())
+    }
+}
+
+/// Regular files of which the given time is after the given time, in
+/// seconds since the Unix epoch, if any is given, most recent first.
+///
+/// Times are taken as the filesystem recorded them, so they are only as
+/// trustworthy as the clock which set them, and as whatever else could have
+/// set them, since modification and access times can be set at will (e.g.
+/// by "touch -d"). Skewed clocks can also put them in the future. Access
+/// times are only as fresh as the mount options let them be (e.g.
+/// "noatime", "relatime").
+#[tracing::instrument]
+pub fn recent(
+    root_path: &Path,
+    by: TimeField,
+    after: Option<i64>,
+    report_limit: Option<usize>,
+    real_paths: bool,
+    format: OutputFormat,
+    human: bool,
+) -> anyhow::Result<()> {
+    let mut files: Vec<Meta> =
+        top::find_files(root_path, data::FindOpts::default())?
+            .into_iter()
+            .filter(|file| after.is_none_or(|after| by.of(file) > after))
+            .collect();
+
+    // Most recent on top.
+    files.sort_by(|a, b| {
+        by.of(b).cmp(&by.of(a)).then_with(|| a.cmp_by_path(b))
+    });
+    if let Some(n) = report_limit {
+        files.truncate(n);
+    }
+
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            human,
+            real_paths,
+            ..Default::default()
+        },
+    );
+    for file in &files {
+        reporter.row(&[
+            (by.name(), Value::Text(time::fmt_local(by.of(file))?.into())),
+            ("size", Value::Size(file.size)),
+            ("path", Value::Path(&file.path)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    data::{self, Meta, lossless_path},
+    on_error,
+    root::Root,
+    time,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Snapshot file to write.
+    #[clap(short, long = "out")]
+    output: PathBuf,
+
+    /// Compress the snapshot, since those of big trees are big. Detected
+    /// when it is read back, so it need not be given to diff.
+    #[clap(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        snap(&root_path, &self.output, self.compress)?;
+        Ok(())
+    }
+}
+
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Version of the snapshot format written, and the newest one read. Older
+/// ones are migrated as they are read.
+pub const VERSION: u32 = 1;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// First line of a snapshot, describing the rest.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Header {
+    /// Of the format, 0 if it was written before versions were recorded.
+    #[serde(default)]
+    pub version: u32,
+
+    #[serde(with = "lossless_path")]
+    pub root: PathBuf,
+
+    /// When the walk started, in seconds since the epoch.
+    pub time: i64,
+}
+
+/// Write metadata of all entries under the root as JSON lines: the header,
+/// then one Meta per entry, with all of its fields, as they are.
+#[tracing::instrument]
+pub fn snap(
+    root_path: &Path,
+    output: &Path,
+    compression: Compression,
+) -> anyhow::Result<()> {
+    let file = fs::File::create(output)
+        .context(format!("Failed to create file={:?}", output))?;
+    let file = BufWriter::new(file);
+    // Finished explicitly, since errors of finishing on drop are lost.
+    match compression {
+        Compression::None => write(root_path, file)?.flush()?,
+        Compression::Gzip => {
+            let out = flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            );
+            write(root_path, out)?.finish()?.flush()?;
+        }
+        Compression::Zstd => {
+            let out = zstd::Encoder::new(file, 0)?;
+            write(root_path, out)?.finish()?.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn write<W: Write>(root_path: &Path, mut out: W) -> anyhow::Result<W> {
+    let header = Header {
+        version: VERSION,
+        root: root_path.to_path_buf(),
+        time: time::now(),
+    };
+    serde_json::to_writer(&mut out, &header)?;
+    writeln!(out)?;
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        serde_json::to_writer(&mut out, &meta_result?)?;
+        writeln!(out)?;
+    }
+    Ok(out)
+}
+
+/// Read back a snapshot written by snap: its header and its entries.
+/// Compressed ones are decompressed, as detected by their magic bytes.
+pub fn read(
+    path: &Path,
+) -> anyhow::Result<(
+    Header,
+    impl Iterator<Item = anyhow::Result<Meta>> + use<>,
+)> {
+    let file = fs::File::open(path)
+        .context(format!("Failed to open snapshot path={:?}", path))?;
+    let mut file = BufReader::new(file);
+    let magic = file
+        .fill_buf()
+        .context(format!("Failed to read snapshot path={:?}", path))?;
+    let input: Box<dyn BufRead> = if magic.starts_with(GZIP_MAGIC) {
+        Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(file)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?))
+    } else {
+        Box::new(file)
+    };
+    let mut lines = input.lines();
+    let header = lines
+        .next()
+        .context(format!("Empty snapshot path={:?}", path))??;
+    let header: Header = serde_json::from_str(&header)
+        .context(format!("Invalid snapshot header in path={:?}", path))?;
+    // Entries of 0 are the same as of 1, which only added the version, so
+    // need no migration.
+    if header.version > VERSION {
+        bail!(
+            "Snapshot path={:?} is of version {}, newer than {}, the newest \
+            this fx supports. Upgrade fx to read it.",
+            path,
+            header.version,
+            VERSION
+        );
+    }
+    let path = path.to_path_buf();
+    let entries = lines.enumerate().map(move |(i, line)| {
+        let meta = serde_json::from_str(&line?).context(format!(
+            "Invalid snapshot entry at line={} in path={:?}",
+            i + 2,
+            path
+        ))?;
+        Ok(meta)
+    });
+    Ok((header, entries))
+}
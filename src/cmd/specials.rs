@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use crate::{
+    data::{self, FileType, Meta},
+    on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+};
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Separate output lines/records with a null (\0)
+    /// instead of linefeed (\n) character.
+    #[clap(short = 'Z', long = "null")]
+    null_line_sep: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        specials(&root_path, self.null_line_sep, self.format)?;
+        Ok(())
+    }
+}
+
+/// Special files, ordered by path, with their type and, for devices, the
+/// device they represent, as "major:minor", like "ls -l" shows it.
+#[tracing::instrument]
+pub fn specials(
+    root_path: &Path,
+    null_line_sep: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let mut specials: Vec<Meta> = Vec::new();
+    for meta_result in on_error::filter(
+        data::find(root_path)?,
+        "Metadata collection failed.",
+    ) {
+        let meta = meta_result?;
+        if meta.is_special() {
+            specials.push(meta);
+        }
+    }
+    specials.sort_by(Meta::cmp_by_path);
+
+    let sep = if null_line_sep { "\0" } else { "\n" }.to_string();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            record_sep: sep,
+            ..Default::default()
+        },
+    );
+    for meta in &specials {
+        let device = match meta.typ {
+            FileType::DevChar | FileType::DevBlock => format!(
+                "{}:{}",
+                data::dev_major(meta.rdev),
+                data::dev_minor(meta.rdev)
+            ),
+            _ => "-".to_string(),
+        };
+        reporter.row(&[
+            ("type", Value::Text(meta.typ.name().into())),
+            ("device", Value::Text(device.into())),
+            ("path", Value::Path(&meta.path)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
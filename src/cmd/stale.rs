@@ -0,0 +1,165 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    cmd::{timeline::TimeField, top},
+    data::{self, Meta},
+    report::{self, OutputFormat, Value},
+    root::Root,
+    time,
+};
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Timestamp by which files are stale: last accessed, modified, or
+    /// changed (contents or metadata).
+    #[clap(long, value_enum, default_value_t = TimeField::Atime)]
+    by: TimeField,
+
+    /// Report files of which the time, by --by, is longer ago than this.
+    /// Units: s, min, h, d, w, m (30 days), y (365 days). E.g.: 90d, 2y.
+    #[clap(long, alias = "since", value_parser = time::parse_duration)]
+    #[serde(serialize_with = "crate::config::secs")]
+    older_than: Duration,
+
+    /// Report using human-readable (i.e. aggregated) units.
+    #[clap(short = 'H', long)]
+    human: bool,
+
+    #[clap(short, long = "lim")]
+    limit: Option<usize>,
+
+    /// Report real paths, i.e. with all symlinks resolved, at the cost of
+    /// an extra syscall per path. Paths which fail to resolve are skipped.
+    #[clap(long)]
+    real_paths: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        stale(
+            &root_path,
+            self.by,
+            self.older_than,
+            self.limit,
+            self.real_paths,
+            self.format,
+            self.human,
+        )?;
+        Ok(())
+    }
+}
+
+/// Regular files not accessed (or modified, or changed) within the given
+/// duration, largest first.
+///
+/// Access times are only as good as the filesystem keeps them: with
+/// "noatime" they are never updated after creation, so every old file
+/// looks stale. The default "relatime" updates them at most once a day,
+/// which is fine at this granularity.
+#[tracing::instrument]
+pub fn stale(
+    root_path: &Path,
+    by: TimeField,
+    older_than: Duration,
+    report_limit: Option<usize>,
+    real_paths: bool,
+    format: OutputFormat,
+    human: bool,
+) -> anyhow::Result<()> {
+    let noatime = if data::is_stdin(root_path) || by != TimeField::Atime {
+        None
+    } else {
+        noatime_mount_point(root_path)
+    };
+    if let Some(mount_point) = noatime {
+        eprintln!(
+            "Warning: {:?} is mounted with noatime, \
+            so access times are unreliable.",
+            mount_point
+        );
+    }
+    let now = time::now();
+    let threshold = time::ago(older_than);
+    let mut files: Vec<Meta> =
+        top::find_files(root_path, data::FindOpts::default())?
+            .into_iter()
+            .filter(|file| by.of(file) < threshold)
+            .collect();
+
+    // Largest on top.
+    files.sort_by(|a, b| b.cmp_by_size(a).then_with(|| a.cmp_by_path(b)));
+    if let Some(n) = report_limit {
+        files.truncate(n);
+    }
+
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            human,
+            real_paths,
+            ..Default::default()
+        },
+    );
+    for file in &files {
+        let time = by.of(file);
+        let idle_days = now.saturating_sub(time) / SECS_PER_DAY;
+        reporter.row(&[
+            ("size", Value::Size(file.size)),
+            ("idle_days", Value::Int(idle_days)),
+            (by.name(), Value::Text(time::fmt_local(time)?.into())),
+            ("path", Value::Path(&file.path)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
+
+/// Mount point of the filesystem containing the path, if it is mounted
+/// with "noatime". Best-effort: None if mounts cannot be determined.
+fn noatime_mount_point(path: &Path) -> Option<PathBuf> {
+    let mounts = fs::read_to_string("/proc/self/mounts")
+        .inspect_err(|error| {
+            tracing::debug!(?error, "Failed to read mounts.");
+        })
+        .ok()?;
+    // Longest mount point prefix is the one the path is on.
+    let (mount_point, options) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = unescape_mount_field(fields.nth(1)?);
+            let options = fields.nth(1)?;
+            Some((mount_point, options))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())?;
+    options
+        .split(',')
+        .any(|option| option == "noatime")
+        .then_some(mount_point)
+}
+
+/// Whitespace in mount table fields is escaped as octal, e.g. "\040".
+pub(crate) fn unescape_mount_field(field: &str) -> PathBuf {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+        .into()
+}
@@ -0,0 +1,181 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use chrono::{Datelike, NaiveDate};
+
+use crate::{
+    cmd::top,
+    data::{self, Meta},
+    on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+    time,
+};
+
+const BAR_WIDTH: u64 = 40;
+
+/// Timestamp by which files are placed in time.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeField {
+    Mtime,
+    Atime,
+    Ctime,
+}
+
+impl TimeField {
+    pub(crate) fn of(&self, meta: &Meta) -> i64 {
+        match self {
+            Self::Mtime => meta.mtime,
+            Self::Atime => meta.atime,
+            Self::Ctime => meta.ctime,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Mtime => "mtime",
+            Self::Atime => "atime",
+            Self::Ctime => "ctime",
+        }
+    }
+}
+
+/// Calendar period, in local time.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Bucket {
+    Day,
+    /// ISO 8601 week, starting on Monday.
+    Week,
+    Month,
+    Year,
+}
+
+impl Bucket {
+    /// First day of the period containing the date.
+    fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Day => date,
+            Self::Week => {
+                let week = date.iso_week();
+                NaiveDate::from_isoywd_opt(
+                    week.year(),
+                    week.week(),
+                    chrono::Weekday::Mon,
+                )
+                .unwrap_or(date)
+            }
+            Self::Month => date.with_day(1).unwrap_or(date),
+            Self::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    fn label(&self, start: NaiveDate) -> String {
+        match self {
+            Self::Day => start.format("%Y-%m-%d").to_string(),
+            Self::Week => start.format("%G-W%V").to_string(),
+            Self::Month => start.format("%Y-%m").to_string(),
+            Self::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Timestamp by which to place files in time.
+    #[clap(long, value_enum, default_value_t = TimeField::Mtime)]
+    by: TimeField,
+
+    /// Period into which to bucket files.
+    #[clap(long, value_enum, default_value_t = Bucket::Month)]
+    bucket: Bucket,
+
+    /// Add a column with a bar proportional to the bytes of each bucket.
+    #[clap(long)]
+    bars: bool,
+
+    /// Report using human-readable (i.e. aggregated) units.
+    #[clap(short = 'H', long)]
+    human: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let root_path = self.root.resolve()?;
+        timeline(
+            &root_path,
+            self.by,
+            self.bucket,
+            self.bars,
+            self.format,
+            self.human,
+        )?;
+        Ok(())
+    }
+}
+
+/// Count and total size of regular files per period, in chronological
+/// order. Periods without files are omitted.
+#[tracing::instrument]
+pub fn timeline(
+    root_path: &Path,
+    by: TimeField,
+    bucket: Bucket,
+    bars: bool,
+    format: OutputFormat,
+    human: bool,
+) -> anyhow::Result<()> {
+    let mut buckets: BTreeMap<NaiveDate, (u64, u64)> = BTreeMap::new();
+    for file in top::find_files(root_path, data::FindOpts::default())? {
+        let time = by.of(&file);
+        let date = time::to_local(time)
+            .with_context(|| format!("path={:?} time={}", file.path, time));
+        if let Some(date) = on_error::handle(date, "Invalid timestamp.")? {
+            let (files, bytes) =
+                buckets.entry(bucket.start(date.date_naive())).or_default();
+            *files += 1;
+            *bytes += file.size;
+        }
+    }
+
+    let max_bytes = buckets.values().map(|(_, bytes)| *bytes).max();
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            human,
+            ..Default::default()
+        },
+    );
+    for (start, (files, bytes)) in buckets {
+        let mut row = vec![
+            ("bucket", Value::Text(bucket.label(start).into())),
+            ("files", Value::Int(i64::try_from(files)?)),
+            ("size", Value::Size(bytes)),
+        ];
+        if bars {
+            let width = match max_bytes {
+                Some(max) if max > 0 => {
+                    u128::from(bytes) * u128::from(BAR_WIDTH)
+                        / u128::from(max)
+                }
+                _ => 0,
+            };
+            let bar = "#".repeat(usize::try_from(width)?);
+            row.push(("bar", Value::Text(bar.into())));
+        }
+        reporter.row(&row)?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
@@ -1,137 +1,1196 @@
 use std::{
-    collections::HashMap,
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    ffi::OsStr,
+    io::Write,
+    os::unix::ffi::OsStrExt,
     path::{Path, PathBuf},
 };
 
-use anyhow::Context;
-use rayon::iter::IntoParallelRefIterator;
+use anyhow::bail;
+use bytesize::ByteSize;
 
-use crate::data;
+use crate::{
+    data::{self, Meta, SortKey, Stats},
+    on_error,
+    report::{self, OutputFormat, Value},
+    root::Root,
+};
+
+/// Which of the paths linking to a multiply-linked file are credited with
+/// its size, when each such file is counted once.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum HardlinkAttribution {
+    /// The first path, in path order, so the same from run to run.
+    #[default]
+    First,
 
-#[derive(clap::Args, Debug)]
+    /// All paths, each with an even share of the size, which answers
+    /// "whose is this space".
+    AllSplit,
+
+    /// The first path in the directory with the most of the links, which
+    /// answers "where does this space mostly live".
+    LargestDir,
+}
+
+#[derive(clap::Args, Debug, serde::Serialize)]
 pub struct Cmd {
     /// Report using human-readable (i.e. aggregated) units.
     #[clap(short = 'H', long)]
     human: bool,
 
+    /// Report sizes as exact byte counts. This is the default, but can be
+    /// given to make sure, e.g. in scripts, and conflicts with -H.
+    #[clap(long, conflicts_with = "human")]
+    bytes: bool,
+
+    /// Raw format only. Written between fields, e.g. size and path.
+    /// A tab by default.
+    #[clap(
+        long,
+        value_name = "SEP",
+        default_value = "\t",
+        hide_default_value = true
+    )]
+    field_separator: String,
+
+    /// Table and CSV formats only. Omit the header.
+    #[clap(long)]
+    no_header: bool,
+
     #[clap(short, long = "lim", default_value_t = 25)]
     limit: usize,
 
+    /// Report only the total size of all files under the root, like
+    /// "du -s", skipping per-directory aggregation and ranking.
+    #[clap(
+        short,
+        long,
+        conflicts_with_all = ["files", "exclude_root", "show_entry_count"]
+    )]
+    summarize: bool,
+
     /// Files instead of directories.
     #[clap(short, long)]
     files: bool,
 
-    #[clap(default_value = ".")]
-    root_path: PathBuf,
+    /// Rank by this instead of size. Only size is meaningful for
+    /// directories, so others require --files.
+    #[clap(long, value_enum, default_value_t = SortKey::Size)]
+    sort_by: SortKey,
+
+    /// Only include entries on filesystems mounted under the root, but not
+    /// the root's own filesystem. Useful for analyzing what is mounted under
+    /// a path (e.g. a container's overlay and bind mounts), while ignoring
+    /// the contents of the base filesystem.
+    #[clap(long)]
+    mounts_only: bool,
+
+    /// Follow symlinks, counting what they link to as if it was where the
+    /// links are. Each file is counted once, however many links lead to
+    /// it, and symlink cycles are not followed around.
+    #[clap(long)]
+    follow: bool,
+
+    /// The root is an archive (.tar, .tar.gz, .tgz or .zip), the entries of
+    /// which to rank, as if it was a directory, without extracting it.
+    /// Sizes are the uncompressed sizes, as recorded in the archive.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "mounts_only",
+            "follow",
+            "compare_apparent_vs_disk",
+        ]
+    )]
+    archive: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    format: OutputFormat,
+
+    /// Shorthand for "--format json": an array of objects, with sizes
+    /// always in bytes, regardless of -H.
+    #[clap(long, conflicts_with_all = ["format", "json_tree"])]
+    json: bool,
+
+    /// Instead of the ranking, output the whole tree of directory sizes as
+    /// nested JSON objects of path, size and children (largest first), as
+    /// expected by treemap visualizers. Paths not valid UTF-8 come with
+    /// their bytes, as with --json.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "limit",
+            "format",
+            "exclude_root",
+            "show_entry_count",
+        ]
+    )]
+    json_tree: bool,
+
+    /// Report only directories at most this deep below the root, which is
+    /// at depth 0, like "du --max-depth". Sizes are still of the whole
+    /// subtrees, so those at the limit include everything beneath them.
+    #[clap(long, value_name = "N", conflicts_with_all = ["files", "summarize"])]
+    max_depth: Option<usize>,
+
+    /// Omit from the report directories, or files with --files, whose
+    /// paths match this regular expression, e.g. "/\\.git(/|$)". They are
+    /// still counted in the sizes of their parents, only not listed.
+    #[clap(
+        long,
+        value_name = "REGEX",
+        conflicts_with_all = [
+            "summarize",
+            "json_tree",
+            "sparse_summary",
+            "max_memory",
+        ]
+    )]
+    #[serde(serialize_with = "crate::config::regex")]
+    hide_matching: Option<regex::Regex>,
+
+    /// Report only leaf directories, i.e. those without subdirectories
+    /// holding any files, since ancestors, credited with everything beneath
+    /// them, otherwise outrank the directories actually holding the data.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "json_tree",
+            "max_depth",
+            "accumulate_hidden_as_one",
+            "sparse_summary",
+        ]
+    )]
+    only_leaves: bool,
+
+    /// Report, along with each of the top directories, its top this many
+    /// files, from anywhere beneath it, as a group of rows: that of the
+    /// directory, followed by those of its files, largest first.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "json_tree",
+            "compare_apparent_vs_disk",
+            "accumulate_hidden_as_one",
+            "show_entry_count",
+            "sparse_summary",
+            "max_memory",
+        ]
+    )]
+    drill: Option<usize>,
+
+    /// Omit the root directory's own aggregate, which is otherwise
+    /// included and, being the total, always ranks largest.
+    #[clap(long, conflicts_with = "files")]
+    exclude_root: bool,
+
+    /// Report both apparent size and size on disk (allocated blocks), and
+    /// the ratio of the latter to the former. Ranked by apparent size.
+    /// Low ratios reveal sparse files, high ones small-file slack.
+    #[clap(
+        long,
+        conflicts_with_all = ["summarize", "json_tree", "max_memory"]
+    )]
+    compare_apparent_vs_disk: bool,
+
+    /// Report hidden directories (named with a leading dot) of each
+    /// directory as one ".hidden" entry of their total size, and none of
+    /// their subdirectories, so that the ranking is of visible content,
+    /// while hidden totals are still accounted for.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "json_tree",
+            "compare_apparent_vs_disk",
+        ]
+    )]
+    accumulate_hidden_as_one: bool,
+
+    /// Count each file with multiple hardlinks under the root once, rather
+    /// than once per link, crediting its size as per the given policy,
+    /// "first" if none is given. The policy must be given with "=", e.g.
+    /// "--hardlink-attribution=all-split".
+    #[clap(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "first",
+        conflicts_with_all = [
+            "max_memory",
+            "compare_apparent_vs_disk",
+            "sparse_summary",
+        ]
+    )]
+    hardlink_attribution: Option<HardlinkAttribution>,
+
+    /// Report only the totals of apparent size and of sparse holes, i.e.
+    /// of apparent size beyond allocated blocks, of all files under the
+    /// root. Shows how much of the apparent size is illusory.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "json_tree",
+            "compare_apparent_vs_disk",
+            "max_depth",
+            "exclude_root",
+            "accumulate_hidden_as_one",
+            "show_entry_count",
+            "max_memory",
+        ]
+    )]
+    sparse_summary: bool,
+
+    /// Add a column with the number of immediate entries of each directory.
+    /// Very wide directories slow down many tools, so are worth spotting.
+    #[clap(long, conflicts_with = "files")]
+    show_entry_count: bool,
+
+    /// Soft limit on memory used for gathered files, e.g. "2GiB". Beyond it,
+    /// only what the report needs is kept: the top files, or directory
+    /// aggregates. Estimated roughly, so leave some headroom.
+    #[clap(long)]
+    #[serde(serialize_with = "crate::config::byte_size")]
+    max_memory: Option<ByteSize>,
+
+    /// Instead of sizes under the root, rank directories by how fast they
+    /// grew, in bytes per day, between the snapshots, taken by snap, given
+    /// by --from and --to, by the times in their headers, e.g. to project
+    /// when a volume will fill. The root is that of the snapshots.
+    #[cfg(feature = "snap")]
+    #[clap(
+        long,
+        requires_all = ["from", "to"],
+        conflicts_with_all = [
+            "files",
+            "summarize",
+            "json_tree",
+            "max_depth",
+            "hide_matching",
+            "only_leaves",
+            "drill",
+            "compare_apparent_vs_disk",
+            "accumulate_hidden_as_one",
+            "hardlink_attribution",
+            "sparse_summary",
+            "show_entry_count",
+        ]
+    )]
+    rate: bool,
+
+    /// Earlier snapshot, for --rate.
+    #[cfg(feature = "snap")]
+    #[clap(long, value_name = "SNAPSHOT", requires = "rate")]
+    from: Option<PathBuf>,
+
+    /// Later snapshot, for --rate.
+    #[cfg(feature = "snap")]
+    #[clap(long, value_name = "SNAPSHOT", requires = "rate")]
+    to: Option<PathBuf>,
+
+    /// Print counts of all walked entries, by type, to stderr.
+    #[clap(long)]
+    stats: bool,
+
+    /// Print paths of entries of unrecognized type (e.g. whiteouts on some
+    /// filesystems) to stderr. These are otherwise absent from all results.
+    #[clap(long)]
+    report_unknown: bool,
+
+    #[clap(flatten)]
+    #[serde(flatten)]
+    root: Root,
 }
 
 impl Cmd {
     pub fn run(&self) -> anyhow::Result<()> {
-        let given = &self.root_path;
-        let canonicalized = self
-            .root_path
-            .canonicalize()
-            .context(format!("Failed to canonicalize path={:?}", given))?;
-        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
-        let root_path = canonicalized;
-        top(&root_path, self.files, Some(self.limit), self.human)?;
+        let report_opts = report::Options {
+            human: self.human,
+            field_sep: self.field_separator.clone(),
+            header: !self.no_header,
+            ..Default::default()
+        };
+        let format = if self.json {
+            OutputFormat::Json
+        } else {
+            self.format
+        };
+        #[cfg(feature = "snap")]
+        if let (true, Some(from), Some(to)) =
+            (self.rate, &self.from, &self.to)
+        {
+            return rate(
+                from,
+                to,
+                Some(self.limit),
+                self.exclude_root,
+                format,
+                report_opts,
+            );
+        }
+        let root_path = self.root.resolve()?;
+        let find_opts = data::FindOpts {
+            mounts_only: self.mounts_only,
+            follow_symlinks: self.follow,
+            archive: self.archive,
+            ..Default::default()
+        };
+        let opts = Options {
+            summarize: self.summarize,
+            report_files: self.files,
+            report_limit: Some(self.limit),
+            sort_by: self.sort_by,
+            format,
+            json_tree: self.json_tree,
+            max_depth: self.max_depth,
+            hide_matching: self.hide_matching.clone(),
+            only_leaves: self.only_leaves,
+            drill: self.drill,
+            exclude_root: self.exclude_root,
+            accumulate_hidden: self.accumulate_hidden_as_one,
+            sparse_summary: self.sparse_summary,
+            hardlink_attribution: self.hardlink_attribution,
+            show_entry_count: self.show_entry_count,
+            compare_disk: self.compare_apparent_vs_disk,
+            print_stats: self.stats,
+            report_unknown: self.report_unknown,
+            max_memory: self.max_memory.map(|size| size.as_u64()),
+        };
+        top(&root_path, find_opts, report_opts, &opts)?;
         Ok(())
     }
 }
 
+/// What to rank and how to report it, beyond the walk and the
+/// presentation, as resolved from the command line.
+#[derive(Debug)]
+pub struct Options {
+    pub summarize: bool,
+    pub report_files: bool,
+    pub report_limit: Option<usize>,
+    pub sort_by: SortKey,
+    pub format: OutputFormat,
+    pub json_tree: bool,
+    pub max_depth: Option<usize>,
+    pub hide_matching: Option<regex::Regex>,
+    pub only_leaves: bool,
+    pub drill: Option<usize>,
+    pub exclude_root: bool,
+    pub accumulate_hidden: bool,
+    pub sparse_summary: bool,
+    pub hardlink_attribution: Option<HardlinkAttribution>,
+    pub show_entry_count: bool,
+    pub compare_disk: bool,
+    pub print_stats: bool,
+    pub report_unknown: bool,
+    pub max_memory: Option<u64>,
+}
+
 #[tracing::instrument]
 pub fn top(
     root_path: &Path,
-    report_files: bool,
+    find_opts: data::FindOpts,
+    report_options: report::Options,
+    opts: &Options,
+) -> anyhow::Result<()> {
+    let &Options {
+        summarize,
+        report_files,
+        report_limit,
+        sort_by,
+        format,
+        json_tree,
+        max_depth,
+        ref hide_matching,
+        only_leaves,
+        drill,
+        exclude_root,
+        accumulate_hidden,
+        sparse_summary,
+        hardlink_attribution,
+        show_entry_count,
+        compare_disk,
+        print_stats,
+        report_unknown,
+        max_memory,
+    } = opts;
+    if data::is_stdin(root_path) && !(report_files || summarize) {
+        bail!(
+            "Directories cannot be aggregated from a list of paths. \
+            Try with --files or --summarize."
+        );
+    }
+    if find_opts.archive && data::is_stdin(root_path) {
+        bail!("An archive cannot be read from stdin. Give its path.");
+    }
+    if !report_files && sort_by != SortKey::Size {
+        bail!("Directories can only be sorted by size. Try with --files.");
+    }
+    let mut stats = Stats::default();
+    let mut entry_counts: HashMap<PathBuf, u64> = HashMap::new();
+    let mut gatherer = Gatherer {
+        root_path,
+        budget: max_memory,
+        footprint: 0,
+        aggregate_dirs: !report_files,
+        limit: report_limit,
+        sort_by,
+        gathered: Gathered::All(Vec::new()),
+    };
+    let inspect = |meta_result: &anyhow::Result<Meta>| {
+        stats.count(meta_result);
+        let parent = meta_result
+            .as_ref()
+            .ok()
+            .and_then(|meta| meta.path.parent());
+        if let (true, Some(parent)) = (show_entry_count, parent) {
+            *entry_counts.entry(parent.to_owned()).or_insert(0) += 1;
+        }
+    };
+    let (mut apparent, mut holes): (u64, u64) = (0, 0);
+    for file in walk_files(root_path, find_opts, inspect)? {
+        let file = file?;
+        if sparse_summary {
+            apparent = apparent.saturating_add(file.size);
+            holes = holes
+                .saturating_add(file.size.saturating_sub(file.on_disk()));
+        } else {
+            gatherer.add(file);
+        }
+    }
+    if report_unknown {
+        for path in &stats.unknown_paths {
+            eprintln!("Unknown file type: {:?}", path);
+        }
+    }
+    if print_stats {
+        eprint!("{}", stats);
+    }
+    if sparse_summary {
+        let mut reporter = report::new(format, report_options);
+        reporter.row(&[
+            ("apparent", Value::Size(apparent)),
+            ("holes", Value::Size(holes)),
+            ("path", Value::Path(root_path)),
+        ])?;
+        reporter.finish()?;
+        return Ok(());
+    }
+    let mut disk_sizes: HashMap<PathBuf, u64> = HashMap::new();
+    // Kept apart from those reported in --files mode, only to drill into.
+    let mut drill_files: Vec<Meta> = Vec::new();
+    let (mut files, mut dirs) = match gatherer.gathered {
+        Gathered::All(files) => {
+            let files = match hardlink_attribution {
+                Some(policy) => attribute_hardlinks(files, policy),
+                None => files,
+            };
+            // A file root has no directories to aggregate into, so it is
+            // reported as itself in either mode.
+            let root_is_file =
+                files.iter().any(|file| file.path == root_path);
+            if report_files || root_is_file || summarize {
+                (files, HashMap::new())
+            } else {
+                if compare_disk {
+                    let on_disk: HashMap<PathBuf, u64> = files
+                        .iter()
+                        .map(|file| (file.path.clone(), file.on_disk()))
+                        .collect();
+                    disk_sizes = count_dir_sizes(&on_disk, root_path);
+                }
+                let sizes: HashMap<PathBuf, u64> = files
+                    .iter()
+                    .map(|file| (file.path.clone(), file.size))
+                    .collect();
+                if drill.is_some() {
+                    drill_files = files;
+                }
+                (Vec::new(), count_dir_sizes(&sizes, root_path))
+            }
+        }
+        Gathered::Top(files) => {
+            let files = files.into_iter().map(|Reverse(r)| r.meta).collect();
+            (files, HashMap::new())
+        }
+        Gathered::Dirs(dirs) => (Vec::new(), dirs),
+    };
+    // Only dropped from the report after being summed in full, so that
+    // those which remain have the sizes of their whole subtrees.
+    if let Some(max_depth) = max_depth {
+        dirs.retain(|dir, _| {
+            dir.strip_prefix(root_path).is_ok_and(|relative| {
+                relative.components().count() <= max_depth
+            })
+        });
+    }
+    // Before any are hidden, so that their parents are not taken for
+    // leaves.
+    if only_leaves {
+        let parents: HashSet<PathBuf> = dirs
+            .keys()
+            .filter_map(|dir| dir.parent())
+            .map(Path::to_path_buf)
+            .collect();
+        dirs.retain(|dir, _| !parents.contains(dir));
+    }
+    if let Some(pattern) = hide_matching {
+        let hidden = |path: &Path| pattern.is_match(&path.to_string_lossy());
+        dirs.retain(|dir, _| !hidden(dir));
+        files.retain(|file| !hidden(&file.path));
+        drill_files.retain(|file| !hidden(&file.path));
+    }
+    if json_tree {
+        // Only a file root leaves files here, as a tree of itself.
+        let sizes: HashMap<PathBuf, u64> = if files.is_empty() {
+            dirs
+        } else {
+            files
+                .into_iter()
+                .map(|file| (file.path, file.size))
+                .collect()
+        };
+        report_tree(root_path, &sizes)?;
+    } else if summarize {
+        let total = match dirs.get(root_path) {
+            Some(total) => *total,
+            None => files.iter().map(|file| file.size).sum(),
+        };
+        let total = std::iter::once((root_path.to_owned(), total));
+        report(
+            total,
+            &HashMap::new(),
+            &HashMap::new(),
+            None,
+            format,
+            report_options,
+        )?;
+    } else if !files.is_empty() {
+        let files = sort(files, |a, b| sort_by.cmp(a, b), report_limit);
+        let notes = sparse_notes(&files, report_options.human);
+        if compare_disk {
+            disk_sizes = files
+                .iter()
+                .map(|file| (file.path.clone(), file.on_disk()))
+                .collect();
+        }
+        let sizes = files.into_iter().map(|file| (file.path, file.size));
+        report(
+            sizes,
+            &notes,
+            &HashMap::new(),
+            compare_disk.then_some(&disk_sizes),
+            format,
+            report_options,
+        )?;
+    } else {
+        if exclude_root {
+            dirs.remove(root_path);
+        }
+        if accumulate_hidden {
+            dirs = accumulate_hidden_as_one(dirs, root_path);
+        }
+        let dirs: Vec<(PathBuf, u64)> = dirs.into_iter().collect();
+        // Ties broken by path, as for files, so that which of them make
+        // the limit is the same from run to run.
+        let dirs = sort(
+            dirs,
+            |a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)),
+            report_limit,
+        );
+        match drill {
+            Some(n) => report_drill(
+                dirs,
+                drill_files,
+                |a, b| sort_by.cmp(a, b),
+                n,
+                format,
+                report_options,
+            )?,
+            None => report(
+                dirs.into_iter(),
+                &HashMap::new(),
+                &entry_counts,
+                compare_disk.then_some(&disk_sizes),
+                format,
+                report_options,
+            )?,
+        }
+    }
+    Ok(())
+}
+
+/// Directories by how fast they grew between two snapshots, in bytes per
+/// day, of regular files beneath them, fastest last, as sizes are ranked.
+/// Those which shrank have negative rates and those which are in only one
+/// of the snapshots are taken to be empty in the other.
+#[cfg(feature = "snap")]
+#[tracing::instrument]
+pub fn rate(
+    from: &Path,
+    to: &Path,
     report_limit: Option<usize>,
-    human: bool,
+    exclude_root: bool,
+    format: OutputFormat,
+    options: report::Options,
 ) -> anyhow::Result<()> {
-    let files: HashMap<PathBuf, u64> = data::find(root_path)?
-        .filter_map(|meta_result| match meta_result {
-            Ok(
-                meta @ data::Meta {
-                    typ: data::FileType::Regular,
-                    ..
-                },
-            ) => Some((meta.path, meta.size)),
-            Ok(_) => None,
-            Err(error) => {
-                tracing::error!(?error, "Metadata collection failed.");
-                None
+    const SECS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+
+    let load = |path: &Path| -> anyhow::Result<_> {
+        let (header, entries) = crate::cmd::snap::read(path)?;
+        let mut dirs: HashMap<PathBuf, u64> = HashMap::new();
+        for meta_result in entries {
+            let meta = meta_result?;
+            if meta.is_regular_file() {
+                add_to_dirs(&mut dirs, &meta, &header.root);
             }
+        }
+        Ok((header, dirs))
+    };
+    let (header_a, sizes_a) = load(from)?;
+    let (header_b, sizes_b) = load(to)?;
+    if header_a.root != header_b.root {
+        bail!(
+            "Snapshots are of different roots: {:?} and {:?}",
+            header_a.root,
+            header_b.root
+        );
+    }
+    if header_b.time <= header_a.time {
+        bail!(
+            "Snapshot {:?} must be taken after {:?}, by the times in their \
+            headers.",
+            to,
+            from
+        );
+    }
+    let days = (header_b.time - header_a.time) as f64 / SECS_PER_DAY;
+    let mut dirs: HashSet<&PathBuf> = sizes_a.keys().collect();
+    dirs.extend(sizes_b.keys());
+    if exclude_root {
+        dirs.remove(&header_b.root);
+    }
+    let rates: Vec<(&PathBuf, f64)> = dirs
+        .into_iter()
+        .map(|dir| {
+            let a = sizes_a.get(dir).copied().unwrap_or(0);
+            let b = sizes_b.get(dir).copied().unwrap_or(0);
+            (dir, (b as f64 - a as f64) / days)
         })
         .collect();
+    let rates = sort(
+        rates,
+        |a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)),
+        report_limit,
+    );
 
-    let sizes: HashMap<PathBuf, u64> = {
-        if report_files {
-            files
+    let human = options.human;
+    let mut reporter = report::new(format, options);
+    for (dir, rate) in rates {
+        let rate = if human {
+            let sign = if rate < 0.0 { "-" } else { "" };
+            let per_day = report::fmt_size(rate.abs().round() as u64, true);
+            Value::Text(format!("{sign}{per_day}/day").into())
         } else {
-            count_dir_sizes(files, root_path)
-        }
-    };
-    let sizes = sort(sizes.into_iter(), report_limit);
-    report(sizes, human);
+            Value::Int(rate.round() as i64)
+        };
+        let size = sizes_b.get(dir).copied().unwrap_or(0);
+        reporter.row(&[
+            ("rate", rate),
+            ("size", Value::Size(size)),
+            ("path", Value::Path(dir)),
+        ])?;
+    }
+    reporter.finish()?;
+    Ok(())
+}
+
+/// Each directory in a group with its top n files from beneath it.
+fn report_drill(
+    dirs: Vec<(PathBuf, u64)>,
+    files: Vec<Meta>,
+    cmp: impl Fn(&Meta, &Meta) -> Ordering,
+    n: usize,
+    format: OutputFormat,
+    options: report::Options,
+) -> anyhow::Result<()> {
+    let mut reporter = report::new(format, options);
+    for (dir, size) in &dirs {
+        let mut beneath: Vec<&Meta> = files
+            .iter()
+            .filter(|file| file.path.starts_with(dir))
+            .collect();
+        beneath.sort_by(|a, b| cmp(b, a));
+        beneath.truncate(n);
+        let mut rows: Vec<Vec<report::Field>> = vec![vec![
+            ("size", Value::Size(*size)),
+            ("path", Value::Path(dir)),
+        ]];
+        rows.extend(beneath.iter().map(|file| {
+            vec![
+                ("size", Value::Size(file.size)),
+                ("path", Value::Path(&file.path)),
+            ]
+        }));
+        reporter.group(&rows)?;
+    }
+    reporter.finish()?;
     Ok(())
 }
 
+/// Regular files gathered for the report. Kept whole until they would
+/// exceed the memory budget, after which only what the report needs is
+/// kept.
+enum Gathered {
+    All(Vec<Meta>),
+
+    /// Top N files by the sort key.
+    Top(BinaryHeap<Reverse<Ranked>>),
+
+    /// Sizes aggregated per directory, with the files discarded.
+    Dirs(HashMap<PathBuf, u64>),
+}
+
+struct Gatherer<'a> {
+    root_path: &'a Path,
+    budget: Option<u64>,
+    footprint: u64,
+    aggregate_dirs: bool,
+    limit: Option<usize>,
+    sort_by: SortKey,
+    gathered: Gathered,
+}
+
+impl Gatherer<'_> {
+    fn add(&mut self, file: Meta) {
+        match &mut self.gathered {
+            Gathered::All(files) => {
+                // Rough, but proportional, estimate of what is held.
+                self.footprint +=
+                    (size_of::<Meta>() + file.path.as_os_str().len()) as u64;
+                files.push(file);
+                if self.budget.is_some_and(|b| self.footprint > b) {
+                    self.switch();
+                }
+            }
+            Gathered::Top(files) => {
+                files.push(Reverse(Ranked {
+                    key: self.sort_by,
+                    meta: file,
+                }));
+                if self.limit.is_some_and(|n| files.len() > n) {
+                    files.pop();
+                }
+            }
+            Gathered::Dirs(dirs) => {
+                add_to_dirs(dirs, &file, self.root_path);
+            }
+        }
+    }
+
+    fn switch(&mut self) {
+        let Gathered::All(files) = &mut self.gathered else {
+            return;
+        };
+        let files = std::mem::take(files);
+        let strategy = if self.aggregate_dirs {
+            "aggregating directories as files are found"
+        } else {
+            "keeping only the top files"
+        };
+        eprintln!(
+            "Warning: memory budget exceeded after {} files. \
+            Switching to {}.",
+            files.len(),
+            strategy
+        );
+        self.gathered = if self.aggregate_dirs {
+            Gathered::Dirs(HashMap::new())
+        } else {
+            Gathered::Top(BinaryHeap::new())
+        };
+        for file in files {
+            self.add(file);
+        }
+    }
+}
+
+/// Meta ordered by the given key, for use in a heap.
+struct Ranked {
+    key: SortKey,
+    meta: Meta,
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&self.meta, &other.meta)
+    }
+}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+/// Sequential counterpart of count_dir_sizes, one file at a time.
+fn add_to_dirs(dirs: &mut HashMap<PathBuf, u64>, file: &Meta, root: &Path) {
+    for dir in file.path.ancestors().skip(1) {
+        if !dir.starts_with(root) {
+            break;
+        }
+        *dirs.entry(dir.to_owned()).or_insert(0) += file.size;
+    }
+}
+
+/// Apparent size of a sparse file can vastly exceed its footprint on disk,
+/// which would be misleading in a ranking, so we point those out.
+fn sparse_notes(files: &[Meta], human: bool) -> HashMap<PathBuf, String> {
+    // Small enough holes aren't worth the noise.
+    const MIN_HOLE: u64 = 1024 * 1024;
+
+    let mut notes = HashMap::new();
+    for Meta {
+        path, size, blocks, ..
+    } in files
+    {
+        let on_disk = blocks.saturating_mul(512);
+        if on_disk.saturating_mul(2) < *size
+            && size.saturating_sub(on_disk) >= MIN_HOLE
+        {
+            tracing::warn!(?path, size, on_disk, "Sparse file.");
+            notes.insert(
+                path.clone(),
+                format!(
+                    "sparse: {} on disk",
+                    report::fmt_size(on_disk, human)
+                ),
+            );
+        }
+    }
+    notes
+}
+
+pub(crate) fn find_files(
+    root_path: &Path,
+    find_opts: data::FindOpts,
+) -> anyhow::Result<Vec<Meta>> {
+    find_files_inspecting(root_path, find_opts, |_| ())
+}
+
+/// Like find_files, but also passes every walked entry to the given
+/// function, before filtering.
+pub(crate) fn find_files_inspecting(
+    root_path: &Path,
+    find_opts: data::FindOpts,
+    inspect: impl FnMut(&anyhow::Result<Meta>),
+) -> anyhow::Result<Vec<Meta>> {
+    walk_files(root_path, find_opts, inspect)?.collect()
+}
+
+/// Regular files under the root, as found, without collecting them.
+/// Failures are handled by the error policy, so any passed on are to abort
+/// with.
+fn walk_files(
+    root_path: &Path,
+    find_opts: data::FindOpts,
+    inspect: impl FnMut(&anyhow::Result<Meta>),
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
+    let metas = data::find_with(root_path, find_opts)?.inspect(inspect);
+    let files = on_error::filter(metas, "Metadata collection failed.")
+        .filter(|meta_result| {
+            meta_result.as_ref().map_or(true, Meta::is_regular_file)
+        });
+    Ok(files)
+}
+
+pub(crate) fn file_sizes(
+    root_path: &Path,
+) -> anyhow::Result<HashMap<PathBuf, u64>> {
+    let files: HashMap<PathBuf, u64> =
+        find_files(root_path, data::FindOpts::default())?
+            .into_iter()
+            .map(|file| (file.path, file.size))
+            .collect();
+    Ok(files)
+}
+
+/// Total size of files under each directory, up to and including the root,
+/// which thus holds the grand total.
+///
+/// Aggregated bottom-up, each directory into its parent, once, rather than
+/// each file into all its ancestors, which, in deep trees, would multiply
+/// the work by the depth.
 #[tracing::instrument(skip(files))]
-fn count_dir_sizes(
-    files: HashMap<PathBuf, u64>,
+pub(crate) fn count_dir_sizes(
+    files: &HashMap<PathBuf, u64>,
     root_path: &Path,
 ) -> HashMap<PathBuf, u64> {
-    use dashmap::DashMap;
-    use rayon::iter::ParallelIterator;
-
-    let dirs: DashMap<PathBuf, u64> = DashMap::new();
-    files.par_iter().for_each(|(file, size)| {
-        // Skip self.
-        for dir in file.ancestors().skip(1) {
-            // Don't go above requested root:
-            if dir.starts_with(root_path) {
-                *dirs.entry(dir.to_owned()).or_insert(0) += size;
+    // Don't go above requested root.
+    let within_root = |dir: &&Path| dir.starts_with(root_path);
+
+    // Own sizes, of files immediately in each directory.
+    let mut dirs: HashMap<PathBuf, u64> = HashMap::new();
+    for (file, size) in files {
+        if let Some(parent) = file.parent().filter(within_root) {
+            *dirs.entry(parent.to_owned()).or_insert(0) += size;
+        }
+    }
+
+    // Ancestors holding no files of their own. Each chain is only walked
+    // up until it joins one already added, whose ancestors are, or will be,
+    // added in turn.
+    let holding: Vec<PathBuf> = dirs.keys().cloned().collect();
+    for dir in holding {
+        for ancestor in dir.ancestors().skip(1) {
+            if !within_root(&ancestor) || dirs.contains_key(ancestor) {
+                break;
             }
+            dirs.insert(ancestor.to_owned(), 0);
+        }
+    }
+
+    // Longer paths first, so that each directory is complete, with all of
+    // its (longer-pathed) descendants, by the time it is added to its
+    // parent.
+    let mut order: Vec<PathBuf> = dirs.keys().cloned().collect();
+    order.sort_by_key(|dir| Reverse(dir.as_os_str().len()));
+    for dir in order {
+        let size = dirs.get(&dir).copied().unwrap_or(0);
+        if let Some(parent) = dir.parent().filter(within_root) {
+            *dirs.entry(parent.to_owned()).or_insert(0) += size;
         }
-    });
-    dirs.into_iter().collect()
+    }
+    dirs
 }
 
-#[tracing::instrument(skip(sizes))]
-fn sort(
-    sizes: impl Iterator<Item = (PathBuf, u64)>,
+/// Files with multiple links under the root reduced to those credited with
+/// their sizes by the policy, so that each is counted once in total.
+fn attribute_hardlinks(
+    files: Vec<Meta>,
+    policy: HardlinkAttribution,
+) -> Vec<Meta> {
+    let (linked, mut attributed): (Vec<Meta>, Vec<Meta>) =
+        files.into_iter().partition(|file| file.nlink > 1);
+    let mut inodes: HashMap<(u64, u64), Vec<Meta>> = HashMap::new();
+    for file in linked {
+        inodes.entry((file.dev, file.ino)).or_default().push(file);
+    }
+    for mut links in inodes.into_values() {
+        links.sort_by(Meta::cmp_by_path);
+        match policy {
+            HardlinkAttribution::First => {
+                attributed.extend(links.into_iter().take(1));
+            }
+            HardlinkAttribution::AllSplit => {
+                let count = links.len() as u64;
+                let size = links.first().map_or(0, |link| link.size);
+                // Remainder to the first, so that shares sum to the size.
+                let (share, remainder) = (size / count, size % count);
+                for (i, mut link) in links.into_iter().enumerate() {
+                    link.size =
+                        if i == 0 { share + remainder } else { share };
+                    attributed.push(link);
+                }
+            }
+            HardlinkAttribution::LargestDir => {
+                let mut dirs: BTreeMap<Option<&Path>, usize> =
+                    BTreeMap::new();
+                for link in &links {
+                    *dirs.entry(link.path.parent()).or_insert(0) += 1;
+                }
+                // Ties to the first dir by path: max_by_key takes the
+                // last of equals, so iterate in reverse.
+                let dir = dirs
+                    .into_iter()
+                    .rev()
+                    .max_by_key(|(_, count)| *count)
+                    .map(|(dir, _)| dir.map(Path::to_path_buf));
+                let credited = links.iter().position(|link| {
+                    dir.as_ref().is_some_and(|dir| {
+                        link.path.parent() == dir.as_deref()
+                    })
+                });
+                if let Some(i) = credited {
+                    attributed.push(links.swap_remove(i));
+                }
+            }
+        }
+    }
+    attributed
+}
+
+/// Hidden directories directly in each directory replaced by one ".hidden"
+/// entry of their total, and directories under hidden ones dropped, being
+/// already accounted for in those totals. Hidden-ness is judged only below
+/// the root, which may itself be anywhere.
+#[tracing::instrument(skip(dirs))]
+fn accumulate_hidden_as_one(
+    dirs: HashMap<PathBuf, u64>,
+    root_path: &Path,
+) -> HashMap<PathBuf, u64> {
+    let is_hidden = |name: &OsStr| name.as_bytes().starts_with(b".");
+    let mut accumulated: HashMap<PathBuf, u64> = HashMap::new();
+    for (dir, size) in dirs {
+        let Ok(relative) = dir.strip_prefix(root_path) else {
+            accumulated.insert(dir, size);
+            continue;
+        };
+        let mut components = relative.components();
+        let name = components.next_back();
+        let under_hidden = components.any(|c| is_hidden(c.as_os_str()));
+        match (name, dir.parent()) {
+            _ if under_hidden => {}
+            (Some(name), Some(parent)) if is_hidden(name.as_os_str()) => {
+                *accumulated.entry(parent.join(".hidden")).or_insert(0) +=
+                    size;
+            }
+            _ => {
+                *accumulated.entry(dir).or_insert(0) += size;
+            }
+        }
+    }
+    accumulated
+}
+
+#[tracing::instrument(skip_all)]
+fn sort<T>(
+    mut items: Vec<T>,
+    cmp: impl Fn(&T, &T) -> Ordering,
     report_limit: Option<usize>,
-) -> impl Iterator<Item = (PathBuf, u64)> {
+) -> Vec<T> {
     tracing::debug!("BEGIN");
-    let mut sizes: Vec<(PathBuf, u64)> =
-        sizes.map(|(p, s)| (p.to_owned(), s)).collect();
 
     // Largest on top.
-    sizes.sort_by(|a, b| b.1.cmp(&a.1));
+    items.sort_by(|a, b| cmp(b, a));
 
     // Take top largest.
-    let mut sizes = match report_limit {
-        None => sizes,
-        Some(n) => sizes.into_iter().take(n).collect(),
-    };
+    if let Some(n) = report_limit {
+        items.truncate(n);
+    }
 
     // Largest on bottom.
-    sizes.reverse();
+    items.reverse();
 
-    sizes.into_iter()
+    items
 }
 
+/// Sizes as a tree of nested JSON objects, from the root down, with each
+/// directory's children ordered largest first, then by path.
 #[tracing::instrument(skip(sizes))]
-fn report(sizes: impl Iterator<Item = (PathBuf, u64)>, human: bool) {
-    let mut table = comfy_table::Table::new();
-    table.load_preset(comfy_table::presets::NOTHING); // No borders or dividers.
-    table.set_header(["SIZE", "PATH"]);
+fn report_tree(
+    root_path: &Path,
+    sizes: &HashMap<PathBuf, u64>,
+) -> anyhow::Result<()> {
+    let mut children: HashMap<&Path, Vec<(&Path, u64)>> = HashMap::new();
     for (path, size) in sizes {
-        let size = if human {
-            bytesize::ByteSize(size).to_string()
-        } else {
-            size.to_string()
+        if let Some(parent) = path.parent().filter(|_| path != root_path) {
+            children.entry(parent).or_default().push((path, *size));
+        }
+    }
+    for siblings in children.values_mut() {
+        siblings.sort_by(|(path_a, size_a), (path_b, size_b)| {
+            size_b.cmp(size_a).then_with(|| path_a.cmp(path_b))
+        });
+    }
+    let size = sizes.get(root_path).copied().unwrap_or(0);
+    let tree = tree_node(root_path, size, &children);
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer(&mut out, &tree)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn tree_node(
+    path: &Path,
+    size: u64,
+    children: &HashMap<&Path, Vec<(&Path, u64)>>,
+) -> serde_json::Value {
+    let nodes: Vec<serde_json::Value> = children
+        .get(path)
+        .into_iter()
+        .flatten()
+        .map(|(child, size)| tree_node(child, *size, children))
+        .collect();
+    let mut node = report::to_json(&[
+        ("path", Value::Path(report::trim_prefix(path))),
+        ("size", Value::Size(size)),
+    ]);
+    node["children"] = serde_json::Value::from(nodes);
+    node
+}
+
+#[tracing::instrument(skip(sizes, notes, entry_counts))]
+fn report(
+    sizes: impl Iterator<Item = (PathBuf, u64)>,
+    notes: &HashMap<PathBuf, String>,
+    entry_counts: &HashMap<PathBuf, u64>,
+    disk_sizes: Option<&HashMap<PathBuf, u64>>,
+    format: OutputFormat,
+    options: report::Options,
+) -> anyhow::Result<()> {
+    let mut reporter = report::new(format, options);
+    for (path, size) in sizes {
+        let mut row = match disk_sizes {
+            None => vec![("size", Value::Size(size))],
+            Some(disk_sizes) => {
+                let disk = disk_sizes.get(&path).copied().unwrap_or(0);
+                let ratio = if size == 0 {
+                    "-".to_string()
+                } else {
+                    format!("{:.2}", disk as f64 / size as f64)
+                };
+                vec![
+                    ("apparent", Value::Size(size)),
+                    ("disk", Value::Size(disk)),
+                    ("ratio", Value::Text(ratio.into())),
+                ]
+            }
         };
-        let path = path.to_string_lossy().to_string();
-        table.add_row(vec![&size, &path]);
+        if !entry_counts.is_empty() {
+            let count = entry_counts.get(&path).copied().unwrap_or(0);
+            row.push(("entries", Value::Int(i64::try_from(count)?)));
+        }
+        row.push(("path", Value::Path(&path)));
+        if !notes.is_empty() {
+            let note = notes.get(&path).map_or("", String::as_str);
+            row.push(("note", Value::Text(note.into())));
+        }
+        reporter.row(&row)?;
     }
-    println!("{table}");
+    reporter.finish()?;
+    Ok(())
 }
@@ -0,0 +1,296 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow, bail};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    data::{self, Meta},
+    hash, on_error,
+    report::{self, OutputFormat, Value},
+};
+
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Algo {
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl Algo {
+    /// From manifest file stem or extension, as in "SHA256SUMS", "B3SUMS",
+    /// "foo.sha256" or "foo.blake3", falling back to hash length, which is
+    /// ambiguous for 256-bit hashes, which are then assumed to be SHA2-256,
+    /// as with sha256sum.
+    fn infer(manifest: &Path, hash_len: usize) -> anyhow::Result<Self> {
+        let words: Vec<String> = [manifest.file_stem(), manifest.extension()]
+            .into_iter()
+            .flatten()
+            .map(|word| word.to_string_lossy().to_lowercase())
+            .collect();
+        // By its name alone, or followed by "sum" or "sums".
+        let named = |names: &[&str]| {
+            words.iter().any(|word| {
+                names.iter().any(|name| {
+                    word.strip_prefix(name).is_some_and(|rest| {
+                        ["", "sum", "sums"].contains(&rest)
+                    })
+                })
+            })
+        };
+        match () {
+            _ if named(&["sha256"]) => Ok(Self::Sha256),
+            _ if named(&["sha512"]) => Ok(Self::Sha512),
+            _ if named(&["b3", "blake3"]) => Ok(Self::Blake3),
+            _ if hash_len == 32 => Ok(Self::Sha256),
+            _ if hash_len == 64 => Ok(Self::Sha512),
+            _ => Err(anyhow!(
+                "Cannot infer hash algorithm of manifest={manifest:?}. \
+                Specify it with --algo."
+            )),
+        }
+    }
+
+    fn hash(
+        &self,
+        path: &Path,
+        chunk_size: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Sha256 => hash::sha2_256(path, chunk_size),
+            Self::Sha512 => hash::sha2_512(path, chunk_size),
+            Self::Blake3 => hash::blake3(path, chunk_size),
+        }
+    }
+}
+
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Cmd {
+    /// Checksum file, in the format of sha256sum and friends, with paths
+    /// relative to the root.
+    #[clap(short, long)]
+    manifest: PathBuf,
+
+    /// Hash algorithm of the manifest. Inferred when not given.
+    #[clap(short, long, value_enum)]
+    algo: Option<Algo>,
+
+    /// Byte size of chunks to read at a time.
+    #[clap(short, long = "chunk", default_value_t = 8192)]
+    chunk_size: usize,
+
+    /// Also report files which are in the tree, but not in the manifest.
+    #[clap(short, long)]
+    extra: bool,
+
+    /// Output format.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+
+    #[clap(default_value = ".")]
+    root_path: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let given = &self.root_path;
+        let canonicalized = self
+            .root_path
+            .canonicalize()
+            .context(format!("Failed to canonicalize path={:?}", given))?;
+        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
+        let root_path = canonicalized;
+        verify(
+            &root_path,
+            &self.manifest,
+            self.algo,
+            self.chunk_size,
+            self.extra,
+            self.format,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Failed,
+    Missing,
+    Extra,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Failed => "FAILED",
+            Self::Missing => "MISSING",
+            Self::Extra => "EXTRA",
+        }
+    }
+}
+
+/// Like "sha256sum -c", reports per-file status, but also which files are
+/// missing from the tree, and, optionally, which are extra in it.
+/// Fails if any file failed verification or is missing.
+#[tracing::instrument]
+pub fn verify(
+    root_path: &Path,
+    manifest: &Path,
+    algo: Option<Algo>,
+    chunk_size: usize,
+    report_extra: bool,
+    format: OutputFormat,
+) -> anyhow::Result<()> {
+    let entries = read_manifest(manifest)?;
+    let algo = match (algo, entries.first()) {
+        (Some(algo), _) => algo,
+        (None, Some((hash, _))) => Algo::infer(manifest, hash.len())?,
+        (None, None) => Algo::Sha256, // Nothing to check anyway.
+    };
+    tracing::debug!(entries = entries.len(), ?algo, "Manifest loaded.");
+    let results: Vec<(PathBuf, Status)> = entries
+        .par_iter()
+        .map(|(expected, path)| {
+            let full_path = root_path.join(path);
+            let status = match algo.hash(&full_path, chunk_size) {
+                Ok(actual) if &actual == expected => Status::Ok,
+                Ok(_) => Status::Failed,
+                Err(error) => {
+                    if full_path.symlink_metadata().is_ok() {
+                        tracing::error!(
+                            ?error,
+                            path = ?full_path,
+                            "Failed to hash."
+                        );
+                        Status::Failed
+                    } else {
+                        Status::Missing
+                    }
+                }
+            };
+            (path.clone(), status)
+        })
+        .collect();
+    let mut results = results;
+    if report_extra {
+        let listed: HashSet<PathBuf> = entries
+            .iter()
+            .map(|(_, path)| root_path.join(path))
+            .collect();
+        for meta_result in on_error::filter(
+            data::find(root_path)?,
+            "Failure while finding files.",
+        ) {
+            let meta = meta_result?;
+            if meta.is_regular_file() && !listed.contains(&meta.path) {
+                let Meta { path, .. } = meta;
+                let path = path
+                    .strip_prefix(root_path)
+                    .map(Path::to_path_buf)
+                    .unwrap_or(path);
+                results.push((path, Status::Extra));
+            }
+        }
+    }
+
+    let mut reporter = report::new(
+        format,
+        report::Options {
+            field_sep: ": ".to_string(),
+            ..Default::default()
+        },
+    );
+    for (path, status) in &results {
+        reporter.row(&[
+            ("path", Value::Path(path)),
+            ("status", Value::Text(status.as_str().into())),
+        ])?;
+    }
+    reporter.finish()?;
+
+    let count = |status| results.iter().filter(|(_, s)| *s == status).count();
+    let (ok, failed, missing, extra) = (
+        count(Status::Ok),
+        count(Status::Failed),
+        count(Status::Missing),
+        count(Status::Extra),
+    );
+    eprintln!(
+        "OK: {ok}, FAILED: {failed}, MISSING: {missing}, EXTRA: {extra}"
+    );
+    if failed > 0 || missing > 0 {
+        bail!("Verification failed.");
+    }
+    Ok(())
+}
+
+/// Lines of "<hex hash> <path>", where the path may be prefixed with a
+/// binary-mode "*", and lines with escaped paths are prefixed with "\".
+/// Read as bytes, since paths need not be valid UTF-8.
+fn read_manifest(manifest: &Path) -> anyhow::Result<Vec<(Vec<u8>, PathBuf)>> {
+    let data = fs::read(manifest)
+        .context(format!("Failed to read manifest from path={manifest:?}"))?;
+    let mut entries = Vec::new();
+    for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+        if line.trim_ascii().is_empty() {
+            continue;
+        }
+        let (line, escaped) = match line.strip_prefix(b"\\") {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+        let invalid =
+            || anyhow!("Invalid manifest line {} in {manifest:?}", i + 1);
+        let space =
+            line.iter().position(|&b| b == b' ').ok_or_else(invalid)?;
+        let (hash, path) = (&line[..space], &line[space + 1..]);
+        let path = match path.first() {
+            Some(b' ' | b'*') => &path[1..],
+            _ => path,
+        };
+        let path = if escaped {
+            unescape(path)
+        } else {
+            path.to_vec()
+        };
+        let hash = std::str::from_utf8(hash)
+            .map_err(anyhow::Error::from)
+            .and_then(hash::from_hex)
+            .context(format!(
+                "Invalid hash on manifest line {} in {manifest:?}",
+                i + 1
+            ))?;
+        entries.push((hash, PathBuf::from(OsStr::from_bytes(&path))));
+    }
+    Ok(entries)
+}
+
+/// Path of an escaped manifest line, in which backslashes, linefeeds and
+/// carriage returns are written as "\\", "\n" and "\r".
+fn unescape(path: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(path.len());
+    let mut bytes = path.iter().copied().peekable();
+    while let Some(b) = bytes.next() {
+        let escaped = match (b, bytes.peek()) {
+            (b'\\', Some(b'\\')) => b'\\',
+            (b'\\', Some(b'n')) => b'\n',
+            (b'\\', Some(b'r')) => b'\r',
+            _ => {
+                unescaped.push(b);
+                continue;
+            }
+        };
+        bytes.next();
+        unescaped.push(escaped);
+    }
+    unescaped
+}
@@ -0,0 +1,77 @@
+use std::{ffi::OsString, fmt, io::Write, time::Duration};
+
+use bytesize::ByteSize;
+use serde::{Serialize, Serializer};
+
+/// Print the effective options as pretty JSON to stdout, by field names.
+/// Fields of types without a readable serialization of their own are
+/// converted by the helpers below.
+pub fn dump(options: &impl Serialize) -> anyhow::Result<()> {
+    let mut out = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut out, options)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+pub fn display<T: fmt::Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Lossily, as UTF-8 strings, rather than as OS-specific byte arrays.
+pub fn os_strings<S: Serializer>(
+    values: &[OsString],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(values.iter().map(|value| value.to_string_lossy()))
+}
+
+/// Lossily, as a UTF-8 string.
+pub fn os_string<S: Serializer>(
+    value: &Option<OsString>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value
+        .as_ref()
+        .map(|value| value.to_string_lossy())
+        .serialize(serializer)
+}
+
+/// As its pattern.
+pub fn regex<S: Serializer>(
+    value: &Option<regex::Regex>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value
+        .as_ref()
+        .map(|value| value.as_str())
+        .serialize(serializer)
+}
+
+/// In bytes.
+pub fn byte_size<S: Serializer>(
+    size: &Option<ByteSize>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    size.map(|size| size.as_u64()).serialize(serializer)
+}
+
+/// In seconds.
+pub fn secs<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+/// In seconds.
+pub fn opt_secs<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    duration
+        .map(|duration| duration.as_secs())
+        .serialize(serializer)
+}
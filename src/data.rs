@@ -1,19 +1,32 @@
 use std::{
-    collections::HashSet,
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
     ffi::{OsStr, OsString},
     fs,
-    os::unix::fs::{FileTypeExt, MetadataExt},
+    io::{self, Read},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt},
+    },
     path::{Path, PathBuf},
 };
 
 use anyhow::Context;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{archive, on_error, path, slow};
 
 // Ref: https://pubs.opengroup.org/onlinepubs/009604499/basedefs/sys/stat.h.html
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum FileType {
     Regular,
     Directory,
-    Symlink { dst: PathBuf },
+    Symlink {
+        #[serde(with = "lossless_path")]
+        dst: PathBuf,
+    },
 
     Sock,
     Fifo,
@@ -24,8 +37,73 @@ pub enum FileType {
     Unknown,
 }
 
-#[derive(Clone, Debug)]
+impl FileType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Regular => "regular",
+            Self::Directory => "directory",
+            Self::Symlink { .. } => "symlink",
+            Self::Sock => "sock",
+            Self::Fifo => "fifo",
+            Self::DevChar => "dev_char",
+            Self::DevBlock => "dev_block",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Major number of a device ID, as encoded by Linux (glibc's "major").
+pub fn dev_major(dev: u64) -> u64 {
+    ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff)
+}
+
+/// Minor number of a device ID, as encoded by Linux (glibc's "minor").
+pub fn dev_minor(dev: u64) -> u64 {
+    (dev & 0xff) | ((dev >> 12) & !0xff)
+}
+
+/// As a string, if it is valid UTF-8, or else as an array of its bytes, so
+/// that no path is lost, nor made unreadable, in serialization.
+pub mod lossless_path {
+    use std::{
+        ffi::OsString,
+        os::unix::ffi::{OsStrExt, OsStringExt},
+        path::{Path, PathBuf},
+    };
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(
+        path: &Path,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(text) => serializer.serialize_str(text),
+            None => serializer.collect_seq(path.as_os_str().as_bytes()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<PathBuf, D::Error> {
+        let path = match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => PathBuf::from(text),
+            Repr::Bytes(bytes) => PathBuf::from(OsString::from_vec(bytes)),
+        };
+        Ok(path)
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Meta {
+    #[serde(with = "lossless_path")]
     pub path: PathBuf,
     pub typ: FileType,
     pub size: u64,
@@ -61,22 +139,72 @@ impl Meta {
         matches!(self.typ, FileType::Directory)
     }
 
+    /// Socket, named pipe or device.
+    pub fn is_special(&self) -> bool {
+        matches!(
+            self.typ,
+            FileType::Sock
+                | FileType::Fifo
+                | FileType::DevChar
+                | FileType::DevBlock
+        )
+    }
+
+    /// Bytes allocated on disk, as opposed to the apparent size.
+    /// Block counts are in 512-byte units, regardless of blksize.
+    pub fn on_disk(&self) -> u64 {
+        self.blocks.saturating_mul(512)
+    }
+
+    pub fn cmp_by_size(&self, other: &Self) -> Ordering {
+        self.size.cmp(&other.size)
+    }
+
+    pub fn cmp_by_mtime(&self, other: &Self) -> Ordering {
+        self.mtime.cmp(&other.mtime)
+    }
+
+    pub fn cmp_by_atime(&self, other: &Self) -> Ordering {
+        self.atime.cmp(&other.atime)
+    }
+
+    pub fn cmp_by_name(&self, other: &Self) -> Ordering {
+        self.path.file_name().cmp(&other.path.file_name())
+    }
+
+    pub fn cmp_by_path(&self, other: &Self) -> Ordering {
+        self.path.cmp(&other.path)
+    }
+
     pub fn from_path(path: &Path) -> anyhow::Result<Self> {
-        let meta = path
-            .symlink_metadata()
-            .context(format!("Failed to read metadata from path={path:?}"))?;
+        let meta = slow::time("metadata", path, || path.symlink_metadata())
+            .context(format!(
+            "Failed to read metadata from path={path:?}"
+        ))?;
+        let selph = Self::from_fs_metadata(path.to_owned(), meta)?;
+        Ok(selph)
+    }
+
+    /// Like from_path, but follows symlinks, so that the metadata is of the
+    /// final target, while the path remains as given.
+    pub fn from_path_resolved(path: &Path) -> anyhow::Result<Self> {
+        let meta = slow::time("metadata", path, || path.metadata()).context(
+            format!("Failed to read resolved metadata from path={path:?}"),
+        )?;
         let selph = Self::from_fs_metadata(path.to_owned(), meta)?;
         Ok(selph)
     }
 
     pub fn from_dir_entry(entry: &fs::DirEntry) -> anyhow::Result<Self> {
-        let meta = entry.metadata().with_context(|| {
-            format!(
-                "Failed to read metadata from dir entry with path={:?}",
-                entry.path()
-            )
-        })?;
-        let selph = Self::from_fs_metadata(entry.path(), meta)?;
+        let path = entry.path();
+        let meta = slow::time("metadata", &path, || entry.metadata())
+            .with_context(|| {
+                format!(
+                    "Failed to read metadata from dir entry with path={:?}",
+                    path
+                )
+            })?;
+        let selph = Self::from_fs_metadata(path, meta)?;
         Ok(selph)
     }
 
@@ -125,12 +253,137 @@ impl Meta {
     }
 }
 
+/// Dimension by which to order metadata records.
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortKey {
+    Size,
+    Mtime,
+    Atime,
+    Name,
+    Path,
+}
+
+impl SortKey {
+    /// Ascending order by the selected dimension, with ties broken by path,
+    /// so that the order is total and stable across runs.
+    pub fn cmp(&self, a: &Meta, b: &Meta) -> Ordering {
+        let primary = match self {
+            Self::Size => a.cmp_by_size(b),
+            Self::Mtime => a.cmp_by_mtime(b),
+            Self::Atime => a.cmp_by_atime(b),
+            Self::Name => a.cmp_by_name(b),
+            Self::Path => Ordering::Equal,
+        };
+        primary.then_with(|| a.cmp_by_path(b))
+    }
+}
+
+/// Tally of walked entries, by type, for diagnostics.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub regular: u64,
+    pub directory: u64,
+    pub symlink: u64,
+    pub sock: u64,
+    pub fifo: u64,
+    pub dev_char: u64,
+    pub dev_block: u64,
+    pub unknown: u64,
+    pub errors: u64,
+
+    /// Entries of unrecognized type would otherwise be silently absent from
+    /// all results, so we keep track of where they are.
+    pub unknown_paths: Vec<PathBuf>,
+}
+
+impl Stats {
+    pub fn count(&mut self, meta_result: &anyhow::Result<Meta>) {
+        let meta = match meta_result {
+            Ok(meta) => meta,
+            Err(_) => {
+                self.errors += 1;
+                return;
+            }
+        };
+        match meta.typ {
+            FileType::Regular => self.regular += 1,
+            FileType::Directory => self.directory += 1,
+            FileType::Symlink { .. } => self.symlink += 1,
+            FileType::Sock => self.sock += 1,
+            FileType::Fifo => self.fifo += 1,
+            FileType::DevChar => self.dev_char += 1,
+            FileType::DevBlock => self.dev_block += 1,
+            FileType::Unknown => {
+                tracing::warn!(path = ?meta.path, "Unknown file type.");
+                self.unknown += 1;
+                self.unknown_paths.push(meta.path.clone());
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, count) in [
+            ("regular", self.regular),
+            ("directory", self.directory),
+            ("symlink", self.symlink),
+            ("sock", self.sock),
+            ("fifo", self.fifo),
+            ("dev_char", self.dev_char),
+            ("dev_block", self.dev_block),
+            ("unknown", self.unknown),
+            ("errors", self.errors),
+        ] {
+            writeln!(f, "{name}: {count}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Walk options beyond the root.
+#[derive(Clone, Debug, Default)]
+pub struct FindOpts {
+    /// Skip all directories with these names.
+    pub skip_dirs: Vec<OsString>,
+
+    /// Skip all paths starting with these prefixes. Relative ones are
+    /// relative to the walk root.
+    pub skip_prefixes: Vec<PathBuf>,
+
+    /// If any, only yield paths starting with one of these prefixes, and
+    /// only traverse those and their ancestors. Relative ones are relative
+    /// to the walk root. The skip options still apply under them.
+    pub only_prefixes: Vec<PathBuf>,
+
+    /// Skip all paths matching patterns in these files of gitignore syntax.
+    /// Patterns are relative to the walk root.
+    pub ignore_files: Vec<PathBuf>,
+
+    /// Only yield entries on devices other than the root's, i.e. those
+    /// under mount points. The root's filesystem is still traversed, to
+    /// find the mount points, but its own contents are not yielded.
+    pub mounts_only: bool,
+
+    /// Follow symlinks, yielding their targets' metadata under the links'
+    /// paths, and descending into linked directories. Each file, by (dev,
+    /// ino), is yielded at most once, however many ways it is reachable,
+    /// which also keeps cycles from being walked forever.
+    pub follow_symlinks: bool,
+
+    /// The root is an archive file (.tar, .tar.gz, .tgz or .zip), the
+    /// entries of which are to be listed, as if it was a directory, instead
+    /// of walking a tree. Other options do not apply to it.
+    pub archive: bool,
+}
+
 pub fn find(
     root_path: &Path,
 ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
-    let skip_dirs: Vec<OsString> = vec![];
-    let skip_prefixes: Vec<PathBuf> = vec![];
-    Find::new(root_path, skip_dirs, skip_prefixes)
+    find_with(root_path, FindOpts::default())
 }
 
 pub fn find_while_skipping<S: AsRef<OsStr>, P: AsRef<Path>>(
@@ -138,58 +391,167 @@ pub fn find_while_skipping<S: AsRef<OsStr>, P: AsRef<Path>>(
     skip_dirs: Vec<S>,
     skip_prefixes: Vec<P>,
 ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
-    Find::new(root_path, skip_dirs, skip_prefixes)
+    let opts = FindOpts {
+        skip_dirs: skip_dirs
+            .into_iter()
+            .map(|s| s.as_ref().to_owned())
+            .collect(),
+        skip_prefixes: skip_prefixes
+            .into_iter()
+            .map(|p| p.as_ref().to_owned())
+            .collect(),
+        ..FindOpts::default()
+    };
+    find_with(root_path, opts)
 }
 
+/// Root path which stands for a list of paths read from stdin, rather than
+/// for a tree to walk.
+pub const STDIN: &str = "-";
+
+pub fn is_stdin(root_path: &Path) -> bool {
+    root_path == Path::new(STDIN)
+}
+
+/// Entries of the tree under the root or, if the root is "-", of the paths
+/// listed in stdin. Listed paths are taken as they are: neither descended
+/// into, nor subject to the walk options. If the root is an archive, as per
+/// the options, its entries are listed instead.
+pub fn find_with(
+    root_path: &Path,
+    opts: FindOpts,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
+    let metas: Box<dyn Iterator<Item = anyhow::Result<Meta>>> =
+        if is_stdin(root_path) {
+            let paths = read_listed_paths(io::stdin().lock())?;
+            Box::new(paths.into_iter().map(|path| Meta::from_path(&path)))
+        } else if opts.archive {
+            Box::new(archive::entries(root_path)?)
+        } else {
+            Box::new(Find::new(root_path, opts)?)
+        };
+    Ok(metas)
+}
+
+/// Environment variable listing directory names to skip, in addition to
+/// those given as options.
+pub const ENV_SKIP_DIRS: &str = "FX_SKIP_DIRS";
+
+/// Environment variable listing path prefixes to skip, in addition to those
+/// given as options.
+pub const ENV_SKIP_PREFIXES: &str = "FX_SKIP_PREFIXES";
+
+/// Elements of the list in the environment variable, separated by colons,
+/// like PATH, without the empty ones. Taken as bytes, so need not be UTF-8.
+/// Nulls cannot separate them, since they cannot occur in the environment.
+pub fn env_list(name: &str) -> Vec<OsString> {
+    let Some(value) = std::env::var_os(name) else {
+        return Vec::new();
+    };
+    let elements: Vec<OsString> = value
+        .as_bytes()
+        .split(|byte| *byte == b':')
+        .filter(|element| !element.is_empty())
+        .map(|element| OsStr::from_bytes(element).to_owned())
+        .collect();
+    tracing::debug!(name, ?elements, "Read list from environment.");
+    elements
+}
+
+/// Separated by nulls if there are any, or else by linefeeds, so that
+/// output of both "find" and "find -print0" can be used.
+fn read_listed_paths(mut input: impl Read) -> anyhow::Result<Vec<PathBuf>> {
+    let mut buf = Vec::new();
+    input
+        .read_to_end(&mut buf)
+        .context("Failed to read paths from stdin")?;
+    let sep = if buf.contains(&b'\0') { b'\0' } else { b'\n' };
+    let paths = buf
+        .split(|byte| *byte == sep)
+        .filter(|path| !path.is_empty())
+        .map(|path| PathBuf::from(OsStr::from_bytes(path)))
+        .collect();
+    Ok(paths)
+}
+
+/// Symlinks' (src, dst) pairs, with failures handled by the error policy.
 pub fn find_symlinks(
     root_path: &Path,
-) -> anyhow::Result<impl Iterator<Item = (PathBuf, PathBuf)>> {
-    find(root_path).map(|metas| {
-        metas.filter_map(|meta_result| match meta_result {
-            Ok(Meta {
-                path: src,
-                typ: FileType::Symlink { dst },
-                ..
-            }) => Some((src, dst)),
-            Ok(_) => None,
-            Err(error) => {
-                let error: String = error
-                    .chain()
-                    .map(|e| e.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" -> ");
-                tracing::error!(%error, "Metadata fetch failed.");
-                None
-            }
-        })
-    })
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<(PathBuf, PathBuf)>>>
+{
+    let metas = on_error::filter(find(root_path)?, "Metadata fetch failed.");
+    let symlinks = metas.filter_map(|meta_result| match meta_result {
+        Ok(Meta {
+            path: src,
+            typ: FileType::Symlink { dst },
+            ..
+        }) => Some(Ok((src, dst))),
+        Ok(_) => None,
+        Err(error) => Some(Err(error)),
+    });
+    Ok(symlinks)
 }
 
 struct Find {
     frontier: Vec<Meta>,
     skip_dirs: HashSet<OsString>,
     skip_prefixes: HashSet<PathBuf>,
+    only_prefixes: Vec<PathBuf>,
+    ignore: Option<Gitignore>,
+    mounts_only: bool,
+    root_dev: u64,
+
+    /// Files yielded so far, when following symlinks.
+    visited: Option<HashSet<(u64, u64)>>,
+
+    /// Failures to read directories or their entries, yielded one at a
+    /// time, so that the error policy applies to each on its own.
+    failures: VecDeque<anyhow::Error>,
 }
 
 impl Find {
-    fn new<S: AsRef<OsStr>, P: AsRef<Path>>(
-        root_path: &Path,
-        skip_dirs: Vec<S>,
-        skip_prefixes: Vec<P>,
-    ) -> anyhow::Result<Self> {
+    fn new(root_path: &Path, opts: FindOpts) -> anyhow::Result<Self> {
         let meta = Meta::from_path(root_path)?;
-        let skip_dirs: HashSet<OsString> = skip_dirs
-            .into_iter()
-            .map(|s| s.as_ref().to_owned())
-            .collect();
-        let skip_prefixes: HashSet<PathBuf> = skip_prefixes
-            .into_iter()
-            .map(|p| p.as_ref().to_owned())
-            .collect();
-        let mut selph = Self {
-            frontier: Vec::new(),
+        let FindOpts {
             skip_dirs,
             skip_prefixes,
+            only_prefixes,
+            ignore_files,
+            mounts_only,
+            follow_symlinks,
+            archive: _,
+        } = opts;
+        let ignore = if ignore_files.is_empty() {
+            None
+        } else {
+            let mut builder = GitignoreBuilder::new(root_path);
+            for path in &ignore_files {
+                if let Some(error) = builder.add(path) {
+                    return Err(error).context(format!(
+                        "Failed to load ignore file from path={path:?}"
+                    ));
+                }
+            }
+            Some(builder.build()?)
+        };
+        let mut selph = Self {
+            frontier: Vec::new(),
+            skip_dirs: skip_dirs.into_iter().collect(),
+            // Walked paths are under the root as given, which is usually
+            // canonicalized, so relative prefixes would never match them.
+            skip_prefixes: skip_prefixes
+                .iter()
+                .map(|prefix| path::normalize(root_path, prefix))
+                .collect(),
+            only_prefixes: only_prefixes
+                .iter()
+                .map(|prefix| path::normalize(root_path, prefix))
+                .collect(),
+            ignore,
+            mounts_only,
+            root_dev: meta.dev,
+            visited: follow_symlinks.then(HashSet::new),
+            failures: VecDeque::new(),
         };
         if !selph.est_omittendus(&meta) {
             selph.frontier.push(meta);
@@ -197,8 +559,16 @@ impl Find {
         Ok(selph)
     }
 
+    /// Traversed, but not yielded.
+    fn est_celandus(&self, meta: &Meta) -> bool {
+        (self.mounts_only && meta.dev == self.root_dev)
+            || self.est_supra_inclusos(&meta.path)
+    }
+
     fn est_omittendus(&self, meta: &Meta) -> bool {
-        self.est_omittendus_praefixo(&meta.path)
+        self.est_extra_inclusos(&meta.path)
+            || self.est_omittendus_praefixo(&meta.path)
+            || self.est_omittendus_regula(meta)
             || (meta.is_directory()
                 && meta
                     .path
@@ -213,48 +583,122 @@ impl Find {
             .any(|prefix| path.starts_with(prefix))
     }
 
+    /// Neither under any of the only prefixes, nor on the way to one.
+    fn est_extra_inclusos(&self, path: &Path) -> bool {
+        !self.only_prefixes.is_empty()
+            && !self.only_prefixes.iter().any(|prefix| {
+                path.starts_with(prefix) || prefix.starts_with(path)
+            })
+    }
+
+    /// On the way to, but not under, any of the only prefixes.
+    fn est_supra_inclusos(&self, path: &Path) -> bool {
+        !self.only_prefixes.is_empty()
+            && !self
+                .only_prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+    }
+
     fn est_omittendus_nomine(&self, name: &OsStr) -> bool {
         self.skip_dirs.contains(name)
     }
-}
 
-impl Iterator for Find {
-    type Item = anyhow::Result<Meta>;
+    fn est_omittendus_regula(&self, meta: &Meta) -> bool {
+        // Since ignored directories are not descended into, their contents
+        // need not be matched against their parents' rules.
+        self.ignore.as_ref().is_some_and(|ignore| {
+            ignore.matched(&meta.path, meta.is_directory()).is_ignore()
+        })
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let meta = self.frontier.pop()?;
-        if let Meta {
+    /// The entry to yield in place of the given one, if any: its target,
+    /// if it is a symlink to follow, and nothing if already yielded.
+    fn visit(&mut self, meta: Meta) -> Option<Meta> {
+        let Some(visited) = &mut self.visited else {
+            return Some(meta);
+        };
+        let meta = if meta.is_symlink() {
+            // Dangling link has no target to follow, so is yielded itself.
+            Meta::from_path_resolved(&meta.path).unwrap_or(meta)
+        } else {
+            meta
+        };
+        visited.insert((meta.dev, meta.ino)).then_some(meta)
+    }
+
+    /// Entries of the directory, if it is one, added to the frontier. Those
+    /// which fail to be read are added to the failures, each on its own,
+    /// and the rest are kept.
+    fn expand(&mut self, meta: &Meta) {
+        let Meta {
             path,
             typ: FileType::Directory,
             ..
-        } = &meta
-        {
-            match path
-                .read_dir()
-                .context(format!("Failed to read dir at path={:?}", path))
-            {
+        } = meta
+        else {
+            return;
+        };
+        let entries: Vec<io::Result<fs::DirEntry>> =
+            match slow::time("read_dir", path, || {
+                path.read_dir().map(Iterator::collect)
+            }) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    self.failures.push_back(anyhow::Error::from(e).context(
+                        format!("Failed to read dir at path={:?}", path),
+                    ));
+                    return;
+                }
+            };
+        let mut read: Vec<fs::DirEntry> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            match entry {
+                Ok(entry) => read.push(entry),
                 Err(e) => {
-                    return Some(Err(e));
+                    self.failures.push_back(anyhow::Error::from(e).context(
+                        format!(
+                            "Failed to read entry of dir at path={:?}",
+                            path
+                        ),
+                    ));
                 }
-                Ok(read_dir) => {
-                    for entry_result in read_dir {
-                        match entry_result {
-                            Err(e) => return Some(Err(e.into())),
-                            Ok(entry) => match Meta::from_dir_entry(&entry) {
-                                Ok(meta) => {
-                                    if !self.est_omittendus(&meta) {
-                                        self.frontier.push(meta);
-                                    }
-                                }
-                                Err(e) => {
-                                    return Some(Err(e));
-                                }
-                            },
-                        }
+            }
+        }
+        // Each one is a stat (and maybe a readlink), which add up in
+        // wide directories, so we fetch them in parallel.
+        let metas: Vec<anyhow::Result<Meta>> =
+            read.par_iter().map(Meta::from_dir_entry).collect();
+        for meta_result in metas {
+            match meta_result {
+                Ok(meta) => {
+                    if !self.est_omittendus(&meta) {
+                        self.frontier.push(meta);
                     }
                 }
+                Err(e) => self.failures.push_back(e),
+            }
+        }
+    }
+}
+
+impl Iterator for Find {
+    type Item = anyhow::Result<Meta>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(e) = self.failures.pop_front() {
+                return Some(Err(e));
+            }
+            let meta = self.frontier.pop()?;
+            let Some(meta) = self.visit(meta) else {
+                continue;
+            };
+            self.expand(&meta);
+            if self.est_celandus(&meta) {
+                continue;
             }
+            return Some(Ok(meta));
         }
-        Some(Ok(meta))
     }
 }
@@ -0,0 +1,98 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::Context;
+
+/// What happened during a run, for a supervising process to follow it by.
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// A candidate file, found during the walk.
+    FileFound {
+        #[serde(flatten)]
+        path: PathField<'a>,
+        size: u64,
+    },
+
+    /// Groups left after a pass of refinement, numbered from 0, anew for
+    /// each batch, if files were spilled to disk, by --max-memory.
+    PassFinished {
+        pass: usize,
+        groups: usize,
+    },
+
+    /// A group, as it is about to be reported.
+    GroupFinalized {
+        size: u64,
+        members: Vec<PathField<'a>>,
+    },
+
+    Done {
+        stats: Stats,
+    },
+}
+
+/// A path as the JSON reporter writes it: as a string, converted lossily,
+/// and, if it is not valid UTF-8, also as an array of its exact bytes.
+#[derive(Debug, serde::Serialize)]
+pub struct PathField<'a> {
+    path: Cow<'a, str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_bytes: Option<&'a [u8]>,
+}
+
+impl<'a> From<&'a Path> for PathField<'a> {
+    fn from(path: &'a Path) -> Self {
+        Self {
+            path: path.to_string_lossy(),
+            path_bytes: path
+                .to_str()
+                .is_none()
+                .then(|| path.as_os_str().as_bytes()),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Stats {
+    pub scanned: u64,
+    pub hashed: u64,
+    pub groups: usize,
+    pub files: usize,
+}
+
+/// Stream of events as NDJSON: one object per line, tagged by its "event"
+/// field. Separate from the report, so that both can be consumed at once.
+/// Each event is flushed as soon as it is written, so that it is seen live.
+#[derive(Debug)]
+pub struct Events {
+    out: Mutex<BufWriter<File>>,
+}
+
+impl Events {
+    /// Into the file at the path, which may be that of an already open
+    /// descriptor, e.g. "/dev/fd/3".
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .context(format!("Failed to create events path={path:?}"))?;
+        let selph = Self {
+            out: Mutex::new(BufWriter::new(file)),
+        };
+        Ok(selph)
+    }
+
+    pub fn emit(&self, event: &Event) -> anyhow::Result<()> {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        serde_json::to_writer(&mut *out, event)?;
+        writeln!(out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
@@ -1,13 +1,31 @@
-use std::{fs, hash::Hasher, io::Read, path::Path};
+use std::{
+    fs,
+    hash::Hasher,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::anyhow;
+
+use crate::slow;
 
 pub fn xxh(path: &Path, chunk_size: usize) -> anyhow::Result<u64> {
-    use twox_hash::XxHash3_64;
+    xxh_resume(path, chunk_size, twox_hash::XxHash3_64::new(), 0)
+}
 
+/// Finish the hash of a file, of which the first `offset` bytes have
+/// already been written to the given hasher, by reading only the rest.
+pub fn xxh_resume(
+    path: &Path,
+    chunk_size: usize,
+    mut hash: twox_hash::XxHash3_64,
+    offset: u64,
+) -> anyhow::Result<u64> {
     let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
     let mut buff = vec![0u8; chunk_size];
-    let mut hash = XxHash3_64::new();
     loop {
-        let n = file.read(&mut buff)?;
+        let n = slow::time("read", path, || file.read(&mut buff))?;
         if n == 0 {
             break;
         }
@@ -22,7 +40,7 @@ pub fn blake3(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
     let mut buff = vec![0u8; chunk_size];
     let mut hash = blake3::Hasher::new();
     loop {
-        let n = file.read(&mut buff)?;
+        let n = slow::time("read", path, || file.read(&mut buff))?;
         if n == 0 {
             break;
         }
@@ -33,6 +51,24 @@ pub fn blake3(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
     Ok(hash)
 }
 
+pub fn sha2_256(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
+    use sha2::Digest;
+
+    let mut file = fs::File::open(path)?;
+    let mut buff = vec![0u8; chunk_size];
+    let mut hash = sha2::Sha256::new();
+    loop {
+        let n = slow::time("read", path, || file.read(&mut buff))?;
+        if n == 0 {
+            break;
+        }
+        let chunk = &buff[..n];
+        hash.update(chunk);
+    }
+    let hash = hash.finalize().to_vec();
+    Ok(hash)
+}
+
 pub fn sha2_512(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
     use sha2::Digest;
 
@@ -40,7 +76,7 @@ pub fn sha2_512(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
     let mut buff = vec![0u8; chunk_size];
     let mut hash = sha2::Sha512::new();
     loop {
-        let n = file.read(&mut buff)?;
+        let n = slow::time("read", path, || file.read(&mut buff))?;
         if n == 0 {
             break;
         }
@@ -50,3 +86,20 @@ pub fn sha2_512(path: &Path, chunk_size: usize) -> anyhow::Result<Vec<u8>> {
     let hash = hash.finalize().to_vec();
     Ok(hash)
 }
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn from_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)?;
+            if pair.len() != 2 {
+                return Err(anyhow!("Odd number of hex digits: {hex:?}"));
+            }
+            Ok(u8::from_str_radix(pair, 16)?)
+        })
+        .collect()
+}
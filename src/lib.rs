@@ -1,5 +1,20 @@
+pub mod archive;
+pub mod checkpoint;
 pub mod cmd;
+pub mod config;
 pub mod data;
+pub mod events;
 pub mod hash;
+pub mod on_error;
+pub mod output;
 pub mod path;
+pub mod profile;
+pub mod progress;
+pub mod report;
+pub mod root;
+pub mod slow;
+pub mod spill;
+pub mod time;
 pub mod tracing;
+pub mod users;
+pub mod xattr_cache;
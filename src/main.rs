@@ -1,51 +1,201 @@
+use std::{ffi::OsString, sync::Arc};
+
 use clap::Parser;
 use tracing::level_filters::LevelFilter;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, serde::Serialize)]
 #[command(version, about)]
 struct Cli {
     #[clap(short, long = "log", default_value_t = LevelFilter::ERROR)]
+    #[serde(serialize_with = "fx::config::display")]
     log_level: LevelFilter,
 
+    /// Print wall time spent in each stage (e.g. walk, each pass of dups)
+    /// to stderr at the end.
+    #[clap(long)]
+    profile: bool,
+
+    /// What to do about entries which fail to be walked or read: skip them
+    /// silently, skip them with an error logged, or abort the run.
+    #[clap(long, value_enum, default_value_t, global = true)]
+    on_error: fx::on_error::OnError,
+
+    /// Strip this from the start of every reported path which starts with
+    /// it, e.g. "/very/long/mount/path/", leaving other paths untouched.
+    #[clap(long, value_name = "PREFIX", global = true)]
+    #[serde(serialize_with = "fx::config::os_string")]
+    trim_prefix: Option<OsString>,
+
+    /// Warn, with the path, about each read of a directory, metadata or
+    /// file contents which takes longer than this many milliseconds, to
+    /// find what on slow storage (e.g. network mounts) drags the run down.
+    #[clap(long, value_name = "MS", global = true)]
+    warn_slow_paths: Option<u64>,
+
+    /// Print the effective options, as JSON, and exit without running.
+    #[clap(long, global = true)]
+    #[serde(skip)]
+    dump_config: bool,
+
     #[clap(subcommand)]
     cmd: Cmd,
 }
 
-#[derive(clap::Subcommand, Debug)]
+#[derive(clap::Subcommand, Debug, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
 enum Cmd {
     /// Find top-N users of storage space. Aggregated directories by default,
     /// files optionally. Ignores symlinks.
+    ///
+    /// Given "-" as the root, there is no tree to aggregate directories
+    /// over, so listed paths require --files or --summarize.
     Top(fx::cmd::top::Cmd),
 
     /// Find dangling symlinks (i.e. those with non-existing targets).
     Dang(fx::cmd::dang::Cmd),
 
     /// Find duplicate files.
+    ///
+    /// Given "-" as the root, the options which filter the walk do not
+    /// apply to the listed paths.
     Dups(fx::cmd::dups::Cmd),
 
+    /// Find files with identical contents across two roots, e.g. those in
+    /// one which are already in the other.
+    Common(fx::cmd::common::Cmd),
+
     /// Find symlink cycles.
     Loops(fx::cmd::loops::Cmd),
-    // TODO Snap(fx::cmd::snap::Cmd), // Collect all metadata and store it.
-    // TODO Diff(fx::cmd::diff::Cmd), // Compare changes in metadata in time.
-    // TODO Empties.
-    // TODO Recently accessed.
-    // TODO Recently modified.
-    // TODO Recently created.
-    // TODO Group by: user, group, user & group; count; optionally print.
-    // TODO More than one link count.
+
+    /// Find empty files and directories.
+    ///
+    /// Given "-" as the root, a listed directory is empty unless any of its
+    /// entries are listed too.
+    Empties(fx::cmd::empties::Cmd),
+
+    /// Find groups of hard links, i.e. of paths of the same files.
+    Hardlinks(fx::cmd::hardlinks::Cmd),
+
+    /// Find top-N owners of storage space: users, groups, or both.
+    Owned(fx::cmd::owned::Cmd),
+
+    /// Find paths which would collide on a case-insensitive filesystem,
+    /// e.g. "A/b" and "a/B". Worth checking before moving a tree to macOS
+    /// or Windows.
+    ///
+    /// Given "-" as the root, listed paths are compared whole, since there
+    /// is no root for them to be relative to.
+    CasefoldCollisions(fx::cmd::casefold_collisions::Cmd),
+
+    /// Find entries with risky permissions or given ownership, e.g.
+    /// world-writable or setuid files, for security reviews.
+    AuditPerms(fx::cmd::audit_perms::Cmd),
+
+    /// Find special files: sockets, named pipes and devices, the latter
+    /// with their major and minor numbers.
+    Specials(fx::cmd::specials::Cmd),
+
+    /// Find directories whose modification time is far from their newest
+    /// entry's, a hint of timestamp tampering, for security reviews.
+    ///
+    /// Given "-" as the root, only listed entries of listed directories
+    /// count.
+    MtimeAnomalies(fx::cmd::mtime_anomalies::Cmd),
+
+    /// Verify files against a checksum manifest, like "sha256sum -c".
+    Verify(fx::cmd::verify::Cmd),
+
+    /// Find files modified (or accessed, or changed) after a given time,
+    /// within a given duration, or since boot, most recent first, e.g. to
+    /// see what changed since an incident. Without any of those, all files
+    /// are reported, most recent first, as far as --lim allows.
+    Recent(fx::cmd::recent::Cmd),
+
+    /// Find files not accessed (or modified, or changed) in a long time,
+    /// largest first, e.g. as candidates for archival. Access times are
+    /// unreliable on filesystems mounted with "noatime".
+    ///
+    /// Given "-" as the root, listed paths are not checked for a noatime
+    /// mount.
+    #[command(visible_alias = "old")]
+    Stale(fx::cmd::stale::Cmd),
+
+    /// Export metadata of all entries as a Parquet file, for analysis with
+    /// external tools, like DuckDB or Spark.
+    #[cfg(feature = "parquet")]
+    Export(fx::cmd::export::Cmd),
+
+    /// Histogram of file counts and sizes over periods of time, showing
+    /// when the data accumulated.
+    Timeline(fx::cmd::timeline::Cmd),
+
+    /// Interactively browse storage space usage of directories.
+    Browse(fx::cmd::browse::Cmd),
+
+    /// Collect metadata of all entries and store it, as JSON lines,
+    /// optionally compressed, to be compared with later.
+    #[cfg(feature = "snap")]
+    Snap(fx::cmd::snap::Cmd),
+
+    /// Compare two snapshots, taken by snap: entries added, deleted, moved
+    /// and modified, with the fields which differ.
+    #[cfg(feature = "snap")]
+    Diff(fx::cmd::diff::Cmd),
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
-    fx::tracing::init(cli.log_level)?;
-    let span = tracing::debug_span!(env!("CARGO_PKG_NAME"));
-    let _span_guard = span.enter();
-    tracing::debug!(?cli, "Starting.");
-    match cli.cmd {
+    if cli.dump_config {
+        return fx::config::dump(&cli);
+    }
+    let profile = cli
+        .profile
+        .then(|| Arc::new(fx::profile::Profile::default()));
+    fx::tracing::init(cli.log_level, profile.clone())?;
+    fx::on_error::set(cli.on_error);
+    if let Some(prefix) = cli.trim_prefix.clone() {
+        fx::report::set_trim_prefix(prefix);
+    }
+    if let Some(ms) = cli.warn_slow_paths {
+        fx::slow::set(std::time::Duration::from_millis(ms));
+    }
+    let result = {
+        let span = tracing::debug_span!(env!("CARGO_PKG_NAME"));
+        let _span_guard = span.enter();
+        tracing::debug!(?cli, "Starting.");
+        run(cli.cmd)
+    };
+    if let Some(profile) = profile {
+        eprint!("{}", profile);
+    }
+    result
+}
+
+fn run(cmd: Cmd) -> anyhow::Result<()> {
+    match cmd {
         Cmd::Top(cmd) => cmd.run()?,
         Cmd::Dang(cmd) => cmd.run()?,
         Cmd::Dups(cmd) => cmd.run()?,
+        Cmd::Common(cmd) => cmd.run()?,
         Cmd::Loops(cmd) => cmd.run()?,
+        Cmd::Empties(cmd) => cmd.run()?,
+        Cmd::Hardlinks(cmd) => cmd.run()?,
+        Cmd::Owned(cmd) => cmd.run()?,
+        Cmd::CasefoldCollisions(cmd) => cmd.run()?,
+        Cmd::AuditPerms(cmd) => cmd.run()?,
+        Cmd::Specials(cmd) => cmd.run()?,
+        Cmd::Recent(cmd) => cmd.run()?,
+        Cmd::MtimeAnomalies(cmd) => cmd.run()?,
+        Cmd::Stale(cmd) => cmd.run()?,
+        Cmd::Timeline(cmd) => cmd.run()?,
+        Cmd::Browse(cmd) => cmd.run()?,
+        #[cfg(feature = "parquet")]
+        Cmd::Export(cmd) => cmd.run()?,
+        #[cfg(feature = "snap")]
+        Cmd::Snap(cmd) => cmd.run()?,
+        #[cfg(feature = "snap")]
+        Cmd::Diff(cmd) => cmd.run()?,
+        Cmd::Verify(cmd) => cmd.run()?,
     }
     Ok(())
 }
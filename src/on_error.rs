@@ -0,0 +1,73 @@
+use std::sync::OnceLock;
+
+/// What to do about a failure to process one entry (e.g. to read its
+/// metadata or contents), which the rest of the run could do without.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    /// Skip the entry, only logging the error at debug level.
+    Skip,
+
+    /// Skip the entry, logging the error at error level, which is shown by
+    /// default.
+    #[default]
+    Warn,
+
+    /// Stop the run and exit with the error.
+    Abort,
+}
+
+static POLICY: OnceLock<OnError> = OnceLock::new();
+
+/// Set the policy for the whole process. Only the first call has effect.
+pub fn set(policy: OnError) {
+    if POLICY.set(policy).is_err() {
+        tracing::warn!(?policy, "Error policy already set. Ignoring.");
+    }
+}
+
+pub fn policy() -> OnError {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+/// Handle the result of processing one entry by the policy: the value if
+/// any, None if the entry is to be skipped, or the error, with the given
+/// context, if the run is to be aborted.
+pub fn handle<T>(
+    result: anyhow::Result<T>,
+    context: &'static str,
+) -> anyhow::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(error) => match policy() {
+            OnError::Skip => {
+                tracing::debug!(?error, "{} Skipping.", context);
+                Ok(None)
+            }
+            OnError::Warn => {
+                tracing::error!(?error, "{} Skipping.", context);
+                Ok(None)
+            }
+            OnError::Abort => Err(error.context(context)),
+        },
+    }
+}
+
+/// Results of processing entries, of which the failed are handled by the
+/// policy: skipped, or passed on, to abort with, as by collecting into a
+/// Result.
+pub fn filter<T>(
+    results: impl Iterator<Item = anyhow::Result<T>>,
+    context: &'static str,
+) -> impl Iterator<Item = anyhow::Result<T>> {
+    results.filter_map(move |result| handle(result, context).transpose())
+}
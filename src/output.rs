@@ -0,0 +1,112 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::Path,
+};
+
+/// How paths are encoded when written to output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encoding {
+    /// Replace invalid UTF-8 with U+FFFD (or escape, when quoting).
+    #[default]
+    Lossy,
+
+    /// Exact path bytes, as given by the OS. Since these can include
+    /// anything but a null, only null-separated output is unambiguous.
+    Raw,
+}
+
+/// How paths are quoted when written to output.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuoteStyle {
+    /// Rust's debug formatting. E.g.: "it's \"here\"".
+    #[default]
+    Debug,
+
+    /// Single-quoted for POSIX shells, so safe to eval.
+    /// E.g.: 'it'\''s "here"'.
+    Shell,
+
+    /// Double-quoted with C escapes, like "ls --quoting-style=c".
+    /// E.g.: "it's \"here\"".
+    C,
+}
+
+pub fn write_path<W: Write>(
+    out: &mut W,
+    path: &Path,
+    quote: Option<QuoteStyle>,
+    encoding: Encoding,
+) -> io::Result<()> {
+    let bytes: Cow<[u8]> = match encoding {
+        Encoding::Raw => Cow::Borrowed(path.as_os_str().as_bytes()),
+        Encoding::Lossy => match path.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        },
+    };
+    match quote {
+        None => out.write_all(&bytes),
+        Some(QuoteStyle::Debug) => write!(out, "{path:?}"),
+        Some(QuoteStyle::Shell) => write_shell_quoted(out, &bytes),
+        Some(QuoteStyle::C) => write_c_quoted(out, &bytes),
+    }
+}
+
+/// Nothing is special within single quotes, except the single quote itself,
+/// which can only be written by closing the quotes, escaping it and
+/// reopening the quotes.
+fn write_shell_quoted<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(b"'")?;
+    for (i, part) in bytes.split(|b| *b == b'\'').enumerate() {
+        if i > 0 {
+            out.write_all(b"'\\''")?;
+        }
+        out.write_all(part)?;
+    }
+    out.write_all(b"'")
+}
+
+/// Printable UTF-8 is kept as is, everything else is escaped, as octal if
+/// there is no named escape.
+fn write_c_quoted<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => out.write_all(b"\\\"")?,
+                '\\' => out.write_all(b"\\\\")?,
+                '\x07' => out.write_all(b"\\a")?,
+                '\x08' => out.write_all(b"\\b")?,
+                '\x0c' => out.write_all(b"\\f")?,
+                '\n' => out.write_all(b"\\n")?,
+                '\r' => out.write_all(b"\\r")?,
+                '\t' => out.write_all(b"\\t")?,
+                '\x0b' => out.write_all(b"\\v")?,
+                c if c.is_control() => {
+                    let mut buf = [0; 4];
+                    for b in c.encode_utf8(&mut buf).bytes() {
+                        write!(out, "\\{b:03o}")?;
+                    }
+                }
+                c => write!(out, "{c}")?,
+            }
+        }
+        for b in chunk.invalid() {
+            write!(out, "\\{b:03o}")?;
+        }
+    }
+    out.write_all(b"\"")
+}
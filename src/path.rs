@@ -1,4 +1,94 @@
-use std::path::{Component, Path, PathBuf};
+use std::{
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Unicode normalization form to bring names to before comparing them, so
+/// that the same name, as written by different systems (e.g. NFD by macOS,
+/// NFC by most others), compares equal.
+#[derive(
+    clap::ValueEnum,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnicodeForm {
+    /// Compare names as they are.
+    #[default]
+    None,
+
+    /// Canonical composition.
+    Nfc,
+
+    /// Canonical decomposition.
+    Nfd,
+}
+
+impl UnicodeForm {
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            Self::None => name.to_string(),
+            Self::Nfc => name.nfc().collect(),
+            Self::Nfd => name.nfd().collect(),
+        }
+    }
+}
+
+/// Like .canonicalize(), but a symlink in the final component is kept as
+/// is, rather than resolved, so that the symlink itself can be examined.
+pub fn canonicalize_keeping_symlink(path: &Path) -> io::Result<PathBuf> {
+    if !path.symlink_metadata()?.is_symlink() {
+        return path.canonicalize();
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            Ok(parent.canonicalize()?.join(name))
+        }
+        // A symlink path must have a name, but "." or ".." would not.
+        _ => path.canonicalize(),
+    }
+}
+
+/// Canonicalize with the given function or, if that fails (e.g. due to a
+/// symlink cycle or a removed working directory), fall back to normalize,
+/// with a warning, since the path may still be usable.
+pub fn canonicalize_or_normalize(
+    path: &Path,
+    canonicalize: impl FnOnce(&Path) -> io::Result<PathBuf>,
+) -> PathBuf {
+    match canonicalize(path) {
+        Ok(canonicalized) => canonicalized,
+        Err(error) => {
+            let normalized = match std::env::current_dir() {
+                Ok(working_dir) => normalize(&working_dir, path),
+                // Normalizing against a root-less base would yield a path
+                // relative to nothing in particular.
+                Err(_) if path.is_absolute() => {
+                    normalize(Path::new("/"), path)
+                }
+                Err(_) => path.to_owned(),
+            };
+            eprintln!(
+                "Warning: failed to canonicalize path {:?}: {}. \
+                Proceeding with {:?}.",
+                path, error, normalized
+            );
+            normalized
+        }
+    }
+}
 
 /// Alternative to .canonicalize() which doesn't access the filesystem.
 pub fn normalize(working_dir: &Path, path: &Path) -> PathBuf {
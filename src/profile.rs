@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tracing::{Subscriber, span};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+/// Wall time spent in each named span, e.g. in each pass of dups, summed
+/// over all spans of the same name.
+#[derive(Debug, Default)]
+pub struct Profile {
+    stages: Mutex<HashMap<&'static str, Stage>>,
+}
+
+#[derive(Debug)]
+struct Stage {
+    first_enter: Instant,
+    elapsed: Duration,
+    spans: u64,
+}
+
+impl Profile {
+    /// Tracing layer which records into this profile.
+    pub fn layer(self: &Arc<Self>) -> Recorder {
+        Recorder {
+            profile: Arc::clone(self),
+        }
+    }
+
+    fn record(
+        &self,
+        name: &'static str,
+        first_enter: Instant,
+        last: Instant,
+    ) {
+        let elapsed = last.saturating_duration_since(first_enter);
+        let mut stages = match self.stages.lock() {
+            Ok(stages) => stages,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let stage = stages.entry(name).or_insert(Stage {
+            first_enter,
+            elapsed: Duration::ZERO,
+            spans: 0,
+        });
+        stage.first_enter = stage.first_enter.min(first_enter);
+        stage.elapsed += elapsed;
+        stage.spans += 1;
+    }
+}
+
+/// Stages in the order they started. Nested stages are included in their
+/// parents' times, so the times do not add up to the total.
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let stages = match self.stages.lock() {
+            Ok(stages) => stages,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut stages: Vec<(&&str, &Stage)> = stages.iter().collect();
+        stages.sort_by_key(|(_, stage)| stage.first_enter);
+        for (name, stage) in stages {
+            write!(f, "{}: {:.3}s", name, stage.elapsed.as_secs_f64())?;
+            if stage.spans > 1 {
+                write!(f, " ({} spans)", stage.spans)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Recorder {
+    profile: Arc<Profile>,
+}
+
+/// Bounds of a span's activity. A span can be entered from many threads at
+/// once (as in dups' parallel passes), so its time is taken from the first
+/// entry to the last exit, rather than summed per entry.
+struct Timing {
+    first_enter: Option<Instant>,
+    last_exit: Option<Instant>,
+}
+
+impl<S> Layer<S> for Recorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        _attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Timing {
+                first_enter: None,
+                last_exit: None,
+            });
+        }
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+            timing.first_enter.get_or_insert_with(Instant::now);
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(timing) = span.extensions_mut().get_mut::<Timing>() {
+            timing.last_exit = Some(Instant::now());
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        // Never entered spans did no work worth reporting.
+        if let Some(Timing {
+            first_enter: Some(first_enter),
+            last_exit: Some(last_exit),
+        }) = extensions.get::<Timing>()
+        {
+            self.profile.record(span.name(), *first_enter, *last_exit);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Counters of work done, updated by workers and read by a status printer.
+#[derive(Debug, Default)]
+pub struct Progress {
+    scanned: AtomicU64,
+    hashed: AtomicU64,
+}
+
+impl Progress {
+    /// Count entries found during the walk.
+    pub fn add_scanned(&self, entries: u64) {
+        self.scanned.fetch_add(entries, Ordering::Relaxed);
+    }
+
+    /// Count bytes read for hashing.
+    pub fn add_hashed(&self, bytes: u64) {
+        self.hashed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn scanned(&self) -> u64 {
+        self.scanned.load(Ordering::Relaxed)
+    }
+
+    pub fn hashed(&self) -> u64 {
+        self.hashed.load(Ordering::Relaxed)
+    }
+
+    /// Print a status line to stderr at each interval, until the returned
+    /// guard is dropped. Plain lines, rather than a redrawn bar, so that it
+    /// reads well in logs and non-interactive terminals.
+    pub fn print_every(self: &Arc<Self>, interval: Duration) -> Printer {
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let selph = Arc::clone(self);
+        let thread = thread::spawn(move || {
+            let start = Instant::now();
+            // Sender is never used, only dropped, to disconnect.
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stop_rx.recv_timeout(interval)
+            {
+                eprintln!("{}", selph.status(start.elapsed()));
+            }
+        });
+        Printer {
+            stop_tx: Some(stop_tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn status(&self, elapsed: Duration) -> String {
+        format!(
+            "[{}s] scanned {} entries, hashed {}",
+            elapsed.as_secs(),
+            self.scanned(),
+            bytesize::ByteSize(self.hashed()),
+        )
+    }
+}
+
+/// Stops the status printer when dropped.
+pub struct Printer {
+    stop_tx: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Printer {
+    fn drop(&mut self) {
+        drop(self.stop_tx.take());
+        if let Some(Err(error)) = self.thread.take().map(|t| t.join()) {
+            tracing::error!(?error, "Progress printer panicked.");
+        }
+    }
+}
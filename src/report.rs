@@ -0,0 +1,604 @@
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    io::{self, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+
+use crate::{on_error, output};
+
+#[derive(
+    clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Aligned columns, with a header.
+    Table,
+
+    /// Comma-separated values, with a header.
+    Csv,
+
+    /// A single JSON array of records (or of arrays of records, for groups).
+    /// With rows kept apart, like dups anomalies, an object instead, of the
+    /// array, as "records", and of each of them, by name.
+    Json,
+
+    /// Newline-delimited JSON. One record (or array of records) per line.
+    Ndjson,
+
+    /// Plain, delimited lines of fields, without a header.
+    Raw,
+
+    /// Fields separated by a null, records ended by an extra null and
+    /// groups by yet another, e.g. "size\0path\0\0", with paths as exact
+    /// bytes. Unambiguous for any paths, since they cannot contain nulls.
+    Nul,
+}
+
+#[derive(Clone, Debug)]
+pub enum Value<'a> {
+    Size(u64),
+    Int(i64),
+    Text(Cow<'a, str>),
+    Path(&'a Path),
+}
+
+/// Named value of one output record.
+pub type Field<'a> = (&'static str, Value<'a>);
+
+/// Consumer of command results, which it renders in some output format.
+pub trait Reporter {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()>;
+
+    /// Rows which belong together, like duplicates of each other.
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()>;
+
+    /// Rows kept apart from the rest, under the name, like files left out
+    /// of the results and why. Only JSON has room for them, so the other
+    /// formats drop them.
+    fn aside(
+        &mut self,
+        _name: &'static str,
+        _rows: &[Vec<Field>],
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+/// Presentation knobs. Not all are meaningful in every format.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Sizes in human-readable units. Table, raw and nul only.
+    pub human: bool,
+
+    /// Raw only. Quote paths in this style, if any.
+    pub quote: Option<output::QuoteStyle>,
+
+    /// Raw only.
+    pub output_encoding: output::Encoding,
+
+    /// Raw only. Written between the fields of a record.
+    pub field_sep: String,
+
+    /// Raw only. Written after each record.
+    pub record_sep: String,
+
+    /// Raw only. Written after each group.
+    pub group_sep: String,
+
+    /// Table and CSV only. Begin with a header of field names.
+    pub header: bool,
+
+    /// Resolve reported paths to real ones, i.e. follow all symlinks.
+    /// Rows whose paths fail to resolve are skipped.
+    pub real_paths: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            human: false,
+            quote: None,
+            output_encoding: output::Encoding::default(),
+            field_sep: "\t".to_string(),
+            record_sep: "\n".to_string(),
+            group_sep: "\n".to_string(),
+            header: true,
+            real_paths: false,
+        }
+    }
+}
+
+static TRIM_PREFIX: OnceLock<OsString> = OnceLock::new();
+
+/// Set, for the whole process, a prefix to strip from reported paths. Only
+/// the first call has effect.
+pub fn set_trim_prefix(prefix: OsString) {
+    if TRIM_PREFIX.set(prefix).is_err() {
+        tracing::warn!("Trim prefix already set. Ignoring.");
+    }
+}
+
+/// The path without the trim prefix, if one is set and the path starts
+/// with it, as a string rather than by components, so e.g. "/a/b" trimmed
+/// of "/a/" is "b". A path which is nothing but the prefix is left whole,
+/// rather than reported as empty.
+pub fn trim_prefix(path: &Path) -> &Path {
+    let Some(prefix) = TRIM_PREFIX.get() else {
+        return path;
+    };
+    path.as_os_str()
+        .as_bytes()
+        .strip_prefix(prefix.as_bytes())
+        .filter(|rest| !rest.is_empty())
+        .map_or(path, |rest| Path::new(OsStr::from_bytes(rest)))
+}
+
+pub fn new(format: OutputFormat, options: Options) -> Box<dyn Reporter> {
+    let out = io::stdout().lock();
+    let real_paths = options.real_paths;
+    let mut reporter: Box<dyn Reporter> = match format {
+        OutputFormat::Table => Box::new(Table::new(out, options)),
+        OutputFormat::Csv => Box::new(Csv::new(out, options.header)),
+        OutputFormat::Json => Box::new(Json::new(out)),
+        OutputFormat::Ndjson => Box::new(Ndjson::new(out)),
+        OutputFormat::Raw => Box::new(Raw::new(out, options)),
+        OutputFormat::Nul => Box::new(Raw::new(
+            out,
+            Options {
+                quote: None,
+                output_encoding: output::Encoding::Raw,
+                field_sep: "\0".to_string(),
+                record_sep: "\0\0".to_string(),
+                group_sep: "\0".to_string(),
+                ..options
+            },
+        )),
+    };
+    // Innermost, so that real paths are resolved before being trimmed.
+    if TRIM_PREFIX.get().is_some() {
+        reporter = Box::new(TrimPrefix { inner: reporter });
+    }
+    if real_paths {
+        reporter = Box::new(RealPaths { inner: reporter });
+    }
+    reporter
+}
+
+pub fn fmt_size(size: u64, human: bool) -> String {
+    if human {
+        bytesize::ByteSize(size).to_string()
+    } else {
+        size.to_string()
+    }
+}
+
+fn to_string(value: &Value, human: bool) -> String {
+    match value {
+        Value::Size(size) => fmt_size(*size, human),
+        Value::Int(int) => int.to_string(),
+        Value::Text(text) => text.to_string(),
+        Value::Path(path) => path.to_string_lossy().to_string(),
+    }
+}
+
+/// Paths which are not valid UTF-8 are converted lossily, and annotated
+/// with their exact bytes, in an extra "<name>_bytes" field.
+pub(crate) fn to_json(row: &[Field]) -> serde_json::Value {
+    let mut object = serde_json::Map::new();
+    for (name, value) in row {
+        let json = match value {
+            Value::Size(size) => serde_json::Value::from(*size),
+            Value::Int(int) => serde_json::Value::from(*int),
+            Value::Text(text) => serde_json::Value::from(text.as_ref()),
+            Value::Path(path) => {
+                serde_json::Value::from(path.to_string_lossy().as_ref())
+            }
+        };
+        object.insert(name.to_string(), json);
+        match value {
+            Value::Path(path) if path.to_str().is_none() => {
+                let bytes = path.as_os_str().as_bytes();
+                object.insert(
+                    format!("{name}_bytes"),
+                    serde_json::Value::from(bytes),
+                );
+            }
+            _ => {}
+        }
+    }
+    serde_json::Value::Object(object)
+}
+
+struct Table<W: Write> {
+    out: W,
+    table: comfy_table::Table,
+    options: Options,
+    has_header: bool,
+    has_groups: bool,
+}
+
+impl<W: Write> Table<W> {
+    fn new(out: W, options: Options) -> Self {
+        let mut table = comfy_table::Table::new();
+        table.load_preset(comfy_table::presets::NOTHING); // No borders or dividers.
+        Self {
+            out,
+            table,
+            options,
+            has_header: false,
+            has_groups: false,
+        }
+    }
+}
+
+impl<W: Write> Reporter for Table<W> {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        if !self.has_header && self.options.header {
+            self.table
+                .set_header(row.iter().map(|(name, _)| name.to_uppercase()));
+            self.has_header = true;
+        }
+        self.table.add_row(
+            row.iter()
+                .map(|(_, value)| to_string(value, self.options.human)),
+        );
+        Ok(())
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        // Blank line between groups.
+        if self.has_groups {
+            self.table.add_row(vec![""]);
+        }
+        self.has_groups = true;
+        for row in rows {
+            self.row(row)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        writeln!(self.out, "{}", self.table)?;
+        Ok(())
+    }
+}
+
+struct Csv<W: Write> {
+    out: W,
+    has_header: bool,
+    groups: usize,
+}
+
+impl<W: Write> Csv<W> {
+    fn new(out: W, header: bool) -> Self {
+        Self {
+            out,
+            // Pretend it was already written, if one is not wanted.
+            has_header: !header,
+            groups: 0,
+        }
+    }
+
+    fn write_record<'a>(
+        &mut self,
+        fields: impl Iterator<Item = Cow<'a, str>>,
+    ) -> anyhow::Result<()> {
+        let fields: Vec<String> = fields.map(|f| escape_csv(&f)).collect();
+        writeln!(self.out, "{}", fields.join(","))?;
+        Ok(())
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl<W: Write> Reporter for Csv<W> {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        if !self.has_header {
+            self.write_record(row.iter().map(|(name, _)| Cow::from(*name)))?;
+            self.has_header = true;
+        }
+        self.write_record(
+            row.iter()
+                .map(|(_, value)| Cow::from(to_string(value, false))),
+        )
+    }
+
+    /// Group membership is given by a leading "group" column.
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        self.groups += 1;
+        for row in rows {
+            let group = i64::try_from(self.groups)?;
+            let mut row_with_group = vec![("group", Value::Int(group))];
+            row_with_group.extend(row.iter().cloned());
+            self.row(&row_with_group)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct Json<W: Write> {
+    out: W,
+    records: Vec<serde_json::Value>,
+    asides: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<W: Write> Json<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            records: Vec::new(),
+            asides: serde_json::Map::new(),
+        }
+    }
+}
+
+impl<W: Write> Reporter for Json<W> {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        self.records.push(to_json(row));
+        Ok(())
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        let group = rows.iter().map(|row| to_json(row)).collect();
+        self.records.push(serde_json::Value::Array(group));
+        Ok(())
+    }
+
+    fn aside(
+        &mut self,
+        name: &'static str,
+        rows: &[Vec<Field>],
+    ) -> anyhow::Result<()> {
+        let rows = rows.iter().map(|row| to_json(row)).collect();
+        self.asides
+            .insert(name.to_string(), serde_json::Value::Array(rows));
+        Ok(())
+    }
+
+    /// A plain array of the records, unless there are asides, in which
+    /// case an object of the records and the asides, each by its name.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let records =
+            serde_json::Value::Array(std::mem::take(&mut self.records));
+        let asides = std::mem::take(&mut self.asides);
+        if asides.is_empty() {
+            serde_json::to_writer(&mut self.out, &records)?;
+        } else {
+            let mut object = serde_json::Map::new();
+            object.insert("records".to_string(), records);
+            object.extend(asides);
+            serde_json::to_writer(&mut self.out, &object)?;
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+}
+
+struct Ndjson<W: Write> {
+    out: W,
+}
+
+impl<W: Write> Ndjson<W> {
+    fn new(out: W) -> Self {
+        Self { out }
+    }
+}
+
+impl<W: Write> Reporter for Ndjson<W> {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.out, &to_json(row))?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        let group: Vec<serde_json::Value> =
+            rows.iter().map(|row| to_json(row)).collect();
+        serde_json::to_writer(&mut self.out, &group)?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+struct Raw<W: Write> {
+    out: W,
+    options: Options,
+}
+
+impl<W: Write> Raw<W> {
+    fn new(out: W, options: Options) -> Self {
+        Self { out, options }
+    }
+}
+
+impl<W: Write> Reporter for Raw<W> {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        for (i, (_, value)) in row.iter().enumerate() {
+            if i > 0 {
+                write!(self.out, "{}", self.options.field_sep)?;
+            }
+            match value {
+                Value::Path(path) => output::write_path(
+                    &mut self.out,
+                    path,
+                    self.options.quote,
+                    self.options.output_encoding,
+                )?,
+                value => write!(
+                    self.out,
+                    "{}",
+                    to_string(value, self.options.human)
+                )?,
+            }
+        }
+        write!(self.out, "{}", self.options.record_sep)?;
+        Ok(())
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        for row in rows {
+            self.row(row)?;
+        }
+        write!(self.out, "{}", self.options.group_sep)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Decorator which canonicalizes path fields before passing rows on.
+struct RealPaths {
+    inner: Box<dyn Reporter>,
+}
+
+impl RealPaths {
+    /// Resolved paths of the row, in order, or None if any failed and the
+    /// error policy is to skip.
+    fn resolve(row: &[Field]) -> anyhow::Result<Option<Vec<PathBuf>>> {
+        let mut resolved = Vec::new();
+        for (_, value) in row {
+            if let Value::Path(path) = value {
+                let result = path
+                    .canonicalize()
+                    .with_context(|| format!("path={path:?}"));
+                match on_error::handle(result, "Failed to canonicalize.")? {
+                    Some(path) => resolved.push(path),
+                    None => return Ok(None),
+                }
+            }
+        }
+        Ok(Some(resolved))
+    }
+
+    fn replace<'a>(
+        row: &[Field<'a>],
+        resolved: &'a [PathBuf],
+    ) -> Vec<Field<'a>> {
+        let mut resolved = resolved.iter();
+        row.iter()
+            .map(|(name, value)| match value {
+                Value::Path(_) => {
+                    // Same number and order, by construction in resolve.
+                    let path = resolved.next().unwrap();
+                    (*name, Value::Path(path))
+                }
+                value => (*name, value.clone()),
+            })
+            .collect()
+    }
+}
+
+impl Reporter for RealPaths {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        if let Some(resolved) = Self::resolve(row)? {
+            self.inner.row(&Self::replace(row, &resolved))?;
+        }
+        Ok(())
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        let mut resolved: Vec<(&Vec<Field>, Vec<PathBuf>)> = Vec::new();
+        for row in rows {
+            if let Some(paths) = Self::resolve(row)? {
+                resolved.push((row, paths));
+            }
+        }
+        if resolved.is_empty() {
+            return Ok(());
+        }
+        let rows: Vec<Vec<Field>> = resolved
+            .iter()
+            .map(|(row, paths)| Self::replace(row, paths))
+            .collect();
+        self.inner.group(&rows)
+    }
+
+    fn aside(
+        &mut self,
+        name: &'static str,
+        rows: &[Vec<Field>],
+    ) -> anyhow::Result<()> {
+        let mut resolved: Vec<(&Vec<Field>, Vec<PathBuf>)> = Vec::new();
+        for row in rows {
+            if let Some(paths) = Self::resolve(row)? {
+                resolved.push((row, paths));
+            }
+        }
+        let rows: Vec<Vec<Field>> = resolved
+            .iter()
+            .map(|(row, paths)| Self::replace(row, paths))
+            .collect();
+        self.inner.aside(name, &rows)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+}
+
+/// Decorator which strips the trim prefix from path fields before passing
+/// rows on.
+struct TrimPrefix {
+    inner: Box<dyn Reporter>,
+}
+
+impl TrimPrefix {
+    fn trim<'a>(row: &[Field<'a>]) -> Vec<Field<'a>> {
+        row.iter()
+            .map(|(name, value)| match value {
+                Value::Path(path) => (*name, Value::Path(trim_prefix(path))),
+                value => (*name, value.clone()),
+            })
+            .collect()
+    }
+}
+
+impl Reporter for TrimPrefix {
+    fn row(&mut self, row: &[Field]) -> anyhow::Result<()> {
+        self.inner.row(&Self::trim(row))
+    }
+
+    fn group(&mut self, rows: &[Vec<Field>]) -> anyhow::Result<()> {
+        let rows: Vec<Vec<Field>> =
+            rows.iter().map(|row| Self::trim(row)).collect();
+        self.inner.group(&rows)
+    }
+
+    fn aside(
+        &mut self,
+        name: &'static str,
+        rows: &[Vec<Field>],
+    ) -> anyhow::Result<()> {
+        let rows: Vec<Vec<Field>> =
+            rows.iter().map(|row| Self::trim(row)).collect();
+        self.inner.aside(name, &rows)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+}
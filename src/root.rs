@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::data;
+
+/// Positional root argument of the commands which can take a list of paths
+/// instead of walking a tree. Each describes, in its own help, how that
+/// changes what it does.
+#[derive(clap::Args, Debug, serde::Serialize)]
+pub struct Root {
+    /// Root of the tree to walk, or "-" to read a list of paths from
+    /// stdin instead, separated by nulls, if there are any, or else by
+    /// linefeeds. Listed paths are taken as they are, without descending
+    /// into directories.
+    #[clap(default_value = ".")]
+    pub root_path: PathBuf,
+}
+
+impl Root {
+    /// The given root, canonicalized, unless it is "-", for stdin.
+    pub fn resolve(&self) -> anyhow::Result<PathBuf> {
+        let given = &self.root_path;
+        if data::is_stdin(given) {
+            return Ok(given.clone());
+        }
+        let canonicalized = given
+            .canonicalize()
+            .context(format!("Failed to canonicalize path={:?}", given))?;
+        tracing::debug!(?given, ?canonicalized, "Canonicalized root path.");
+        Ok(canonicalized)
+    }
+}
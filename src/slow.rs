@@ -0,0 +1,34 @@
+use std::{
+    path::Path,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+/// Set the threshold for the whole process. Only the first call has effect.
+pub fn set(threshold: Duration) {
+    if THRESHOLD.set(threshold).is_err() {
+        tracing::warn!(
+            ?threshold,
+            "Slow path threshold already set. Ignoring."
+        );
+    }
+}
+
+/// Perform the operation on the path, warning if it takes longer than the
+/// threshold, if one is set, so that paths on slow storage (e.g. a stale
+/// network mount) can be told apart from a hung run.
+pub fn time<T>(op: &'static str, path: &Path, f: impl FnOnce() -> T) -> T {
+    let Some(threshold) = THRESHOLD.get() else {
+        return f();
+    };
+    let started = Instant::now();
+    let result = f();
+    let elapsed = started.elapsed();
+    if elapsed > *threshold {
+        tracing::debug!(op, ?path, ?elapsed, "Slow path.");
+        eprintln!("Warning: {op} of path={path:?} took {elapsed:?}");
+    }
+    result
+}
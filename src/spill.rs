@@ -0,0 +1,127 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::Context;
+
+use crate::data::Meta;
+
+/// Most batches a spill is split into, each a file open at once. Beyond
+/// it, batches are bigger than the budget, which is only a soft limit.
+const MAX_BATCHES: u64 = 256;
+
+/// Files set aside on disk, when there are too many to hold in memory, to
+/// be taken back a batch at a time.
+///
+/// Format is one Meta per line, as JSON, in a temporary file, which is
+/// removed as soon as it is created, so that it is gone with the process,
+/// however that ends.
+pub struct Spill {
+    file: BufWriter<fs::File>,
+    count: usize,
+    footprint: u64,
+}
+
+impl Spill {
+    pub fn create() -> anyhow::Result<Self> {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+        let n = CREATED.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "fx-spill-{}-{}",
+            process::id(),
+            n
+        ));
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .context(format!("Failed to create spill file at {path:?}"))?;
+        fs::remove_file(&path)
+            .context(format!("Failed to unlink spill file at {path:?}"))?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            count: 0,
+            footprint: 0,
+        })
+    }
+
+    pub fn push(&mut self, meta: &Meta) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.file, meta)?;
+        self.file.write_all(b"\n")?;
+        self.count += 1;
+        self.footprint += footprint(meta);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// All the files, in batches of roughly the budget each, as estimated
+    /// by footprint, each read only when it is taken. Files of the same key
+    /// are always in the same batch.
+    pub fn batches<K, F>(
+        self,
+        budget: u64,
+        key: F,
+    ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Vec<Meta>>>>
+    where
+        K: Hash,
+        F: Fn(&Meta) -> K,
+    {
+        let n = self
+            .footprint
+            .div_ceil(budget.max(1))
+            .clamp(1, MAX_BATCHES)
+            .min(self.count.max(1) as u64);
+        tracing::debug!(
+            files = self.count,
+            footprint = self.footprint,
+            batches = n,
+            "Splitting spill."
+        );
+        let mut parts: Vec<Spill> =
+            (0..n).map(|_| Spill::create()).collect::<Result<_, _>>()?;
+        for meta_result in self.read()? {
+            let meta = meta_result?;
+            let mut hasher = DefaultHasher::new();
+            key(&meta).hash(&mut hasher);
+            let i = usize::try_from(hasher.finish() % n)?;
+            parts[i].push(&meta)?;
+        }
+        Ok(parts
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .map(|part| part.read()?.collect()))
+    }
+
+    fn read(
+        self,
+    ) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Meta>>> {
+        let mut file = self
+            .file
+            .into_inner()
+            .map_err(|error| error.into_error())
+            .context("Failed to flush spill file")?;
+        file.seek(SeekFrom::Start(0))
+            .context("Failed to rewind spill file")?;
+        Ok(BufReader::new(file).lines().map(|line_result| {
+            let line = line_result.context("Failed to read spill file")?;
+            serde_json::from_str(&line).context("Failed to parse spill file")
+        }))
+    }
+}
+
+/// Rough, but proportional, estimate of what a Meta takes in memory.
+pub fn footprint(meta: &Meta) -> u64 {
+    (size_of::<Meta>() + meta.path.as_os_str().len()) as u64
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const MONTH: u64 = 30 * DAY;
+const YEAR: u64 = 365 * DAY;
+
+/// Parse a duration like "90s", "15min", "2h", "7d", "4w", "6m" or "2y".
+/// Months and years are approximated as 30 and 365 days.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("Missing unit in duration: {s:?}"))?;
+    let (n, unit) = s.split_at(split);
+    let n: u64 = n
+        .parse()
+        .map_err(|e| anyhow!("Invalid number in duration: {s:?}: {e}"))?;
+    let unit: u64 = match unit {
+        "s" => 1,
+        "min" => MINUTE,
+        "h" => HOUR,
+        "d" => DAY,
+        "w" => WEEK,
+        "m" | "mo" => MONTH,
+        "y" => YEAR,
+        _ => bail!(
+            "Unknown unit in duration: {s:?}. \
+            Expected one of: s, min, h, d, w, m, y."
+        ),
+    };
+    let secs = n
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("Duration too large: {s:?}"))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Current time, in seconds since the Unix epoch, as in Meta timestamps.
+pub fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(d) => i64::try_from(d.as_secs()).unwrap_or(i64::MAX),
+        Err(e) => -i64::try_from(e.duration().as_secs()).unwrap_or(i64::MAX),
+    }
+}
+
+/// Timestamp, in seconds since the Unix epoch, of the given time ago.
+pub fn ago(duration: Duration) -> i64 {
+    now()
+        .saturating_sub(i64::try_from(duration.as_secs()).unwrap_or(i64::MAX))
+}
+
+/// Local date and time of a timestamp in seconds since the Unix epoch.
+pub fn to_local(secs: i64) -> anyhow::Result<DateTime<Local>> {
+    Local
+        .timestamp_opt(secs, 0)
+        .single()
+        .ok_or_else(|| anyhow!("Invalid timestamp: {secs}"))
+}
+
+/// Local date and time of a timestamp, as ISO 8601 with the offset.
+pub fn fmt_local(secs: i64) -> anyhow::Result<String> {
+    Ok(to_local(secs)?.format("%Y-%m-%dT%H:%M:%S%:z").to_string())
+}
+
+/// Parse a point in time, into seconds since the Unix epoch, given as
+/// "@<secs>" since the epoch, as ISO 8601 with an offset (e.g.
+/// "2024-05-01T13:00:00Z"), or as local date and time ("2024-05-01T13:00:00")
+/// or date alone, meaning its start.
+pub fn parse_timestamp(s: &str) -> anyhow::Result<i64> {
+    let s = s.trim();
+    if let Some(secs) = s.strip_prefix('@') {
+        return secs
+            .parse()
+            .map_err(|e| anyhow!("Invalid epoch seconds: {s:?}: {e}"));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.timestamp());
+    }
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|date| date.and_time(chrono::NaiveTime::MIN))
+        })
+        .map_err(|_| {
+            anyhow!(
+                "Invalid time: {s:?}. Expected one of: @<secs>, \
+                YYYY-MM-DDTHH:MM:SS[Z|+HH:MM], YYYY-MM-DD."
+            )
+        })?;
+    // Ambiguous local times, as in DST fold, are taken at their earliest.
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.timestamp())
+        .ok_or_else(|| anyhow!("Nonexistent local time: {s:?}"))
+}
+
+/// Time of system boot, in seconds since the Unix epoch, as recorded by the
+/// kernel in /proc/stat.
+pub fn boot() -> anyhow::Result<i64> {
+    let path = "/proc/stat";
+    let stat = fs::read_to_string(path)
+        .context(format!("Failed to read path={path:?}"))?;
+    let btime = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .ok_or_else(|| anyhow!("Missing btime in {path:?}"))?;
+    btime
+        .trim()
+        .parse()
+        .context(format!("Invalid btime in {path:?}: {btime:?}"))
+}
@@ -1,6 +1,14 @@
+use std::sync::Arc;
+
 use tracing::{Level, level_filters::LevelFilter};
 
-pub fn init(level: LevelFilter) -> anyhow::Result<()> {
+use crate::profile::Profile;
+
+/// With a profile, spans are also recorded into it, regardless of level.
+pub fn init(
+    level: LevelFilter,
+    profile: Option<Arc<Profile>>,
+) -> anyhow::Result<()> {
     use tracing_subscriber::{
         EnvFilter, Layer,
         fmt::{self, format::FmtSpan},
@@ -23,8 +31,13 @@ pub fn init(level: LevelFilter) -> anyhow::Result<()> {
         .with_filter(
             EnvFilter::from_default_env().add_directive(level.into()),
         );
+    // Trace-level spans are per-file, so would only add overhead.
+    let layer_profile = profile
+        .map(|profile| profile.layer().with_filter(LevelFilter::DEBUG));
     tracing::subscriber::set_global_default(
-        tracing_subscriber::registry().with(layer_stderr),
+        tracing_subscriber::registry()
+            .with(layer_stderr)
+            .with(layer_profile),
     )?;
     Ok(())
 }
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, fs};
+
+use anyhow::{Context, anyhow};
+
+/// User ID, given either as a number or as a name in /etc/passwd.
+pub fn uid(user: &str) -> anyhow::Result<u32> {
+    id(user, "/etc/passwd")
+}
+
+/// Group ID, given either as a number or as a name in /etc/group.
+pub fn gid(group: &str) -> anyhow::Result<u32> {
+    id(group, "/etc/group")
+}
+
+/// Both files are of lines of colon-separated fields, of which the first
+/// is the name and the third is the ID.
+fn id(name_or_id: &str, db: &str) -> anyhow::Result<u32> {
+    if let Ok(id) = name_or_id.parse::<u32>() {
+        return Ok(id);
+    }
+    let entries = fs::read_to_string(db)
+        .context(format!("Failed to read path={:?}", db))?;
+    entries
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?;
+            Some((name, id))
+        })
+        .find(|(name, _)| *name == name_or_id)
+        .ok_or_else(|| anyhow!("Not found in {:?}: {:?}", db, name_or_id))
+        .and_then(|(_, id)| {
+            id.parse::<u32>()
+                .context(format!("Invalid ID in {:?}: {:?}", db, id))
+        })
+}
+
+/// Names of user or group IDs, looked up once per ID, since owners repeat
+/// across many files, and lookups re-read the whole database. IDs which
+/// fail to resolve are named by their numbers, which are cached too.
+#[derive(Debug)]
+pub struct Names {
+    db: &'static str,
+    cache: HashMap<u32, String>,
+}
+
+impl Names {
+    /// Of users, from /etc/passwd.
+    pub fn users() -> Self {
+        Self::new("/etc/passwd")
+    }
+
+    /// Of groups, from /etc/group.
+    pub fn groups() -> Self {
+        Self::new("/etc/group")
+    }
+
+    fn new(db: &'static str) -> Self {
+        Self {
+            db,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn name(&mut self, id: u32) -> &str {
+        let db = self.db;
+        self.cache.entry(id).or_insert_with(|| {
+            name(id, db).unwrap_or_else(|error| {
+                tracing::debug!(id, db, ?error, "Name lookup failed.");
+                id.to_string()
+            })
+        })
+    }
+}
+
+/// Reverse of id: the name of the first entry with the ID.
+fn name(id: u32, db: &str) -> anyhow::Result<String> {
+    let entries = fs::read_to_string(db)
+        .context(format!("Failed to read path={:?}", db))?;
+    entries
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse::<u32>().ok()?;
+            Some((name, id))
+        })
+        .find(|(_, entry_id)| *entry_id == id)
+        .map(|(name, _)| name.to_string())
+        .ok_or_else(|| anyhow!("Not found in {:?}: {:?}", db, id))
+}
@@ -0,0 +1,67 @@
+use std::io;
+
+use anyhow::anyhow;
+
+use crate::{data::Meta, hash};
+
+const PREFIX: &str = "user.fx.";
+
+/// Lookup the hash of the file in its own extended attributes, computing
+/// and storing it there if it is absent or stale. Stored alongside are the
+/// file's mtime and size, as of hashing, by which staleness is judged.
+///
+/// Storage is best-effort: where extended attributes are unsupported or not
+/// writable, this just computes the hash.
+///
+/// Format of the attribute value:
+///
+/// ```text
+/// <mtime> <size> <hash as hex>
+/// ```
+pub fn hash<F>(algo: &str, meta: &Meta, compute: F) -> anyhow::Result<Vec<u8>>
+where
+    F: FnOnce() -> anyhow::Result<Vec<u8>>,
+{
+    let path = &meta.path;
+    let name = format!("{PREFIX}{algo}");
+    match xattr::get_deref(path, &name) {
+        Ok(Some(value)) => match parse(&value) {
+            Ok((mtime, size, hash))
+                if mtime == meta.mtime && size == meta.size =>
+            {
+                tracing::trace!(?path, algo, "Xattr cache hit.");
+                return Ok(hash);
+            }
+            Ok(_) => {
+                tracing::trace!(?path, algo, "Xattr cache stale.");
+            }
+            Err(error) => {
+                tracing::warn!(?path, ?name, ?error, "Invalid xattr value.");
+            }
+        },
+        Ok(None) => {}
+        Err(error) => log_unavailable(path, &error),
+    }
+    let hash = compute()?;
+    let value =
+        format!("{} {} {}", meta.mtime, meta.size, hash::to_hex(&hash));
+    if let Err(error) = xattr::set_deref(path, &name, value.as_bytes()) {
+        log_unavailable(path, &error);
+    }
+    Ok(hash)
+}
+
+fn log_unavailable(path: &std::path::Path, error: &io::Error) {
+    // Expected on filesystems without xattr support or on read-only files,
+    // which are just not cached, so not worth more than debug.
+    tracing::debug!(?path, ?error, "Xattr cache unavailable.");
+}
+
+fn parse(value: &[u8]) -> anyhow::Result<(i64, u64, Vec<u8>)> {
+    let value = std::str::from_utf8(value)?;
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let [mtime, size, hash] = fields[..] else {
+        return Err(anyhow!("Expected 3 fields, found {}", fields.len()));
+    };
+    Ok((mtime.parse()?, size.parse()?, hash::from_hex(hash)?))
+}
@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    os::unix::fs::{PermissionsExt, symlink},
+};
+
+use assert_cmd::Command;
+
+#[test]
+fn audit_perms() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let set_mode = |name: &str, mode: u32| {
+        let path = root_path.join(name);
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    };
+    fs::set_permissions(&root_path, fs::Permissions::from_mode(0o755))
+        .unwrap();
+    let plain = set_mode("plain", 0o644);
+    let writable = set_mode("writable", 0o666);
+    let setuid = set_mode("setuid", 0o4755);
+    symlink(&plain, root_path.join("link")).unwrap();
+    let out = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("audit-perms")
+            .args(args)
+            .args(["--format", "csv"])
+            .arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    let (uid, gid) = {
+        use std::os::unix::fs::MetadataExt;
+        let meta = fs::metadata(&plain).unwrap();
+        (meta.uid(), meta.gid())
+    };
+    assert_eq!(
+        format!(
+            "mode,uid,gid,path\n0666,{uid},{gid},{}\n",
+            writable.display()
+        ),
+        out(&["--world-writable"])
+    );
+    assert_eq!(
+        format!("mode,uid,gid,path\n4755,{uid},{gid},{}\n", setuid.display()),
+        out(&["--setuid", "--owner", &uid.to_string()])
+    );
+    assert_eq!("", out(&["--setuid", "--owner", &(uid + 1).to_string()]));
+
+    // At least one criterion is required.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("audit-perms").arg(&root_path);
+    cmd.assert().failure();
+}
+
+#[test]
+fn audit_perms_names() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let path = root_path.join("setuid");
+    fs::write(&path, "").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o4755)).unwrap();
+    let meta = fs::metadata(&path).unwrap();
+    // Named as in the database, or else by number.
+    let name = |id: u32, db: &str| {
+        fs::read_to_string(db)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| line.split(':').collect::<Vec<&str>>())
+            .find(|fields| fields.get(2) == Some(&id.to_string().as_str()))
+            .map_or(id.to_string(), |fields| fields[0].to_string())
+    };
+    let (user, group) = (
+        name(meta.uid(), "/etc/passwd"),
+        name(meta.gid(), "/etc/group"),
+    );
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["audit-perms", "--setuid", "--names", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "mode,user,group,path\n4755,{user},{group},{}\n",
+        path.display()
+    ));
+}
@@ -0,0 +1,43 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn browse_print() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::create_dir_all(root_path.join("x/y")).unwrap();
+    fs::create_dir(root_path.join("z")).unwrap();
+    for (path, size) in
+        [("x/y/f", 600), ("x/g", 200), ("z/h", 100), ("i", 100)]
+    {
+        fs::File::create(root_path.join(path))
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+    }
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["browse", "--print"]).arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let root = root_path.display();
+    // Largest first, ties by name, each sized as a share of its parent.
+    let expected = format!(
+        "\
+{root} (1000 B)
+     800 B  80.0% [################    ] x/
+     100 B  10.0% [##                  ] i
+     100 B  10.0% [##                  ] z/
+
+{root}/x (800 B)
+     600 B  75.0% [###############     ] y/
+     200 B  25.0% [#####               ] g
+
+{root}/x/y (600 B)
+     600 B 100.0% [####################] f
+
+{root}/z (100 B)
+     100 B 100.0% [####################] h
+"
+    );
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+}
@@ -0,0 +1,47 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn casefold_collisions() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for dir in ["A/b", "a/B", "x/Straße", "x/STRASSE", "y/Z"] {
+        fs::create_dir_all(root_path.join(dir)).unwrap();
+    }
+    // Descendants of an already colliding prefix are not reported again.
+    fs::write(root_path.join("A/b/f"), "").unwrap();
+    fs::write(root_path.join("a/B/f"), "").unwrap();
+    fs::write(root_path.join("y/z"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("casefold-collisions").arg(&root_path);
+    let root = root_path.display();
+    cmd.assert().success().stdout(format!(
+        "{root}/A\n{root}/a\n\n\
+        {root}/x/STRASSE\n{root}/x/Straße\n\n\
+        {root}/y/Z\n{root}/y/z\n\n"
+    ));
+}
+
+#[test]
+fn casefold_collisions_normalize_unicode() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    // "é", composed (NFC) and decomposed (NFD).
+    let (composed, decomposed) = ("caf\u{e9}", "cafe\u{301}");
+    fs::write(root_path.join(composed), "").unwrap();
+    fs::write(root_path.join(decomposed), "").unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("casefold-collisions").args(args).arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!("", run(&[]));
+    let root = root_path.display();
+    // Sorted by bytes: "e" (0x65) before "é" (0xc3 0xa9).
+    let expected = format!("{root}/{decomposed}\n{root}/{composed}\n\n");
+    assert_eq!(expected, run(&["--normalize-unicode", "nfc"]));
+    assert_eq!(expected, run(&["--normalize-unicode", "nfd"]));
+}
@@ -0,0 +1,42 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn common_across_roots_only() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let root_a = tmp.join("a");
+    let root_b = tmp.join("b");
+    fs::create_dir(&root_a).unwrap();
+    fs::create_dir(&root_b).unwrap();
+    fs::write(root_a.join("foo"), "foo\n").unwrap();
+    fs::write(root_b.join("foo_copy"), "foo\n").unwrap();
+    // Duplicates within one root are not of interest.
+    fs::write(root_a.join("bar_1"), "bar\n").unwrap();
+    fs::write(root_a.join("bar_2"), "bar\n").unwrap();
+    fs::write(root_b.join("baz"), "baz\n").unwrap();
+
+    // Same either way, the tail pass only saving hashing.
+    for args in [&[][..], &["--no-tail-sample"][..]] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("common").args(args).arg(&root_a).arg(&root_b);
+        cmd.assert().success().stdout(format!(
+            "{}\n{}\n\n",
+            root_a.join("foo").display(),
+            root_b.join("foo_copy").display()
+        ));
+    }
+}
+
+#[test]
+fn common_overlapping_roots() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_a = tmp.path().canonicalize().unwrap();
+    let root_b = root_a.join("b");
+    fs::create_dir(&root_b).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("common").arg(&root_a).arg(&root_b);
+    cmd.assert().failure();
+}
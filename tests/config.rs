@@ -0,0 +1,15 @@
+use assert_cmd::Command;
+
+#[test]
+fn dump_config() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["--log", "info", "stale", "--older-than", "2d"])
+        .args(["--dump-config", "/nonexistent"]);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let config: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!("info", config["log_level"]);
+    let stale = &config["cmd"]["stale"];
+    assert_eq!(2 * 24 * 60 * 60, stale["older_than"]);
+    assert_eq!("table", stale["format"]);
+    assert_eq!("/nonexistent", stale["root_path"]);
+}
@@ -0,0 +1,27 @@
+use std::os::unix::fs::symlink;
+
+use assert_cmd::Command;
+
+#[test]
+fn dang_root_is_dangling_symlink() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().join("broken");
+    symlink(tmp.path().join("nowhere"), &root_path).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dang").arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n", root_path.display()));
+}
+
+#[test]
+fn dang_root_is_valid_symlink() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().join("link");
+    symlink(tmp.path(), &root_path).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dang").arg(&root_path);
+    cmd.assert().success().stdout("");
+}
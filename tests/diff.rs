@@ -0,0 +1,127 @@
+#![cfg(feature = "snap")]
+
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::Command;
+
+fn snap(root_path: &Path, output: &Path) {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("snap").arg("--out").arg(output).arg(root_path);
+    cmd.assert().success();
+}
+
+#[test]
+fn diff_snapshots() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let (snap_a, snap_b) = (tmp.path().join("a"), tmp.path().join("b"));
+    fs::create_dir(&root_path).unwrap();
+    let path = |name: &str| root_path.join(name);
+    fs::write(path("deleted"), "1").unwrap();
+    fs::write(path("moved"), "22").unwrap();
+    fs::write(path("grown"), "333").unwrap();
+    fs::write(path("chmoded"), "4444").unwrap();
+    fs::set_permissions(path("chmoded"), fs::Permissions::from_mode(0o644))
+        .unwrap();
+    snap(&root_path, &snap_a);
+
+    fs::remove_file(path("deleted")).unwrap();
+    fs::rename(path("moved"), path("moved-to")).unwrap();
+    fs::write(path("grown"), "333333").unwrap();
+    fs::set_permissions(path("chmoded"), fs::Permissions::from_mode(0o600))
+        .unwrap();
+    fs::write(path("added"), "55555").unwrap();
+    snap(&root_path, &snap_b);
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["diff", "--format", "csv"])
+        .arg(&snap_a)
+        .arg(&snap_b);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut lines = out.lines();
+    assert_eq!(Some("kind,field,before,after,path"), lines.next());
+    // Timestamps change too, but by how much depends on timing.
+    let lines: Vec<&str> = lines
+        .filter(|line| !line.contains("time,"))
+        .filter(|line| !line.ends_with(&format!(",{}", root_path.display())))
+        .collect();
+    let p = |name: &str| path(name).display().to_string();
+    assert_eq!(
+        vec![
+            format!("added,,,,{}", p("added")),
+            format!("modified,mode,0644,0600,{}", p("chmoded")),
+            format!("deleted,,,,{}", p("deleted")),
+            format!("modified,size,3,6,{}", p("grown")),
+            format!(
+                "moved,path,{},{},{}",
+                p("moved"),
+                p("moved-to"),
+                p("moved-to")
+            ),
+        ],
+        lines
+    );
+}
+
+#[test]
+fn diff_ignore_field() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let (snap_a, snap_b) = (tmp.path().join("a"), tmp.path().join("b"));
+    fs::create_dir(&root_path).unwrap();
+    let (touched, chmoded) =
+        (root_path.join("touched"), root_path.join("chmoded"));
+    fs::write(&touched, "1").unwrap();
+    fs::write(&chmoded, "2").unwrap();
+    fs::set_permissions(&chmoded, fs::Permissions::from_mode(0o644)).unwrap();
+    let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+    fs::File::options()
+        .write(true)
+        .open(&touched)
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_accessed(old).set_modified(old))
+        .unwrap();
+    snap(&root_path, &snap_a);
+
+    fs::File::options()
+        .write(true)
+        .open(&touched)
+        .unwrap()
+        .set_times(
+            fs::FileTimes::new()
+                .set_accessed(SystemTime::now())
+                .set_modified(SystemTime::now()),
+        )
+        .unwrap();
+    fs::set_permissions(&chmoded, fs::Permissions::from_mode(0o600)).unwrap();
+    snap(&root_path, &snap_b);
+
+    let run = |ignored: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["diff", "--format", "csv"]);
+        for field in ignored {
+            cmd.args(["--ignore-field", field]);
+        }
+        cmd.arg(&snap_a).arg(&snap_b);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    let out = run(&[]);
+    assert!(out.contains(&format!(",{}\n", touched.display())), "{out}");
+    let times = ["atime", "mtime", "ctime"];
+    assert_eq!(
+        format!(
+            "kind,field,before,after,path\n\
+            modified,mode,0644,0600,{}\n",
+            chmoded.display()
+        ),
+        run(&times)
+    );
+    assert_eq!("", run(&[&times[..], &["perms"]].concat()));
+}
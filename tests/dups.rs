@@ -1,5 +1,8 @@
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
     fs,
+    os::unix::{ffi::OsStrExt, fs::symlink},
     path::{Path, PathBuf},
 };
 
@@ -71,6 +74,1196 @@ fn dups_1() {
     assert_eq!(groups_expected, groups_actual);
 }
 
+#[test]
+fn dups_raw_output_encoding() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let names: [&[u8]; 2] = [b"invalid_\xff_1", b"invalid_\xff_2"];
+    for name in names {
+        fs::write(root_path.join(OsStr::from_bytes(name)), "foo\n").unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups")
+        .arg("-Z")
+        .arg("--output-encoding")
+        .arg("raw")
+        .arg(&root_path);
+    let assert = cmd.assert().success();
+    let out = &assert.get_output().stdout;
+
+    let mut paths_actual: Vec<&[u8]> = out
+        .split(|b| *b == b'\0')
+        .filter(|path| !path.is_empty() && *path != b"\n")
+        .map(|path| path.strip_prefix(b"\n").unwrap_or(path))
+        .collect();
+    paths_actual.sort();
+    let paths_expected: Vec<Vec<u8>> = names
+        .iter()
+        .map(|name| root_path.join(OsStr::from_bytes(name)))
+        .map(|path| path.as_os_str().as_bytes().to_vec())
+        .collect();
+    assert_eq!(paths_expected, paths_actual);
+}
+
+#[test]
+fn dups_raw_output_encoding_requires_null_sep() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups")
+        .arg("--output-encoding")
+        .arg("raw")
+        .arg("tests/data/dups/1/");
+    cmd.assert().failure();
+}
+
+#[test]
+fn dups_resolve_symlinks() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let outside = tmp.join("outside");
+    let root_path = tmp.join("root");
+    fs::create_dir(&outside).unwrap();
+    fs::create_dir(&root_path).unwrap();
+    fs::write(outside.join("foo"), "foo\n").unwrap();
+    fs::write(root_path.join("foo"), "foo\n").unwrap();
+    fs::write(root_path.join("bar"), "bar\n").unwrap();
+    symlink(outside.join("foo"), root_path.join("link_to_outside_foo"))
+        .unwrap();
+    symlink(root_path.join("bar"), root_path.join("link_to_bar")).unwrap();
+    symlink(root_path.join("nothing"), root_path.join("dangling")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups").arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    assert!(out.iter().all(|b| b.is_ascii_whitespace()));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups").arg("--resolve-symlinks").arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut paths_actual: Vec<&str> =
+        out.lines().filter(|line| !line.is_empty()).collect();
+    paths_actual.sort();
+    let paths_expected =
+        [root_path.join("foo"), root_path.join("link_to_outside_foo")];
+    let paths_expected: Vec<&str> =
+        paths_expected.iter().map(|p| p.to_str().unwrap()).collect();
+    assert_eq!(paths_expected, paths_actual);
+}
+
+#[test]
+fn dups_symlink_content_target_path() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("foo"), "foo\n").unwrap();
+    fs::write(root_path.join("foo_copy"), "foo\n").unwrap();
+    symlink("foo", root_path.join("link_1")).unwrap();
+    symlink("foo", root_path.join("link_2")).unwrap();
+    symlink("foo_copy", root_path.join("link_3")).unwrap();
+
+    // Grouped by where they point, apart from the files.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--symlink-content", "target-path"])
+        .arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut groups: Vec<&str> = out.split("\n\n").collect();
+    groups.sort();
+    let expected = [
+        "",
+        &format!(
+            "{}\n{}",
+            root_path.join("foo").display(),
+            root_path.join("foo_copy").display()
+        ),
+        &format!(
+            "{}\n{}",
+            root_path.join("link_1").display(),
+            root_path.join("link_2").display()
+        ),
+    ];
+    assert_eq!(expected[..], groups[..]);
+}
+
+#[test]
+fn dups_real_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let outside = tmp.join("outside");
+    let root_path = tmp.join("root");
+    fs::create_dir(&outside).unwrap();
+    fs::create_dir(&root_path).unwrap();
+    fs::write(outside.join("foo"), "foo\n").unwrap();
+    fs::write(root_path.join("foo"), "foo\n").unwrap();
+    symlink(outside.join("foo"), root_path.join("link_to_outside_foo"))
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--resolve-symlinks", "--real-paths"])
+        .arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut paths_actual: Vec<&str> =
+        out.lines().filter(|line| !line.is_empty()).collect();
+    paths_actual.sort();
+    let paths_expected = [outside.join("foo"), root_path.join("foo")];
+    let paths_expected: Vec<&str> =
+        paths_expected.iter().map(|p| p.to_str().unwrap()).collect();
+    assert_eq!(paths_expected, paths_actual);
+}
+
+#[test]
+fn dups_checkpoint_resume() {
+    let tmp = tempfile::tempdir().unwrap();
+    let checkpoint = tmp.path().join("checkpoint");
+    let run = || {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups")
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .arg("tests/data/dups/1/");
+        let out = cmd.assert().success().get_output().stdout.clone();
+        let mut lines: Vec<String> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        lines.sort();
+        lines
+    };
+
+    let out_1 = run();
+    let records_1 = read(&checkpoint);
+    // 2 groups of 2, each hashed once.
+    assert_eq!(4, records_1.lines().count());
+
+    let out_2 = run();
+    let records_2 = read(&checkpoint);
+    assert_eq!(out_1, out_2);
+    assert_eq!(records_1, records_2, "Nothing should've been re-hashed.");
+}
+
+#[test]
+fn dups_xattr_cache() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (a, b) = (root_path.join("a"), root_path.join("b"));
+    fs::write(&a, "foo\n").unwrap();
+    fs::write(&b, "foo\n").unwrap();
+    if xattr::set(&a, "user.fx.test", b"").is_err() {
+        eprintln!("Skipping: no xattr support in {root_path:?}");
+        return;
+    }
+    let run = || {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--xattr-cache"]).arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+
+    assert_eq!(format!("{}\n{}\n\n", a.display(), b.display()), run());
+    let cached = xattr::get(&b, "user.fx.xxh").unwrap().unwrap();
+    let cached = String::from_utf8(cached).unwrap();
+    let (mtime_and_size, _) = cached.rsplit_once(' ').unwrap();
+
+    // A forged, but fresh-looking, hash is trusted, rather than recomputed.
+    let forged = format!("{mtime_and_size} 00");
+    xattr::set(&b, "user.fx.xxh", forged.as_bytes()).unwrap();
+    assert_eq!("", run());
+}
+
+#[test]
+fn dups_group_by_dir() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (x, y) = (root_path.join("x"), root_path.join("y"));
+    fs::create_dir(&x).unwrap();
+    fs::create_dir(&y).unwrap();
+    for dir in [&x, &y] {
+        fs::write(dir.join("foo"), "foo").unwrap();
+        fs::write(dir.join("bar"), "bar_bar").unwrap();
+    }
+    fs::write(x.join("baz_1"), "baz").unwrap();
+    fs::write(x.join("baz_2"), "baz").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--group-by-dir", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "groups,size,dir_a,dir_b\n2,10,{x},{y}\n1,3,{x},{x}\n",
+        x = x.display(),
+        y = y.display()
+    ));
+}
+
+#[test]
+fn dups_quote_style() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("it's \"a\"\n"), "foo").unwrap();
+    fs::write(root_path.join("it's \"b\"\n"), "foo").unwrap();
+    let root = root_path.display();
+    let out = |style: &str| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "-q", "--quote-style", style])
+            .arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        format!("'{root}/it'\\''s \"a\"\n'\n'{root}/it'\\''s \"b\"\n'\n\n"),
+        out("shell")
+    );
+    assert_eq!(
+        format!(
+            "\"{root}/it's \\\"a\\\"\\n\"\n\"{root}/it's \\\"b\\\"\\n\"\n\n"
+        ),
+        out("c")
+    );
+}
+
+#[test]
+fn dups_order_limit_reverse() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for name in ["big_1", "big_2", "big_3"] {
+        fs::write(root_path.join(name), "0123456789").unwrap();
+    }
+    for name in ["small_1", "small_2"] {
+        fs::write(root_path.join(name), "01").unwrap();
+    }
+    let groups = |args: &[&str]| -> Vec<Vec<String>> {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups").args(args).arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out)
+            .unwrap()
+            .split("\n\n")
+            .map(|group| {
+                group
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(|path| {
+                        Path::new(path)
+                            .strip_prefix(&root_path)
+                            .unwrap()
+                            .to_str()
+                            .unwrap()
+                            .to_string()
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .filter(|group| !group.is_empty())
+            .collect()
+    };
+    let big = vec!["big_1", "big_2", "big_3"];
+    let small = vec!["small_1", "small_2"];
+    assert_eq!(vec![big.clone(), small.clone()], groups(&[]));
+    assert_eq!(vec![big.clone()], groups(&["--lim", "1"]));
+    assert_eq!(vec![big.clone()], groups(&["--min-reclaimable", "20"]));
+    assert_eq!(vec![small.clone(), big], groups(&["--reverse"]));
+    assert_eq!(vec![small], groups(&["--reverse", "--lim", "1"]));
+}
+
+#[test]
+fn dups_ignore_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let ignore_file = tmp.path().join("ignore");
+    fs::write(&ignore_file, "*_2\n!foo_2\n").unwrap();
+    let root_path =
+        PathBuf::from("tests/data/dups/1/").canonicalize().unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups")
+        .arg("--ignore-file")
+        .arg(&ignore_file)
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        root_path.join("foo_1").display(),
+        root_path.join("foo_2").display()
+    ));
+}
+
 fn read<P: AsRef<Path>>(path: P) -> String {
     fs::read_to_string(path.as_ref()).unwrap()
 }
+
+#[test]
+fn dups_root_not_canonicalizable() {
+    let tmp = tempfile::tempdir().unwrap();
+    let gone = tmp.path().join("gone");
+    fs::create_dir(&gone).unwrap();
+    // Removed working dir cannot be canonicalized, but can still be read.
+    let bin = assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME"));
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(r#"cd "$1" && rmdir "$1" && exec "$2" dups ."#)
+        .arg("sh")
+        .arg(&gone)
+        .arg(&bin);
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn dups_show_size() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let a = root_path.join("a");
+    let b = root_path.join("b");
+    fs::write(&a, "x".repeat(2048)).unwrap();
+    fs::write(&b, "x".repeat(2048)).unwrap();
+    let (a, b) = (a.display(), b.display());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--show-size"]).arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("2048\t{a}\n2048\t{b}\n\n"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--show-size", "-H"]).arg(&root_path);
+    let human = bytesize::ByteSize(2048).to_string();
+    cmd.assert()
+        .success()
+        .stdout(format!("{human}\t{a}\n{human}\t{b}\n\n"));
+}
+
+#[test]
+fn dups_profile() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["--profile", "dups", "tests/data/dups/1/"]);
+    let out = cmd.assert().success().get_output().stderr.clone();
+    let stages: Vec<String> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .filter_map(|line| Some(line.split_once(": ")?.0.to_string()))
+        .collect();
+    assert_eq!(
+        vec![
+            "fx",
+            "dups",
+            "find_files",
+            "group_by_size",
+            "group_by_sample_head",
+            "group_by_sample_mid",
+            "group_by_sample_tail",
+            "group_by_hash_xxh",
+            "report",
+        ],
+        stages
+    );
+}
+
+#[test]
+fn dups_paths_from_stdin() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (a, b, c) = (
+        root_path.join("a"),
+        root_path.join("b"),
+        root_path.join("c"),
+    );
+    for path in [&a, &b, &c] {
+        fs::write(path, "foo").unwrap();
+    }
+    let (a, c) = (a.display(), c.display());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "-"]).write_stdin(format!("{c}\0{a}\0"));
+    cmd.assert().success().stdout(format!("{a}\n{c}\n\n"));
+}
+
+#[test]
+fn dups_same_device() {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    // Most likely a tmpfs, so another device than the usual temp dir.
+    let Ok(other) = tempfile::tempdir_in("/dev/shm") else {
+        return;
+    };
+    let dev = |path: &Path| fs::metadata(path).unwrap().dev();
+    if dev(tmp.path()) == dev(other.path()) {
+        return;
+    }
+    let a = tmp.path().canonicalize().unwrap().join("a");
+    let b = other.path().canonicalize().unwrap().join("b");
+    let c = tmp.path().canonicalize().unwrap().join("c");
+    for path in [&a, &b, &c] {
+        fs::write(path, "foo").unwrap();
+    }
+    let listed =
+        format!("{}\0{}\0{}\0", a.display(), b.display(), c.display());
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups")
+            .args(args)
+            .arg("-")
+            .write_stdin(listed.clone());
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(3, run(&[]).lines().filter(|l| !l.is_empty()).count());
+    assert_eq!(
+        format!("{}\n{}\n\n", a.display(), c.display()),
+        run(&["--same-device"])
+    );
+}
+
+#[test]
+fn dups_deterministic() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for i in 0..20 {
+        let dir = root_path.join((i % 4).to_string());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(i.to_string()), (i % 5).to_string()).unwrap();
+    }
+    let run = || {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--blake3", "--sha"]).arg(&root_path);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+    let first = run();
+    assert!(!first.is_empty());
+    for _ in 0..4 {
+        assert_eq!(first, run());
+    }
+}
+
+#[test]
+fn dups_max_sample_memory() {
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups").args(args).arg("tests/data/dups/1/");
+        cmd.assert().success().get_output().stdout.clone()
+    };
+    // Samples reduced to hashes filter the same.
+    assert_eq!(run(&[]), run(&["--max-sample-memory", "1"]));
+}
+
+#[test]
+fn dups_heads_not_reread() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let data = "x".repeat(100);
+    fs::write(root_path.join("a1"), &data).unwrap();
+    fs::write(root_path.join("a2"), &data).unwrap();
+    // Same samples, differing only past them, so only the hash, resumed
+    // after the head, can tell it apart.
+    fs::write(root_path.join("b"), format!("{}y", &data[1..])).unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--sample", "4"]).arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        root_path.join("a1").display(),
+        root_path.join("a2").display(),
+    ));
+}
+
+#[test]
+fn dups_print_group_id() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("a1"), "aaa").unwrap();
+    fs::write(root_path.join("a2"), "aaa").unwrap();
+    // By default, the last pass is by xxh, of which the key is LE bytes.
+    let id: String = twox_hash::XxHash3_64::oneshot(b"aaa")
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--print-group-id", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "group,group_id,path\n1,{id},{}\n1,{id},{}\n",
+        root_path.join("a1").display(),
+        root_path.join("a2").display(),
+    ));
+}
+
+#[test]
+fn dups_certainty() {
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups").args(args).arg("tests/data/dups/1/");
+        cmd.assert()
+    };
+    let expected = run(&[]).success().get_output().stdout.clone();
+    assert!(!expected.is_empty());
+    // Without hash collisions, more certainty finds the same.
+    for certainty in ["probabilistic", "cryptographic", "exact"] {
+        run(&["--certainty", certainty])
+            .success()
+            .stdout(expected.clone());
+    }
+    for exact in ["--exact", "-e"] {
+        run(&[exact]).success().stdout(expected.clone());
+    }
+    run(&["--certainty", "exact", "--content-prefix", "1"]).failure();
+    run(&["--exact", "--certainty", "exact"]).failure();
+    run(&["--exact", "--content-prefix", "1"]).failure();
+}
+
+#[test]
+fn dups_count_blocks_once() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let a = root_path.join("a");
+    let b = root_path.join("b");
+    let c = root_path.join("c");
+    fs::write(&a, "x".repeat(100)).unwrap();
+    fs::hard_link(&a, &b).unwrap();
+    fs::write(&c, "x".repeat(100)).unwrap();
+    let (a, b, c) = (a.display(), b.display(), c.display());
+
+    // 2 copies beyond the one kept, by content.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--min-reclaimable", "200"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!("{a}\n{b}\n{c}\n\n"));
+
+    // Only 1, since a and b are the same storage.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--count-blocks-once", "--min-reclaimable", "200"])
+        .arg(&root_path);
+    cmd.assert().success().stdout("");
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--count-blocks-once", "--min-reclaimable", "100"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!("{a}\n{b}\n{c}\n\n"));
+}
+
+#[test]
+fn dups_hardlinks() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let [a1, a2, a3, b1, b2] =
+        ["a1", "a2", "a3", "b1", "b2"].map(|name| root_path.join(name));
+    fs::write(&a1, "aaa").unwrap();
+    fs::hard_link(&a1, &a2).unwrap();
+    fs::write(&a3, "aaa").unwrap();
+    fs::write(&b1, "bbbb").unwrap();
+    fs::hard_link(&b1, &b2).unwrap();
+    let [a1, a2, a3, b1, b2] = [&a1, &a2, &a3, &b1, &b2].map(|p| p.display());
+
+    // Links join the group of the one which was read, or are a group of
+    // their own.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups").arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("{a1}\n{a2}\n{a3}\n\n{b1}\n{b2}\n\n"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--skip-hardlinks"]).arg(&root_path);
+    cmd.assert().success().stdout(format!("{a1}\n{a3}\n\n"));
+}
+
+#[test]
+fn dups_content_prefix() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let full = root_path.join("full");
+    let truncated = root_path.join("truncated");
+    let other = root_path.join("other");
+    fs::write(&full, "x".repeat(4096)).unwrap();
+    fs::write(&truncated, "x".repeat(2048)).unwrap();
+    fs::write(&other, "y".repeat(4096)).unwrap();
+    let (full, truncated) = (full.display(), truncated.display());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups").arg(&root_path);
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--content-prefix", "1KiB"])
+        .arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("{full}\n{truncated}\n\n"));
+}
+
+#[test]
+fn dups_flat() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let path = |name: &str| root_path.join(name);
+    fs::write(path("a1"), "a".repeat(10)).unwrap();
+    fs::write(path("a2"), "a".repeat(10)).unwrap();
+    fs::write(path("b1"), "b".repeat(20)).unwrap();
+    fs::write(path("b2"), "b".repeat(20)).unwrap();
+    fs::write(path("b3"), "b".repeat(20)).unwrap();
+    fs::write(path("c"), "c".repeat(30)).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--flat"]).arg(&root_path);
+    let expected: String = [("b1", 20, 3), ("b2", 20, 3), ("b3", 20, 3)]
+        .into_iter()
+        .chain([("a1", 10, 2), ("a2", 10, 2)])
+        .map(|(name, size, copies)| {
+            format!("{size}\t{copies}\t{}\n", path(name).display())
+        })
+        .collect();
+    cmd.assert().success().stdout(expected);
+}
+
+#[test]
+fn dups_big_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for (name, size) in [("a1", 10), ("a2", 10), ("b1", 20), ("b2", 20)] {
+        fs::write(root_path.join(name), "x".repeat(size)).unwrap();
+    }
+    let out = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups").args(args).arg(&root_path);
+        cmd.assert().success().get_output().stdout.clone()
+    };
+    // Only the processing order differs, not the report.
+    assert_eq!(out(&[]), out(&["--big-first"]));
+}
+
+#[test]
+fn dups_skip_prefix_relative() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for dir in ["keep", "cache"] {
+        let dir = root_path.join(dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file"), "same").unwrap();
+    }
+    fs::write(root_path.join("other"), "same").unwrap();
+    let (keep, other) =
+        (root_path.join("keep").join("file"), root_path.join("other"));
+
+    // Relative to the root, rather than to the working directory.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--skip-prefix", "./cache"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        keep.display(),
+        other.display()
+    ));
+}
+
+#[test]
+fn dups_only_prefix() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for dir in ["a", "b/c", "b/d", "e"] {
+        let dir = root_path.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "same").unwrap();
+    }
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--only-prefix", "b", "--only-prefix"])
+        .arg(root_path.join("a"))
+        // Skipped, even though under an only prefix.
+        .args(["--skip-dir", "d"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        root_path.join("a/file").display(),
+        root_path.join("b/c/file").display()
+    ));
+}
+
+#[test]
+fn dups_skip_from_env() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for dir in ["keep", "env_dir", "cli_dir", "env_prefix"] {
+        let dir = root_path.join(dir);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file"), "same").unwrap();
+    }
+    fs::write(root_path.join("other"), "same").unwrap();
+    let (keep, other) =
+        (root_path.join("keep").join("file"), root_path.join("other"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("FX_SKIP_DIRS", "nope::env_dir")
+        .env("FX_SKIP_PREFIXES", root_path.join("env_prefix").as_os_str())
+        .args(["dups", "--skip-dir", "cli_dir"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        keep.display(),
+        other.display()
+    ));
+}
+
+#[test]
+fn dups_format_nul() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let a = root_path.join("a\nb");
+    let b = root_path.join("c");
+    fs::write(&a, "same").unwrap();
+    fs::write(&b, "same").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--show-size", "--format", "nul"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "4\0{}\0\04\0{}\0\0\0",
+        a.display(),
+        b.display()
+    ));
+}
+
+#[test]
+fn dups_grouper_cmd() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let bin = tmp.path().join("first-byte");
+    fs::write(
+        &bin,
+        "#!/bin/sh\ncase \"$1\" in *fail) exit 1;; esac\nhead -c 1 \"$1\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+    let root_path = tmp.path().join("root");
+    fs::create_dir(&root_path).unwrap();
+    let root_path = root_path.canonicalize().unwrap();
+    for (name, data) in
+        [("a1", "ax"), ("a2", "ayy"), ("b", "bz"), ("fail", "a")]
+    {
+        fs::write(root_path.join(name), data).unwrap();
+    }
+    let (a1, a2) = (root_path.join("a1"), root_path.join("a2"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--grouper-cmd"])
+        .arg(&bin)
+        .arg(&root_path);
+    let out = cmd
+        .assert()
+        .success()
+        .stdout(format!("{}\n{}\n\n", a1.display(), a2.display()))
+        .get_output()
+        .stderr
+        .clone();
+    assert!(String::from_utf8(out).unwrap().contains("fail"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["--on-error", "abort", "dups", "--grouper-cmd"])
+        .arg(&bin)
+        .arg(&root_path);
+    cmd.assert().failure();
+}
+
+#[test]
+fn dups_progress() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let tmp = tempfile::tempdir().unwrap();
+    // Slow enough for at least one status line, at the shortest interval.
+    let bin = tmp.path().join("slow-cat");
+    fs::write(&bin, "#!/bin/sh\nsleep 1.5\ncat \"$1\"\n").unwrap();
+    fs::set_permissions(&bin, fs::Permissions::from_mode(0o755)).unwrap();
+    let root_path = tmp.path().join("root");
+    fs::create_dir(&root_path).unwrap();
+    let root_path = root_path.canonicalize().unwrap();
+    fs::write(root_path.join("a1"), "aaa").unwrap();
+    fs::write(root_path.join("a2"), "aaa").unwrap();
+    fs::write(root_path.join("b"), "bbb").unwrap();
+    let (a1, a2) = (root_path.join("a1"), root_path.join("a2"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--progress-interval", "1", "--grouper-cmd"])
+        .arg(&bin)
+        .arg(&root_path);
+    let err = cmd
+        .assert()
+        .success()
+        .stdout(format!("{}\n{}\n\n", a1.display(), a2.display()))
+        .get_output()
+        .stderr
+        .clone();
+    let err = String::from_utf8(err).unwrap();
+    assert!(
+        err.lines().any(|line| {
+            line.strip_prefix('[')
+                .and_then(|line| line.split_once("s] scanned "))
+                .is_some_and(|(secs, rest)| {
+                    secs.parse::<u64>().is_ok()
+                        && rest.contains(" entries, hashed ")
+                })
+        }),
+        "No status line in: {err:?}"
+    );
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--progress-interval", "0"])
+        .arg(&root_path);
+    let err = cmd.assert().failure().get_output().stderr.clone();
+    assert!(
+        String::from_utf8(err)
+            .unwrap()
+            .contains("--progress-interval")
+    );
+}
+
+#[test]
+fn dups_hashed_excludes_cached() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let data = root_path.join("data");
+    fs::create_dir(&data).unwrap();
+    fs::write(data.join("a1"), "aaa").unwrap();
+    fs::write(data.join("a2"), "aaa").unwrap();
+    let checkpoint = root_path.join("checkpoint");
+    let events_path = root_path.join("events.ndjson");
+
+    // Hashed on the first run, then only looked up.
+    for hashed in [6, 0] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--checkpoint"])
+            .arg(&checkpoint)
+            .arg("--emit-events")
+            .arg(&events_path)
+            .arg(&data);
+        cmd.assert().success();
+        let events = fs::read_to_string(&events_path).unwrap();
+        let done: serde_json::Value =
+            serde_json::from_str(events.lines().last().unwrap()).unwrap();
+        assert_eq!(hashed, done["stats"]["hashed"]);
+    }
+}
+
+#[test]
+fn dups_max_hash_size() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let mut differ = "x".repeat(100).into_bytes();
+    differ[30] = b'y';
+    fs::write(root_path.join("big1"), "x".repeat(100)).unwrap();
+    fs::write(root_path.join("big2"), differ).unwrap();
+    fs::write(root_path.join("small1"), "z".repeat(10)).unwrap();
+    fs::write(root_path.join("small2"), "z".repeat(10)).unwrap();
+    let path = |name: &str| root_path.join(name).display().to_string();
+
+    // Hashed, the big files differ.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--sample", "4"]).arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n",
+        path("small1"),
+        path("small2")
+    ));
+
+    // Sampled, they do not, as far as can be told.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--sample", "4", "--max-hash-size", "50"])
+        .args(["--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "group,match,path\n\
+        1,sampled,{}\n1,sampled,{}\n\
+        2,hashed,{}\n2,hashed,{}\n",
+        path("big1"),
+        path("big2"),
+        path("small1"),
+        path("small2"),
+    ));
+}
+
+#[test]
+fn dups_across_mounts_report() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("a1"), "aaa").unwrap();
+    fs::write(root_path.join("a2"), "aaa").unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--across-mounts-report", "--format", "csv"])
+        .arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut lines = out.lines();
+    assert_eq!(Some("group,dedup,path"), lines.next());
+    // Which one depends on the filesystem of the tmp dir, but the whole
+    // group is classified the same.
+    let dedups: HashSet<&str> =
+        lines.map(|line| line.split(',').nth(1).unwrap()).collect();
+    assert!(
+        dedups == HashSet::from(["reflink"])
+            || dedups == HashSet::from(["delete"]),
+        "{dedups:?}"
+    );
+}
+
+#[test]
+fn dups_reflink() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (a1, a2) = (root_path.join("a1"), root_path.join("a2"));
+    fs::write(&a1, "aaa").unwrap();
+    fs::write(&a2, "aaa").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--yes"]).arg(&root_path);
+    cmd.assert().failure();
+
+    // Whether the pair is listed depends on the filesystem of the tmp dir,
+    // but, if it is, it is deduplicated into the second, from the first.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--reflink", "--yes", "--format", "csv"])
+        .arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let listed = format!("source,path\n{},{}\n", a1.display(), a2.display());
+    assert!(out.is_empty() || out == listed, "{out:?}");
+    assert_eq!("aaa", fs::read_to_string(&a1).unwrap());
+    assert_eq!("aaa", fs::read_to_string(&a2).unwrap());
+}
+
+#[test]
+fn dups_emit_events() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let data = root_path.join("data");
+    fs::create_dir(&data).unwrap();
+    let (a1, a2) = (data.join("a1"), data.join(OsStr::from_bytes(b"a2\xff")));
+    fs::write(&a1, "aaa").unwrap();
+    fs::write(&a2, "aaa").unwrap();
+    fs::write(data.join("b"), "bbbb").unwrap();
+    let events_path = root_path.join("events.ndjson");
+    let a2_bytes = a2.as_os_str().as_bytes().to_vec();
+    let (a1, a2) = (a1.display(), a2.display());
+
+    // Report is unaffected.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("dups")
+        .arg("--emit-events")
+        .arg(&events_path)
+        .arg(&data);
+    cmd.assert().success().stdout(format!("{a1}\n{a2}\n\n"));
+
+    let events: Vec<serde_json::Value> = fs::read_to_string(&events_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let named = |name: &str| {
+        events
+            .iter()
+            .filter(|event| event["event"] == name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(3, named("file_found").len());
+    assert!(!named("pass_finished").is_empty());
+    let groups = named("group_finalized");
+    assert_eq!(1, groups.len());
+    assert_eq!(3, groups[0]["size"]);
+    assert_eq!(
+        serde_json::json!([
+            {"path": a1.to_string()},
+            {"path": a2.to_string(), "path_bytes": a2_bytes},
+        ]),
+        groups[0]["members"]
+    );
+    assert!(
+        named("file_found")
+            .iter()
+            .any(|event| event["path_bytes"] == serde_json::json!(a2_bytes))
+    );
+    let done = events.last().unwrap();
+    assert_eq!("done", done["event"]);
+    assert_eq!(1, done["stats"]["groups"]);
+    assert_eq!(2, done["stats"]["files"]);
+}
+
+#[test]
+fn dups_json() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    // Quotes, separators and a newline, to be escaped by JSON alone.
+    let odd = root_path.join("a \"2\",\n'x'");
+    fs::write(root_path.join("a1"), "aaa").unwrap();
+    fs::write(&odd, "aaa").unwrap();
+    fs::write(root_path.join("b"), "bbbb").unwrap();
+    for quote in [&[][..], &["--quote"][..]] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--json"]).args(quote).arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            serde_json::json!([[
+                {"path": odd.to_str().unwrap()},
+                {"path": root_path.join("a1").to_str().unwrap()},
+            ]]),
+            json
+        );
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--json", "--null"]).arg(&root_path);
+    let err = cmd.assert().failure().get_output().stderr.clone();
+    assert!(
+        String::from_utf8(err)
+            .unwrap()
+            .contains("cannot be used with")
+    );
+}
+
+#[test]
+fn dups_exact_anomalies() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let data = root_path.join("data");
+    fs::create_dir(&data).unwrap();
+    let checkpoint = root_path.join("checkpoint");
+    let same = "x".repeat(100);
+    for name in ["a", "b", "c"] {
+        fs::write(data.join(name), &same).unwrap();
+    }
+    let run = || {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--json", "--exact", "--sample", "4"])
+            .arg("--checkpoint")
+            .arg(&checkpoint)
+            .arg(&data);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        serde_json::from_slice::<serde_json::Value>(&out).unwrap()
+    };
+    let path = |name: &str| data.join(name).to_str().unwrap().to_string();
+    assert_eq!(
+        serde_json::json!({
+            "records": [[
+                {"path": path("a")},
+                {"path": path("b")},
+                {"path": path("c")},
+            ]],
+            "anomalies": [],
+        }),
+        run()
+    );
+
+    // Changed between samples, in place and keeping its mtime, so that the
+    // checkpointed hash still passes for it: a collision, as far as the
+    // passes can tell.
+    let c = data.join("c");
+    let mtime = fs::metadata(&c).unwrap().modified().unwrap();
+    fs::write(&c, format!("{}y{}", &same[..50], &same[51..])).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&c)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "records": [[
+                {"path": path("a")},
+                {"path": path("b")},
+            ]],
+            "anomalies": [
+                {"path": path("c"), "reason": "verify-split"},
+            ],
+        }),
+        run()
+    );
+
+    // Without exact certainty, the collision goes unnoticed.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["dups", "--json", "--sample", "4"])
+        .arg("--checkpoint")
+        .arg(&checkpoint)
+        .arg(&data);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(
+        serde_json::json!([[
+            {"path": path("a")},
+            {"path": path("b")},
+            {"path": path("c")},
+        ]]),
+        serde_json::from_slice::<serde_json::Value>(&out).unwrap()
+    );
+}
+
+#[test]
+fn dups_tail_sample() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let data = root_path.join("data");
+    fs::create_dir(&data).unwrap();
+    let same = "x".repeat(100);
+    fs::write(data.join("a1"), &same).unwrap();
+    fs::write(data.join("a2"), &same).unwrap();
+    // Same head and mid samples, differing only in the tail.
+    fs::write(data.join("b"), format!("{}y", &same[1..])).unwrap();
+    let events_path = root_path.join("events.ndjson");
+    let (a1, a2) = (data.join("a1"), data.join("a2"));
+    let (a1, a2) = (a1.display(), a2.display());
+
+    // Passes and bytes hashed: b is dropped by its tail, before hashing,
+    // unless the tail pass is skipped.
+    for (args, passes, hashed) in
+        [(&[][..], 5, 200), (&["--no-tail-sample"][..], 4, 300)]
+    {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["dups", "--sample", "4", "--emit-events"])
+            .arg(&events_path)
+            .args(args)
+            .arg(&data);
+        cmd.assert().success().stdout(format!("{a1}\n{a2}\n\n"));
+        let events: Vec<serde_json::Value> = fs::read_to_string(&events_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let passes_finished = events
+            .iter()
+            .filter(|event| event["event"] == "pass_finished")
+            .count();
+        assert_eq!(passes, passes_finished);
+        let done = events.last().unwrap();
+        assert_eq!(hashed, done["stats"]["hashed"]);
+    }
+}
+
+#[test]
+fn dups_max_memory_spill() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    // Groups of several sizes, so that they are spread across batches, with
+    // a name which is not UTF-8 and a hardlink, to survive the round trip.
+    for (name, data) in [
+        (&b"a1"[..], "a"),
+        (b"a2", "a"),
+        (b"b1", "bb"),
+        (b"b2_\xff", "bb"),
+        (b"c", "ccc"),
+        (b"d1", "dddd"),
+        (b"d2", "dddd"),
+        (b"d3", "dddd"),
+        (b"e1", "eeeee"),
+        (b"e2", "eeeee"),
+    ] {
+        fs::write(root_path.join(OsStr::from_bytes(name)), data).unwrap();
+    }
+    fs::hard_link(root_path.join("e1"), root_path.join("e1_link")).unwrap();
+
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("dups").args(args).arg(&root_path);
+        cmd.assert().success().get_output().clone()
+    };
+    let held = run(&[]);
+    let spilled = run(&["--max-memory", "1KiB"]);
+    assert!(held.stderr.is_empty());
+    assert!(String::from_utf8_lossy(&spilled.stderr).contains("Spilling"));
+    assert_eq!(held.stdout, spilled.stdout);
+    // Of a, b, d and e, each ended by an empty line.
+    let out = String::from_utf8_lossy(&held.stdout);
+    assert_eq!(4, out.matches("\n\n").count());
+    assert!(
+        out.contains(&format!("{}", root_path.join("e1_link").display()))
+    );
+}
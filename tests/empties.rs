@@ -0,0 +1,46 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn empties() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let path = |name: &str| root_path.join(name);
+    fs::create_dir_all(path("empty_dir")).unwrap();
+    fs::create_dir_all(path("full_dir")).unwrap();
+    fs::create_dir_all(path("skipped_only/skipped")).unwrap();
+    fs::write(path("full_dir/empty_file"), "").unwrap();
+    fs::write(path("full_file"), "x").unwrap();
+    let p = |name: &str| path(name).display().to_string();
+
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("empties")
+            .args(args)
+            .args(["--skip-dir", "skipped"])
+            .arg(&root_path);
+        cmd.assert()
+    };
+    run(&[]).success().stdout(format!(
+        "{}\n{}\n{}\n",
+        p("empty_dir"),
+        p("full_dir/empty_file"),
+        p("skipped_only"),
+    ));
+    run(&["--both"]).success().stdout(format!(
+        "{}\n{}\n{}\n",
+        p("empty_dir"),
+        p("full_dir/empty_file"),
+        p("skipped_only"),
+    ));
+    run(&["--files"])
+        .success()
+        .stdout(format!("{}\n", p("full_dir/empty_file")));
+    run(&["--dirs", "-Z"]).success().stdout(format!(
+        "{}\0{}\0",
+        p("empty_dir"),
+        p("skipped_only"),
+    ));
+    run(&["--both", "--files"]).failure();
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "parquet")]
+
+use std::fs;
+
+use arrow_array::{Array, StringArray, UInt64Array};
+use assert_cmd::Command;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+#[test]
+fn export_parquet() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let output = tmp.path().join("meta.parquet");
+    fs::create_dir(&root_path).unwrap();
+    fs::write(root_path.join("file"), "12345").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("export").arg("-o").arg(&output).arg(&root_path);
+    cmd.assert().success();
+
+    let file = fs::File::open(&output).unwrap();
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    let mut rows: Vec<(String, String, u64)> = Vec::new();
+    for batch in reader {
+        let batch = batch.unwrap();
+        let column = |name| batch.column_by_name(name).unwrap().as_any();
+        let paths = column("path").downcast_ref::<StringArray>().unwrap();
+        let types = column("type").downcast_ref::<StringArray>().unwrap();
+        let sizes = column("size").downcast_ref::<UInt64Array>().unwrap();
+        for i in 0..batch.num_rows() {
+            rows.push((
+                paths.value(i).to_string(),
+                types.value(i).to_string(),
+                sizes.value(i),
+            ));
+        }
+    }
+    let file_row = (
+        root_path.join("file").display().to_string(),
+        "regular".to_string(),
+        5,
+    );
+    assert_eq!(2, rows.len());
+    assert!(rows.contains(&file_row));
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+use assert_cmd::Command;
+
+#[test]
+fn hardlinks() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let path = |name: &str| root_path.join(name);
+    fs::write(path("a1"), "a").unwrap();
+    fs::hard_link(path("a1"), path("a2")).unwrap();
+    fs::write(path("b1"), "b").unwrap();
+    fs::hard_link(path("b1"), path("b2")).unwrap();
+    fs::hard_link(path("b1"), path("b3")).unwrap();
+    // Same contents, but not the same file.
+    fs::write(path("c"), "a").unwrap();
+    let p = |name: &str| path(name).display().to_string();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("hardlinks").arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n\n{}\n{}\n{}\n\n",
+        p("a1"),
+        p("a2"),
+        p("b1"),
+        p("b2"),
+        p("b3"),
+    ));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["hardlinks", "--min-links", "3"]).arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "{}\n{}\n{}\n\n",
+        p("b1"),
+        p("b2"),
+        p("b3"),
+    ));
+}
@@ -0,0 +1,18 @@
+use std::{fs, hash::Hasher};
+
+#[test]
+fn xxh_resume_equals_xxh() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("file");
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(&path, &data).unwrap();
+    let whole = fx::hash::xxh(&path, 4096).unwrap();
+    // Heads within and beyond the hasher's internal buffer, and the whole.
+    for head in [0, 1, 255, 256, 8192, data.len()] {
+        let mut hash = twox_hash::XxHash3_64::new();
+        hash.write(&data[..head]);
+        let resumed =
+            fx::hash::xxh_resume(&path, 4096, hash, head as u64).unwrap();
+        assert_eq!(whole, resumed, "head={head}");
+    }
+}
@@ -0,0 +1,141 @@
+use std::{fs, os::unix::fs::symlink};
+
+use assert_cmd::Command;
+
+#[test]
+fn loops_root_is_cyclic_symlink() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let a = tmp.join("a");
+    let b = tmp.join("b");
+    symlink(&b, &a).unwrap();
+    symlink(&a, &b).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg(&a);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n\n", a.display()));
+}
+
+#[test]
+fn loops_root_is_dangling_symlink() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().join("broken");
+    symlink(tmp.path().join("nowhere"), &root_path).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg(&root_path);
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn loops_output_layout() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let a = root_path.join("a");
+    let b = root_path.join("b");
+    symlink(&b, &a).unwrap();
+    symlink(&a, &b).unwrap();
+    let (a, b) = (a.display(), b.display());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg(&root_path);
+    cmd.assert().success().stdout(format!("{a}\n\n{b}\n\n"));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg("-Z").arg(&root_path);
+    cmd.assert().success().stdout(format!("{a}\0\n{b}\0\n"));
+}
+
+#[test]
+fn loops_root_not_canonicalizable() {
+    let tmp = tempfile::tempdir().unwrap();
+    let gone = tmp.path().join("gone");
+    fs::create_dir(&gone).unwrap();
+    // Removed working dir cannot be canonicalized, but can still be read.
+    let bin = assert_cmd::cargo::cargo_bin(env!("CARGO_PKG_NAME"));
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(r#"cd "$1" && rmdir "$1" && exec "$2" loops ."#)
+        .arg("sh")
+        .arg(&gone)
+        .arg(&bin);
+    cmd.assert().success().stdout("");
+}
+
+#[test]
+fn loops_resolve_relative_against() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let root_path = tmp.join("root");
+    let base = tmp.join("base");
+    fs::create_dir(&root_path).unwrap();
+    fs::create_dir(&base).unwrap();
+    // Dangling relative to its parent, but cyclic relative to the base.
+    let link = root_path.join("link");
+    symlink("x", &link).unwrap();
+    symlink(&link, base.join("x")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg(&root_path);
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops")
+        .arg("--resolve-relative-against")
+        .arg(&base)
+        .arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n\n", link.display()));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.current_dir(&base)
+        .arg("loops")
+        .arg("--resolve-relative-against-cwd")
+        .arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("{}\n\n", link.display()));
+}
+
+#[test]
+fn loops_fix() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let root_path = tmp.join("root");
+    let outside = tmp.join("outside");
+    fs::create_dir(&root_path).unwrap();
+    let (a, b, c) = (
+        root_path.join("a"),
+        root_path.join("b"),
+        root_path.join("c"),
+    );
+    symlink(&b, &a).unwrap();
+    symlink(&a, &b).unwrap();
+    // Cycle through a link outside the root, which is left alone.
+    symlink(&outside, &c).unwrap();
+    symlink(&c, &outside).unwrap();
+    let expected = format!("{}\n{}\n", b.display(), c.display());
+
+    // Dry run by default.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["loops", "--fix"]).arg(&root_path);
+    let out = cmd.assert().success().stdout(expected.clone());
+    let stderr = String::from_utf8(out.get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("Nothing removed"));
+    assert!(b.symlink_metadata().is_ok());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["loops", "--fix", "--yes"]).arg(&root_path);
+    cmd.assert().success().stdout(expected);
+    assert!(b.symlink_metadata().is_err());
+    assert!(c.symlink_metadata().is_err());
+    assert!(a.symlink_metadata().is_ok());
+    assert!(outside.symlink_metadata().is_ok());
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("loops").arg(&root_path);
+    cmd.assert().success().stdout("");
+}
@@ -0,0 +1,51 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::Command;
+
+fn set_mtime(path: &Path, secs: u64) {
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+    fs::File::open(path)
+        .unwrap()
+        .set_times(fs::FileTimes::new().set_modified(mtime))
+        .unwrap();
+}
+
+#[test]
+fn mtime_anomalies() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (ok, newer, older) = (
+        root_path.join("ok"),
+        root_path.join("newer"),
+        root_path.join("older"),
+    );
+    for (dir, dir_mtime, file_mtime) in [
+        (&ok, 10_000, 10_100),
+        (&newer, 10_000, 20_000),
+        (&older, 50_000, 10_000),
+    ] {
+        fs::create_dir(dir).unwrap();
+        let file = dir.join("file");
+        fs::write(&file, "").unwrap();
+        set_mtime(&file, file_mtime);
+        set_mtime(dir, dir_mtime);
+    }
+    // As new as its newest entry, so not reported.
+    set_mtime(&root_path, 50_000);
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["mtime-anomalies", "--tolerance", "1h", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "delta_secs,dir_mtime,newest_entry_mtime,path\n\
+        -40000,1970-01-01T13:53:20+00:00,1970-01-01T02:46:40+00:00,{}\n\
+        10000,1970-01-01T02:46:40+00:00,1970-01-01T05:33:20+00:00,{}\n",
+        older.display(),
+        newer.display(),
+    ));
+}
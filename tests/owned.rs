@@ -0,0 +1,34 @@
+use std::{fs, os::unix::fs::MetadataExt};
+
+use assert_cmd::Command;
+
+#[test]
+fn owned() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("a"), "12345").unwrap();
+    fs::write(root_path.join("b"), "123").unwrap();
+    let metas: Vec<fs::Metadata> =
+        [root_path.clone(), root_path.join("a"), root_path.join("b")]
+            .iter()
+            .map(|path| fs::symlink_metadata(path).unwrap())
+            .collect();
+    let (uid, gid) = (metas[0].uid(), metas[0].gid());
+    let size: u64 = metas.iter().map(|meta| meta.size()).sum();
+
+    let run = |by: &str| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["owned", "--format", "csv", "--by", by])
+            .arg(&root_path);
+        cmd.assert()
+    };
+    run("user")
+        .success()
+        .stdout(format!("uid,entries,size\n{uid},3,{size}\n"));
+    run("group")
+        .success()
+        .stdout(format!("gid,entries,size\n{gid},3,{size}\n"));
+    run("both")
+        .success()
+        .stdout(format!("uid,gid,entries,size\n{uid},{gid},3,{size}\n"));
+}
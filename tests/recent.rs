@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::Command;
+
+#[test]
+fn recent_modified_after() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for (name, size, mtime) in
+        [("old", 1, 1000), ("mid", 2, 2000), ("new", 3, 3000)]
+    {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_len(size).unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(mtime);
+        file.set_times(fs::FileTimes::new().set_modified(mtime))
+            .unwrap();
+    }
+    let expected = format!(
+        "mtime,size,path\n\
+        1970-01-01T00:50:00+00:00,3,{}\n\
+        1970-01-01T00:33:20+00:00,2,{}\n",
+        root_path.join("new").display(),
+        root_path.join("mid").display(),
+    );
+    for after in ["@1500", "1970-01-01T00:25:00Z", "1970-01-01T00:25:00"] {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.env("TZ", "UTC")
+            .args(["recent", "--modified-after", after, "--format", "csv"])
+            .arg(&root_path);
+        cmd.assert().success().stdout(expected.clone());
+    }
+}
+
+#[test]
+fn recent_rejects_bad_time() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["recent", "--modified-after", "yesterday", "."]);
+    cmd.assert().failure();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["recent", "--since", "7d", "--since-boot", "."]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn recent_without_cutoff() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for (name, size, mtime) in
+        [("old", 1, 1000), ("new", 3, 3000), ("mid", 2, 2000)]
+    {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_len(size).unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(mtime);
+        file.set_times(fs::FileTimes::new().set_modified(mtime))
+            .unwrap();
+    }
+    let path = |name: &str| root_path.join(name).display().to_string();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["recent", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "mtime,size,path\n\
+        1970-01-01T00:50:00+00:00,3,{}\n\
+        1970-01-01T00:33:20+00:00,2,{}\n\
+        1970-01-01T00:16:40+00:00,1,{}\n",
+        path("new"),
+        path("mid"),
+        path("old"),
+    ));
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["recent", "--lim", "1", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "mtime,size,path\n1970-01-01T00:50:00+00:00,3,{}\n",
+        path("new"),
+    ));
+}
+
+#[test]
+fn recent_since_boot() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let file = root_path.join("file");
+    fs::write(&file, "").unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["recent", "--since-boot", "--format", "csv"])
+        .arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    assert!(
+        String::from_utf8(out)
+            .unwrap()
+            .ends_with(&format!(",0,{}\n", file.display()))
+    );
+}
+
+#[test]
+fn recent_by_atime_since() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let now = SystemTime::now();
+    let day = Duration::from_secs(24 * 60 * 60);
+    // Accessed recently, but modified long ago, and vice versa.
+    for (name, accessed, modified) in [
+        ("read", now - day, now - 30 * day),
+        ("written", now - 30 * day, now - day),
+    ] {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_times(
+            fs::FileTimes::new()
+                .set_accessed(accessed)
+                .set_modified(modified),
+        )
+        .unwrap();
+    }
+    let run = |by: &str| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["recent", "--by", by, "--since", "7d", "--format", "csv"])
+            .arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    let out = run("atime");
+    assert!(out.starts_with("atime,size,path\n"), "{out}");
+    assert!(
+        out.ends_with(&format!(",0,{}\n", root_path.join("read").display()))
+    );
+    assert_eq!(2, out.lines().count());
+    let out = run("mtime");
+    assert!(out.starts_with("mtime,size,path\n"), "{out}");
+    assert!(
+        out.ends_with(&format!(
+            ",0,{}\n",
+            root_path.join("written").display()
+        ))
+    );
+    assert_eq!(2, out.lines().count());
+}
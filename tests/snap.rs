@@ -0,0 +1,142 @@
+#![cfg(feature = "snap")]
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::symlink},
+    path::PathBuf,
+};
+
+use assert_cmd::Command;
+use fx::data::{FileType, Meta};
+
+#[test]
+fn snap_round_trip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let output = tmp.path().join("snapshot.jsonl");
+    fs::create_dir(&root_path).unwrap();
+    fs::write(root_path.join("file"), "12345").unwrap();
+    // Not valid UTF-8, yet must survive.
+    let odd = root_path.join(OsStr::from_bytes(b"odd\xff"));
+    fs::write(&odd, "x").unwrap();
+    symlink("file", root_path.join("link")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("snap").arg("--out").arg(&output).arg(&root_path);
+    cmd.assert().success().stdout("");
+
+    let (header, entries) = fx::cmd::snap::read(&output).unwrap();
+    assert_eq!(root_path, header.root);
+    assert!(header.time > 0);
+    let snapped: BTreeMap<PathBuf, Meta> = entries
+        .map(|meta| meta.unwrap())
+        .map(|meta| (meta.path.clone(), meta))
+        .collect();
+    let found: BTreeMap<PathBuf, Meta> = fx::data::find(&root_path)
+        .unwrap()
+        .map(|meta| meta.unwrap())
+        .map(|meta| (meta.path.clone(), meta))
+        .collect();
+    assert_eq!(
+        vec![
+            root_path.clone(),
+            root_path.join("file"),
+            root_path.join("link"),
+            odd.clone(),
+        ],
+        snapped.keys().cloned().collect::<Vec<_>>()
+    );
+    for (path, a) in &snapped {
+        let b = &found[path];
+        assert_eq!(a.typ.name(), b.typ.name());
+        assert_eq!(
+            (a.size, a.mode, a.perms, a.uid, a.gid),
+            (b.size, b.mode, b.perms, b.uid, b.gid)
+        );
+        assert_eq!(
+            (a.dev, a.ino, a.nlink, a.rdev, a.blksize, a.blocks),
+            (b.dev, b.ino, b.nlink, b.rdev, b.blksize, b.blocks)
+        );
+        assert_eq!((a.mtime, a.ctime), (b.mtime, b.ctime));
+    }
+    match &snapped[&root_path.join("link")].typ {
+        FileType::Symlink { dst } => assert_eq!(&PathBuf::from("file"), dst),
+        typ => panic!("Not a symlink: {typ:?}"),
+    }
+}
+
+#[test]
+fn snap_compressed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    fs::create_dir(&root_path).unwrap();
+    fs::write(root_path.join("file"), "12345").unwrap();
+    fs::write(root_path.join(OsStr::from_bytes(b"odd\xff")), "x").unwrap();
+
+    let paths = |output: &PathBuf| {
+        let (header, entries) = fx::cmd::snap::read(output).unwrap();
+        assert_eq!(root_path, header.root);
+        entries
+            .map(|meta| meta.unwrap().path)
+            .collect::<Vec<PathBuf>>()
+    };
+    let mut snapped = Vec::new();
+    for (compress, magic) in [
+        ("none", &b"{"[..]),
+        ("gzip", &[0x1f, 0x8b][..]),
+        ("zstd", &[0x28, 0xb5, 0x2f, 0xfd][..]),
+    ] {
+        let output = tmp.path().join(format!("snapshot.{compress}"));
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["snap", "--compress", compress, "--out"])
+            .arg(&output)
+            .arg(&root_path);
+        cmd.assert().success().stdout("");
+        assert!(fs::read(&output).unwrap().starts_with(magic), "{compress}");
+        snapped.push(paths(&output));
+    }
+    assert_eq!(3, snapped[0].len());
+    assert_eq!(snapped[0], snapped[1]);
+    assert_eq!(snapped[0], snapped[2]);
+}
+
+#[test]
+fn snap_version() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let output = tmp.path().join("snapshot.jsonl");
+    fs::create_dir(&root_path).unwrap();
+    fs::write(root_path.join("file"), "12345").unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("snap").arg("--out").arg(&output).arg(&root_path);
+    cmd.assert().success();
+    let (header, _) = fx::cmd::snap::read(&output).unwrap();
+    assert_eq!(fx::cmd::snap::VERSION, header.version);
+
+    let snapshot = fs::read_to_string(&output).unwrap();
+    let (header, entries) = snapshot.split_once('\n').unwrap();
+    let mut header: serde_json::Value = serde_json::from_str(header).unwrap();
+    let with_header = |header: &serde_json::Value| {
+        let path = tmp.path().join("edited.jsonl");
+        fs::write(&path, format!("{header}\n{entries}")).unwrap();
+        path
+    };
+
+    // Written before versions were recorded.
+    header.as_object_mut().unwrap().remove("version");
+    let (header_0, entries_0) =
+        fx::cmd::snap::read(&with_header(&header)).unwrap();
+    assert_eq!(0, header_0.version);
+    let entries_0: Vec<_> = entries_0.map(|meta| meta.unwrap()).collect();
+    assert_eq!(2, entries_0.len());
+
+    header["version"] = serde_json::json!(fx::cmd::snap::VERSION + 1);
+    let newer = with_header(&header);
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("diff").arg(&output).arg(&newer);
+    let out = cmd.assert().failure().get_output().stderr.clone();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("newer than 1"), "{out}");
+}
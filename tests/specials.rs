@@ -0,0 +1,26 @@
+use std::{fs, os::unix::net::UnixListener};
+
+use assert_cmd::Command;
+
+#[test]
+fn specials() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("file"), "").unwrap();
+    let sock = root_path.join("sock");
+    let _listener = UnixListener::bind(&sock).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["specials", "--format", "csv"]).arg(&root_path);
+    cmd.assert()
+        .success()
+        .stdout(format!("type,device,path\nsock,-,{}\n", sock.display()));
+
+    // Devices with their numbers, as in "ls -l /dev/null".
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["specials", "--format", "csv", "-"])
+        .write_stdin(format!("/dev/null\n{}\n", root_path.display()));
+    cmd.assert()
+        .success()
+        .stdout("type,device,path\ndev_char,1:3,/dev/null\n");
+}
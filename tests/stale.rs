@@ -0,0 +1,80 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::Command;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// As reported.
+fn fmt_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    fx::time::fmt_local(i64::try_from(secs).unwrap()).unwrap()
+}
+
+#[test]
+fn stale_older_than() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let now = SystemTime::now();
+    let atime = |idle_days| now - Duration::from_secs(idle_days * DAY);
+    for (name, size, idle_days) in
+        [("small", 1, 400), ("big", 3, 500), ("fresh", 2, 10)]
+    {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_len(size).unwrap();
+        file.set_times(fs::FileTimes::new().set_accessed(atime(idle_days)))
+            .unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["stale", "--older-than", "365d", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "size,idle_days,atime,path\n3,500,{},{}\n1,400,{},{}\n",
+        fmt_time(atime(500)),
+        root_path.join("big").display(),
+        fmt_time(atime(400)),
+        root_path.join("small").display(),
+    ));
+}
+
+#[test]
+fn stale_older_than_invalid() {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["stale", "--older-than", "365", "."]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn old_by_mtime() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let now = SystemTime::now();
+    let long_ago = now - Duration::from_secs(1000 * DAY);
+    // Modified long ago, but read recently, and vice versa.
+    for (name, accessed, modified) in
+        [("written", now, long_ago), ("read", long_ago, now)]
+    {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_times(
+            fs::FileTimes::new()
+                .set_accessed(accessed)
+                .set_modified(modified),
+        )
+        .unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["old", "--since", "2y", "--by", "mtime", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "size,idle_days,mtime,path\n0,1000,{},{}\n",
+        fmt_time(long_ago),
+        root_path.join("written").display(),
+    ));
+}
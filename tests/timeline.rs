@@ -0,0 +1,32 @@
+use std::{
+    fs,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use assert_cmd::Command;
+
+#[test]
+fn timeline_by_month() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for (name, size, mtime) in [
+        ("a", 1, 1_709_251_200), // 2024-03-01
+        ("b", 2, 1_711_843_200), // 2024-03-31
+        ("c", 4, 1_717_200_000), // 2024-06-01
+    ] {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_len(size).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime))
+            .unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["timeline", "--bucket", "month", "--bars", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "bucket,files,size,bar\n2024-03,2,3,{}\n2024-06,1,4,{}\n",
+        "#".repeat(30),
+        "#".repeat(40),
+    ));
+}
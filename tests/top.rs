@@ -0,0 +1,818 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    os::unix::{ffi::OsStrExt, fs::symlink, net::UnixListener},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use assert_cmd::Command;
+
+#[test]
+fn top_root_is_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("file");
+    fs::write(&root_path, "12345").unwrap();
+    let row_expected = format!("5 {}", root_path.display());
+
+    assert_eq!(vec![row_expected.clone()], rows(&["top"], &root_path));
+    assert_eq!(vec![row_expected], rows(&["top", "--files"], &root_path));
+}
+
+#[test]
+fn top_files_notes_sparse() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let sparse = root_path.join("sparse");
+    let dense = root_path.join("dense");
+    fs::File::create(&sparse).unwrap().set_len(1 << 30).unwrap();
+    fs::write(&dense, "12345").unwrap();
+    assert_eq!(
+        vec![
+            format!("5 {}", dense.display()),
+            format!("{} {} sparse: 0 on disk", 1 << 30, sparse.display()),
+        ],
+        rows(&["top", "--files"], &root_path)
+    );
+}
+
+#[test]
+fn top_files_sort_by_mtime() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let now = SystemTime::now();
+    for (name, size, age) in
+        [("old", 3, 300), ("new", 1, 100), ("mid", 2, 200)]
+    {
+        let file = fs::File::create(root_path.join(name)).unwrap();
+        file.set_len(size).unwrap();
+        file.set_modified(now - Duration::from_secs(age)).unwrap();
+    }
+    let rows_expected: Vec<String> = [(3, "old"), (2, "mid"), (1, "new")]
+        .iter()
+        .map(|(size, name)| {
+            format!("{size} {}", root_path.join(name).display())
+        })
+        .collect();
+    assert_eq!(
+        rows_expected,
+        rows(&["top", "--files", "--sort-by", "mtime"], &root_path)
+    );
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--sort-by", "mtime"]).arg(&root_path);
+    cmd.assert().failure();
+}
+
+#[test]
+fn top_format_csv() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let file = root_path.join("a, \"quoted\" name");
+    fs::write(&file, "12345").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--files", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "size,path\n5,\"{}\"\n",
+        file.display().to_string().replace('"', "\"\"")
+    ));
+}
+
+#[test]
+fn top_stats() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("file"), "12345").unwrap();
+    symlink(root_path.join("file"), root_path.join("link")).unwrap();
+    let _sock = UnixListener::bind(root_path.join("sock")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--stats"]).arg(&root_path);
+    let out = cmd.assert().success().get_output().stderr.clone();
+    let out = String::from_utf8(out).unwrap();
+    for line in ["regular: 1", "directory: 1", "symlink: 1", "sock: 1"] {
+        assert!(out.lines().any(|l| l == line), "{line:?} not in {out:?}");
+    }
+}
+
+#[test]
+fn top_show_entry_count() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let wide = root_path.join("wide");
+    fs::create_dir(&wide).unwrap();
+    for name in ["a", "b", "c"] {
+        fs::write(wide.join(name), "1").unwrap();
+    }
+    fs::create_dir(wide.join("empty")).unwrap();
+    fs::write(root_path.join("d"), "1").unwrap();
+    assert_eq!(
+        vec![
+            format!("3 4 {}", wide.display()),
+            format!("4 2 {}", root_path.display()),
+        ],
+        rows(&["top", "--show-entry-count"], &root_path)
+    );
+}
+
+#[test]
+fn top_root_inclusion() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let sub = root_path.join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("a"), "12").unwrap();
+    fs::write(root_path.join("b"), "1").unwrap();
+    let sub_row = format!("2 {}", sub.display());
+    let root_row = format!("3 {}", root_path.display());
+
+    // Included by default.
+    assert_eq!(vec![sub_row.clone(), root_row], rows(&["top"], &root_path));
+    assert_eq!(vec![sub_row], rows(&["top", "--exclude-root"], &root_path));
+}
+
+#[test]
+fn top_summarize() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let sub = root_path.join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(sub.join("a"), "12").unwrap();
+    fs::write(root_path.join("b"), "1").unwrap();
+    assert_eq!(
+        vec![format!("3 {}", root_path.display())],
+        rows(&["top", "-s"], &root_path)
+    );
+}
+
+#[test]
+fn top_max_memory() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for (dir, files) in [("a", 3), ("b", 5)] {
+        let dir = root_path.join(dir);
+        fs::create_dir(&dir).unwrap();
+        for i in 1..=files {
+            fs::write(dir.join(i.to_string()), "x".repeat(i)).unwrap();
+        }
+    }
+    // Budget so small, that the fallback strategy is used from the start.
+    for args in [&["top"][..], &["top", "--files", "--lim", "3"][..]] {
+        let budgeted = [args, &["--max-memory", "1"]].concat();
+        assert_eq!(rows(args, &root_path), rows(&budgeted, &root_path));
+    }
+}
+
+#[test]
+fn top_json_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (a, b) = (root_path.join("a"), root_path.join("b"));
+    let a_c = a.join("c");
+    fs::create_dir_all(&a_c).unwrap();
+    fs::create_dir(&b).unwrap();
+    fs::write(a_c.join("f"), "123").unwrap();
+    fs::write(b.join("f"), "1234").unwrap();
+    fs::write(root_path.join("f"), "1").unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--json-tree"]).arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let tree: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let node = |path: &Path, size: u64, children: Vec<serde_json::Value>| {
+        serde_json::json!({
+            "path": path.to_str().unwrap(),
+            "size": size,
+            "children": children,
+        })
+    };
+    let expected = node(
+        &root_path,
+        8,
+        vec![
+            node(&b, 4, vec![]),
+            node(&a, 3, vec![node(&a_c, 3, vec![])]),
+        ],
+    );
+    assert_eq!(expected, tree);
+}
+
+/// Table rows, without the header, with columns separated by one space.
+fn rows(args: &[&str], root_path: &Path) -> Vec<String> {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(args).arg(root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .collect()
+}
+
+#[test]
+fn top_paths_from_stdin() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (a, b, c) = (
+        root_path.join("a"),
+        root_path.join("b"),
+        root_path.join("c"),
+    );
+    fs::write(&a, "1").unwrap();
+    fs::write(&b, "12").unwrap();
+    fs::write(&c, "123").unwrap();
+    let rows = |args: &[&str], stdin: String| -> Vec<String> {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(args).arg("-").write_stdin(stdin);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| {
+                line.split_whitespace().collect::<Vec<&str>>().join(" ")
+            })
+            .collect()
+    };
+    let (a, c) = (a.display(), c.display());
+    let expected = vec![format!("1 {a}"), format!("3 {c}")];
+
+    // Only the listed, whether separated by linefeeds or nulls.
+    let listed = format!("{a}\n{c}\n");
+    assert_eq!(expected, rows(&["top", "--files"], listed));
+    let listed = format!("{a}\0{c}\0");
+    assert_eq!(expected, rows(&["top", "--files"], listed));
+    let listed = format!("{a}\n{c}\n");
+    assert_eq!(vec!["4 -".to_string()], rows(&["top", "-s"], listed));
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "-"]).write_stdin(format!("{a}\n"));
+    cmd.assert().failure();
+}
+
+#[test]
+fn top_follow() {
+    let tmp = tempfile::tempdir().unwrap();
+    let tmp = tmp.path().canonicalize().unwrap();
+    let root_path = tmp.join("root");
+    let outside = tmp.join("outside");
+    fs::create_dir(&root_path).unwrap();
+    fs::create_dir(&outside).unwrap();
+    fs::write(outside.join("f"), "1234").unwrap();
+    fs::write(root_path.join("g"), "1").unwrap();
+    // Two links to the same, one of them also a cycle back to root.
+    symlink(&outside, root_path.join("a")).unwrap();
+    symlink(&outside, root_path.join("b")).unwrap();
+    symlink(&root_path, root_path.join("c")).unwrap();
+
+    let root = root_path.display();
+    assert_eq!(vec![format!("1 {root}")], rows(&["top"], &root_path));
+    let rows = rows(&["top", "--follow"], &root_path);
+    assert_eq!(2, rows.len());
+    assert_eq!(format!("5 {root}"), rows[1]);
+    let linked = [format!("4 {root}/a"), format!("4 {root}/b")];
+    assert!(linked.contains(&rows[0]), "{rows:?}");
+}
+
+#[test]
+fn top_script_friendly() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("a"), "x".repeat(2048)).unwrap();
+    let root = root_path.display();
+    let out = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("top").args(args).arg(&root_path);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(
+        format!("2048 | {root}\n"),
+        out(&["--format", "raw", "--bytes", "--field-separator", " | "])
+    );
+    assert_eq!(
+        format!("2048,{root}\n"),
+        out(&["--format", "csv", "--no-header"])
+    );
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--bytes", "-H"]).arg(&root_path);
+    cmd.assert().failure();
+}
+
+#[test]
+fn top_compare_apparent_vs_disk() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let sub = root_path.join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::File::create(sub.join("sparse"))
+        .unwrap()
+        .set_len(1 << 30)
+        .unwrap();
+    let args = ["top", "--compare-apparent-vs-disk", "--format", "csv"];
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(args).arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let out = String::from_utf8(out).unwrap();
+    let mut lines: Vec<&str> = out.lines().collect();
+    // Equal sizes, so in no particular order.
+    lines[1..].sort();
+    let size = 1 << 30;
+    assert_eq!(
+        vec![
+            "apparent,disk,ratio,path".to_string(),
+            format!("{size},0,0.00,{}", root_path.display()),
+            format!("{size},0,0.00,{}", sub.display()),
+        ],
+        lines
+    );
+}
+
+#[test]
+fn top_warn_slow_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::write(root_path.join("file"), "1").unwrap();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(args).arg("top").arg(&root_path);
+        let out = cmd.assert().success().get_output().stderr.clone();
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!("", run(&[]));
+    // Every operation takes longer than no time at all.
+    let warnings = run(&["--warn-slow-paths", "0"]);
+    assert!(
+        warnings
+            .contains(&format!("Warning: read_dir of path={:?}", root_path))
+    );
+}
+
+#[test]
+fn top_on_error() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let file = root_path.join("file");
+    fs::write(&file, "1").unwrap();
+    let missing = root_path.join("missing");
+    let listed = format!("{}\n{}\n", file.display(), missing.display());
+    let run = |policy: &str| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["--on-error", policy, "top", "--files", "-"])
+            .write_stdin(listed.clone());
+        cmd.assert()
+    };
+
+    let out = run("skip").success().get_output().clone();
+    assert!(String::from_utf8(out.stdout).unwrap().contains("file"));
+    assert!(out.stderr.is_empty());
+
+    let out = run("warn").success().get_output().clone();
+    assert!(String::from_utf8(out.stdout).unwrap().contains("file"));
+    assert!(String::from_utf8(out.stderr).unwrap().contains("missing"));
+
+    run("abort").failure();
+}
+
+#[test]
+fn top_archive() {
+    use std::io::Write;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let entries = [("d/a", 1), ("d/b", 2), ("c", 4)];
+
+    let tgz = root_path.join("x.tar.gz");
+    let encoder = flate2::write::GzEncoder::new(
+        fs::File::create(&tgz).unwrap(),
+        flate2::Compression::default(),
+    );
+    let mut builder = tar::Builder::new(encoder);
+    for (name, size) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let data = vec![0u8; size as usize];
+        builder.append_data(&mut header, name, &data[..]).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap();
+
+    let zip = root_path.join("x.zip");
+    let mut writer = zip::ZipWriter::new(fs::File::create(&zip).unwrap());
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Stored);
+    for (name, size) in entries {
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&vec![0u8; size as usize]).unwrap();
+    }
+    writer.finish().unwrap();
+
+    for archive in [tgz, zip] {
+        let a = archive.display();
+        assert_eq!(
+            vec![format!("3 {a}/d"), format!("7 {a}")],
+            rows(&["top", "--archive"], &archive)
+        );
+        assert_eq!(
+            vec![
+                format!("1 {a}/d/a"),
+                format!("2 {a}/d/b"),
+                format!("4 {a}/c")
+            ],
+            rows(&["top", "--archive", "--files"], &archive)
+        );
+    }
+
+    // Not an archive.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--archive"]).arg(&root_path);
+    cmd.assert().failure();
+}
+
+#[test]
+fn top_trim_prefix() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::create_dir(root_path.join("d")).unwrap();
+    fs::write(root_path.join("d").join("a"), "1").unwrap();
+    let prefix = format!("{}/", root_path.display());
+    assert_eq!(
+        vec!["1 d/a".to_string()],
+        rows(&["--trim-prefix", &prefix, "top", "--files"], &root_path)
+    );
+    // Paths without the prefix, and the prefix itself, are left as they are.
+    let root = root_path.display();
+    assert_eq!(
+        vec![format!("1 {root}/d/a")],
+        rows(&["--trim-prefix", "/nope/", "top", "--files"], &root_path)
+    );
+    // Equal sizes, so in no particular order.
+    let mut dirs =
+        rows(&["--trim-prefix", &format!("{root}/"), "top"], &root_path);
+    dirs.sort();
+    assert_eq!(vec![format!("1 {root}"), "1 d".to_string()], dirs);
+}
+
+#[test]
+fn top_ties_broken_by_path() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    for name in ["a", "b", "c", "d", "e"] {
+        let dir = root_path.join(name);
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file"), "1").unwrap();
+    }
+    let root = root_path.display();
+    for _ in 0..5 {
+        assert_eq!(
+            vec![format!("1 {root}/d"), format!("1 {root}/e")],
+            rows(&["top", "--exclude-root", "--lim", "2"], &root_path)
+        );
+    }
+}
+
+#[test]
+fn top_accumulate_hidden_as_one() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let write = |dir: &str, size: usize| {
+        let dir = root_path.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "x".repeat(size)).unwrap();
+    };
+    write(".cache/deep", 1);
+    write(".config", 2);
+    write("visible", 10);
+    write("visible/.git", 20);
+    let root = root_path.display();
+    assert_eq!(
+        vec![
+            format!("3 {root}/.hidden"),
+            format!("20 {root}/visible/.hidden"),
+            format!("30 {root}/visible"),
+            format!("33 {root}"),
+        ],
+        rows(&["top", "--accumulate-hidden-as-one"], &root_path)
+    );
+}
+
+#[test]
+fn top_max_depth() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let write = |dir: &str, size: usize| {
+        let dir = root_path.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "x".repeat(size)).unwrap();
+    };
+    write("a/b/c", 4);
+    write("a", 1);
+    write("d", 2);
+    let root = root_path.display();
+    assert_eq!(
+        vec![
+            format!("2 {root}/d"),
+            format!("5 {root}/a"),
+            format!("7 {root}")
+        ],
+        rows(&["top", "--max-depth", "1"], &root_path)
+    );
+    assert_eq!(
+        vec![format!("7 {root}")],
+        rows(&["top", "--max-depth", "0"], &root_path)
+    );
+}
+
+#[test]
+fn top_hide_matching() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let write = |dir: &str, size: usize| {
+        let dir = root_path.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "x".repeat(size)).unwrap();
+    };
+    write("a/.git", 4);
+    write("a", 1);
+    let root = root_path.display();
+    assert_eq!(
+        vec![format!("5 {root}"), format!("5 {root}/a")],
+        rows(&["top", "--hide-matching", r"/\.git(/|$)"], &root_path)
+    );
+    assert_eq!(
+        vec![format!("1 {root}/a/file")],
+        rows(
+            &["top", "--files", "--hide-matching", r"\.git/"],
+            &root_path
+        )
+    );
+}
+
+#[test]
+fn top_only_leaves() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let write = |dir: &str, size: usize| {
+        let dir = root_path.join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("file"), "x".repeat(size)).unwrap();
+    };
+    write("a/b", 4);
+    write("a", 1);
+    write("c", 2);
+    let root = root_path.display();
+    assert_eq!(
+        vec![format!("2 {root}/c"), format!("4 {root}/a/b")],
+        rows(&["top", "--only-leaves"], &root_path)
+    );
+}
+
+#[test]
+fn top_drill() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::create_dir_all(root_path.join("a/x")).unwrap();
+    fs::create_dir(root_path.join("b")).unwrap();
+    fs::write(root_path.join("a/big"), "x".repeat(5)).unwrap();
+    fs::write(root_path.join("a/x/medium"), "x".repeat(3)).unwrap();
+    fs::write(root_path.join("b/small"), "x".repeat(2)).unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--drill", "2", "--lim", "2", "--format", "csv"])
+        .arg(&root_path);
+    let root = root_path.display();
+    cmd.assert().success().stdout(format!(
+        "group,size,path\n\
+        1,8,{root}/a\n\
+        1,5,{root}/a/big\n\
+        1,3,{root}/a/x/medium\n\
+        2,10,{root}\n\
+        2,5,{root}/a/big\n\
+        2,3,{root}/a/x/medium\n"
+    ));
+}
+
+#[test]
+fn top_deep_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let levels = || std::iter::repeat_n("d", 100).collect::<PathBuf>();
+    let mid = root_path.join(levels());
+    let deep = mid.join(levels());
+    fs::create_dir_all(&deep).unwrap();
+    fs::write(mid.join("file"), "x").unwrap();
+    fs::write(deep.join("file"), "xx").unwrap();
+    assert_eq!(
+        vec![format!("3 {}", root_path.display())],
+        rows(&["top", "--max-depth", "0"], &root_path)
+    );
+    assert_eq!(
+        vec![format!("2 {}", deep.display())],
+        rows(&["top", "--only-leaves"], &root_path)
+    );
+}
+
+#[test]
+fn top_sparse_summary() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    fs::File::create(root_path.join("sparse"))
+        .unwrap()
+        .set_len(1 << 30)
+        .unwrap();
+    // Dense, so no holes, whatever the block size.
+    fs::write(root_path.join("dense"), vec![1u8; 8192]).unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--sparse-summary", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().success().stdout(format!(
+        "apparent,holes,path\n{},{},{}\n",
+        (1 << 30) + 8192,
+        1 << 30,
+        root_path.display()
+    ));
+}
+
+#[test]
+fn top_hardlink_attribution() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let (b, c) = (root_path.join("b"), root_path.join("c"));
+    fs::create_dir(&b).unwrap();
+    fs::create_dir(&c).unwrap();
+    let (x, y, z) = (b.join("x"), c.join("y"), c.join("z"));
+    fs::write(&x, "123456789").unwrap();
+    fs::hard_link(&x, &y).unwrap();
+    fs::hard_link(&x, &z).unwrap();
+    let (x, y, z) = (x.display(), y.display(), z.display());
+    let files = |policy: &str| {
+        rows(
+            &[
+                "top",
+                "--files",
+                &format!("--hardlink-attribution={policy}"),
+            ],
+            &root_path,
+        )
+    };
+
+    assert_eq!(
+        vec![format!("9 {x}"), format!("9 {y}"), format!("9 {z}")],
+        rows(&["top", "--files"], &root_path)
+    );
+    assert_eq!(vec![format!("9 {x}")], files("first"));
+    assert_eq!(
+        vec![format!("9 {x}")],
+        rows(&["top", "--files", "--hardlink-attribution"], &root_path)
+    );
+    assert_eq!(
+        vec![format!("3 {x}"), format!("3 {y}"), format!("3 {z}")],
+        files("all-split")
+    );
+    assert_eq!(vec![format!("9 {y}")], files("largest-dir"));
+
+    let root = root_path.display();
+    assert_eq!(
+        vec![
+            format!("3 {root}/b"),
+            format!("6 {root}/c"),
+            format!("9 {root}")
+        ],
+        rows(&["top", "--hardlink-attribution=all-split"], &root_path)
+    );
+}
+
+#[test]
+fn top_json() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap();
+    let odd = root_path.join(OsStr::from_bytes(b"odd\xff"));
+    fs::write(root_path.join("plain"), "12345").unwrap();
+    fs::write(&odd, "1234567").unwrap();
+
+    // Sizes in bytes, even if asked to be human-readable.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--files", "--json", "-H"]).arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(
+        serde_json::json!([
+            {"size": 5, "path": root_path.join("plain").to_str().unwrap()},
+            {
+                "size": 7,
+                "path": odd.to_string_lossy(),
+                "path_bytes": odd.as_os_str().as_bytes(),
+            },
+        ]),
+        json
+    );
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--json", "--format", "csv"])
+        .arg(&root_path);
+    cmd.assert().failure();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--json", "--json-tree"]).arg(&root_path);
+    cmd.assert().failure();
+
+    // Exact bytes in the tree too.
+    let odd_dir = root_path.join(OsStr::from_bytes(b"dir\xff"));
+    fs::create_dir(&odd_dir).unwrap();
+    fs::write(odd_dir.join("f"), "12").unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--json-tree"]).arg(&root_path);
+    let out = cmd.assert().success().get_output().stdout.clone();
+    let tree: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "path": root_path.to_str().unwrap(),
+            "size": 14,
+            "children": [{
+                "path": odd_dir.to_string_lossy(),
+                "path_bytes": odd_dir.as_os_str().as_bytes(),
+                "size": 2,
+                "children": [],
+            }],
+        }),
+        tree
+    );
+}
+
+#[cfg(feature = "snap")]
+#[test]
+fn top_rate() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root_path = tmp.path().canonicalize().unwrap().join("root");
+    let dir = |name: &str| root_path.join(name);
+    for name in ["growing", "shrinking", "steady"] {
+        fs::create_dir_all(dir(name)).unwrap();
+    }
+    fs::write(dir("growing").join("f"), vec![0; 1000]).unwrap();
+    fs::write(dir("shrinking").join("f"), vec![0; 3000]).unwrap();
+    fs::write(dir("steady").join("f"), vec![0; 500]).unwrap();
+    // Times in the headers are made exactly 2 days apart.
+    let snap = |name: &str, time: i64| {
+        let output = tmp.path().join(name);
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.arg("snap").arg("--out").arg(&output).arg(&root_path);
+        cmd.assert().success();
+        let snapshot = fs::read_to_string(&output).unwrap();
+        let (header, entries) = snapshot.split_once('\n').unwrap();
+        let mut header: serde_json::Value =
+            serde_json::from_str(header).unwrap();
+        header["time"] = serde_json::json!(time);
+        fs::write(&output, format!("{header}\n{entries}")).unwrap();
+        output
+    };
+    let from = snap("from", 1_000_000);
+    fs::write(dir("growing").join("f"), vec![0; 3000]).unwrap();
+    fs::write(dir("shrinking").join("f"), vec![0; 2000]).unwrap();
+    fs::write(dir("growing").join("new"), vec![0; 2000]).unwrap();
+    let to = snap("to", 1_000_000 + 2 * 24 * 60 * 60);
+
+    let rate = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+        cmd.args(["top", "--rate", "--format", "csv"])
+            .args(args)
+            .arg("--from")
+            .arg(&from)
+            .arg("--to")
+            .arg(&to);
+        let out = cmd.assert().success().get_output().stdout.clone();
+        String::from_utf8(out).unwrap()
+    };
+    // Fastest last, as largest are.
+    assert_eq!(
+        format!(
+            "rate,size,path\n\
+            -500,2000,{}\n\
+            0,500,{}\n\
+            1500,7500,{}\n\
+            2000,5000,{}\n",
+            dir("shrinking").display(),
+            dir("steady").display(),
+            root_path.display(),
+            dir("growing").display(),
+        ),
+        rate(&[])
+    );
+    assert_eq!(
+        format!("rate,size,path\n2000,5000,{}\n", dir("growing").display()),
+        rate(&["--exclude-root", "--lim", "1"])
+    );
+    // Backwards in time.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--rate", "--from"])
+        .arg(&to)
+        .arg("--to")
+        .arg(&from);
+    cmd.assert().failure();
+    // Needs both snapshots.
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.args(["top", "--rate", "--from"]).arg(&from);
+    cmd.assert().failure();
+}
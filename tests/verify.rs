@@ -0,0 +1,82 @@
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt};
+
+use assert_cmd::Command;
+
+// sha256 of "foo\n":
+const FOO: &str =
+    "b5bb9d8014a0f9b1d61e21e796d78dccdf1352f23cd32812f4850b878ae4944c";
+
+#[test]
+fn verify_ok() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("foo"), "foo\n").unwrap();
+    let manifest = tmp.path().join("SHA256SUMS");
+    fs::write(&manifest, format!("{FOO}  foo\n")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("verify").arg("-m").arg(&manifest).arg(tmp.path());
+    cmd.assert().success().stdout("foo: OK\n");
+}
+
+#[test]
+fn verify_failed_missing_extra() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("root");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("changed"), "bar\n").unwrap();
+    fs::write(root.join("extra"), "foo\n").unwrap();
+    let manifest = tmp.path().join("SHA256SUMS");
+    fs::write(&manifest, format!("{FOO}  changed\n{FOO} *gone\n")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("verify")
+        .arg("--extra")
+        .arg("-m")
+        .arg(&manifest)
+        .arg(&root);
+    cmd.assert()
+        .failure()
+        .stdout("changed: FAILED\ngone: MISSING\nextra: EXTRA\n");
+}
+
+#[test]
+fn verify_non_utf8_and_escaped_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("root");
+    fs::create_dir(&root).unwrap();
+    let non_utf8 = OsStr::from_bytes(b"x\xff");
+    fs::write(root.join(non_utf8), "foo\n").unwrap();
+    fs::write(root.join("a\\b\nc"), "foo\n").unwrap();
+    let manifest = tmp.path().join("SHA256SUMS");
+    let mut data = format!("{FOO}  ").into_bytes();
+    data.extend_from_slice(non_utf8.as_bytes());
+    data.extend_from_slice(format!("\n\\{FOO}  a\\\\b\\nc\n").as_bytes());
+    fs::write(&manifest, data).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("verify").arg("-m").arg(&manifest).arg(&root);
+    cmd.assert()
+        .success()
+        .stdout("x\u{fffd}: OK\na\\b\nc: OK\n");
+}
+
+#[test]
+fn verify_algo_by_name_not_substring() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("foo"), "foo\n").unwrap();
+    // Has "b3" in it, but is not named for BLAKE3, so SHA2-256 is inferred
+    // from the length of the hash.
+    let manifest = tmp.path().join("sub3.txt");
+    fs::write(&manifest, format!("{FOO}  foo\n")).unwrap();
+
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("verify").arg("-m").arg(&manifest).arg(tmp.path());
+    cmd.assert().success().stdout("foo: OK\n");
+
+    // Named for BLAKE3, by which the same hash fails.
+    let manifest = tmp.path().join("B3SUMS");
+    fs::write(&manifest, format!("{FOO}  foo\n")).unwrap();
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.arg("verify").arg("-m").arg(&manifest).arg(tmp.path());
+    cmd.assert().failure().stdout("foo: FAILED\n");
+}